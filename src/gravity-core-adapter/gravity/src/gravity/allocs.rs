@@ -12,8 +12,8 @@ pub fn allocation_by_instruction_index(
             GravityContractInstruction::LAST_ROUND_ALLOC,
             GravityContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
         ],
-        // UpdateConsuls
-        1 => vec![
+        // UpdateConsuls, EmergencyReplace
+        1 | 7 => vec![
             GravityContractInstruction::BFT_ALLOC,
             GravityContractInstruction::LAST_ROUND_ALLOC,
             GravityContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
@@ -0,0 +1,268 @@
+use solana_client::rpc_client::RpcClient;
+use solana_program::{
+    clock::Slot,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+use gravity_misc::misc::WrappedResult;
+
+use crate::gravity::{
+    instruction::update_consuls,
+    state::{GravityContract, StrictMode},
+};
+
+/// Off-chain entry point for integrators: decodes Gravity accounts
+/// and assembles the transactions the processor expects.
+pub struct GravityClient {
+    pub rpc_client: RpcClient,
+    pub program_id: Pubkey,
+}
+
+impl GravityClient {
+    pub fn new(rpc_client: RpcClient, program_id: Pubkey) -> Self {
+        GravityClient {
+            rpc_client,
+            program_id,
+        }
+    }
+
+    pub fn fetch_contract(&self, address: &Pubkey) -> WrappedResult<GravityContract> {
+        let account = self.rpc_client.get_account(address)?;
+
+        Ok(self.decode_account(&account)?)
+    }
+
+    /// The RPC only serves the latest account state, so the slot it was observed at is returned
+    /// alongside, and `None` stands for an account that has not been created yet.
+    pub fn fetch_contract_with_slot(
+        &self,
+        address: &Pubkey,
+    ) -> WrappedResult<(Slot, Option<GravityContract>)> {
+        let response = self
+            .rpc_client
+            .get_account_with_commitment(address, self.rpc_client.commitment())?;
+
+        let gravity_contract_info = match response.value {
+            Some(account) => Some(self.decode_account(&account)?),
+            None => None,
+        };
+
+        Ok((response.context.slot, gravity_contract_info))
+    }
+
+    /// Contracts not migrated yet are refused, the processor acts on none of them.
+    fn decode_account(&self, account: &Account) -> Result<GravityContract, ProgramError> {
+        GravityContract::unpack_checked(
+            &account.data,
+            &account.owner,
+            &self.program_id,
+            StrictMode::On,
+        )
+    }
+
+    pub fn decode_contract(data: &[u8]) -> Result<GravityContract, ProgramError> {
+        let data = data
+            .get(0..GravityContract::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        GravityContract::unpack(data)
+    }
+
+    /// Rounds are advanced through `UpdateConsuls`, so the current consul set is resubmitted as is.
+    pub fn submit_update_round(
+        &self,
+        address: &Pubkey,
+        initializer: &Keypair,
+        consuls: &[&Keypair],
+        new_round: u64,
+    ) -> WrappedResult<Signature> {
+        let gravity_contract_info = self.fetch_contract(address)?;
+
+        self.send_update_consuls(
+            address,
+            &gravity_contract_info,
+            initializer,
+            consuls,
+            &gravity_contract_info.consuls,
+            new_round,
+        )
+    }
+
+    pub fn submit_update_consuls(
+        &self,
+        address: &Pubkey,
+        initializer: &Keypair,
+        consuls: &[&Keypair],
+        new_consuls: &[Pubkey],
+        current_round: u64,
+    ) -> WrappedResult<Signature> {
+        let gravity_contract_info = self.fetch_contract(address)?;
+
+        self.send_update_consuls(
+            address,
+            &gravity_contract_info,
+            initializer,
+            consuls,
+            new_consuls,
+            current_round,
+        )
+    }
+
+    fn send_update_consuls(
+        &self,
+        address: &Pubkey,
+        gravity_contract_info: &GravityContract,
+        initializer: &Keypair,
+        consuls: &[&Keypair],
+        new_consuls: &[Pubkey],
+        current_round: u64,
+    ) -> WrappedResult<Signature> {
+        let signer_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+
+        let instruction = update_consuls(
+            &self.program_id,
+            &initializer.pubkey(),
+            address,
+            &gravity_contract_info.multisig_account,
+            &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+            new_consuls,
+            current_round,
+        )?;
+
+        let mut signers = vec![initializer];
+        signers.extend_from_slice(consuls);
+
+        self.send(&[instruction], initializer, signers)
+    }
+
+    fn send(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+        signers: Vec<&Keypair>,
+    ) -> WrappedResult<Signature> {
+        let (recent_blockhash, _fee_calculator) = self.rpc_client.get_recent_blockhash()?;
+
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &signers,
+            recent_blockhash,
+        );
+
+        Ok(self.rpc_client.send_and_confirm_transaction(&transaction)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+    use solana_account_decoder::{UiAccount, UiAccountEncoding};
+    use solana_client::{mock_sender::Mocks, rpc_request::RpcRequest};
+
+    fn mocked_client(program_id: Pubkey, address: &Pubkey, account: Account) -> GravityClient {
+        let mut mocks = Mocks::default();
+        mocks.insert(
+            RpcRequest::GetAccountInfo,
+            json!({
+                "context": { "slot": 1 },
+                "value": UiAccount::encode(address, account, UiAccountEncoding::Base64, None, None),
+            }),
+        );
+
+        GravityClient::new(
+            RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks),
+            program_id,
+        )
+    }
+
+    fn mocked_client_at_slot(
+        program_id: Pubkey,
+        slot: Slot,
+        value: serde_json::Value,
+    ) -> GravityClient {
+        let mut mocks = Mocks::default();
+        mocks.insert(
+            RpcRequest::GetAccountInfo,
+            json!({
+                "context": { "slot": slot },
+                "value": value,
+            }),
+        );
+
+        GravityClient::new(
+            RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks),
+            program_id,
+        )
+    }
+
+    fn build_account(program_id: Pubkey) -> (GravityContract, Account) {
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            last_round: 10,
+            multisig_account: Pubkey::new_unique(),
+            consul_epoch: 0,
+            chain_id: 0,
+            last_update_slot: 0,
+            secp256k1_consuls: 0,
+            consul_shards: 0,
+        };
+        let mut data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut data);
+
+        let account = Account {
+            lamports: 1,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        (gravity_contract_info, account)
+    }
+
+    #[test]
+    fn test_fetch_contract() {
+        let program_id = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let (gravity_contract_info, account) = build_account(program_id);
+
+        let client = mocked_client(program_id, &address, account.clone());
+        assert_eq!(client.fetch_contract(&address).unwrap(), gravity_contract_info);
+
+        let client = mocked_client(Pubkey::new_unique(), &address, account);
+        assert!(client.fetch_contract(&address).is_err());
+    }
+
+    #[test]
+    fn test_fetch_contract_with_slot() {
+        let program_id = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let (gravity_contract_info, account) = build_account(program_id);
+
+        let client = mocked_client_at_slot(
+            program_id,
+            42,
+            json!(UiAccount::encode(&address, account, UiAccountEncoding::Base64, None, None)),
+        );
+        assert_eq!(
+            client.fetch_contract_with_slot(&address).unwrap(),
+            (42, Some(gravity_contract_info))
+        );
+
+        let client = mocked_client_at_slot(program_id, 3, serde_json::Value::Null);
+        assert_eq!(client.fetch_contract_with_slot(&address).unwrap(), (3, None));
+    }
+}
@@ -19,6 +19,124 @@ pub enum GravityError {
 
     #[error("Invalid instruction index")]
     InvalidInstructionIndex,
+
+    #[error("Consul has already signed the pending update")]
+    DuplicateSignature,
+
+    #[error("Signer is not a consul")]
+    UnknownConsul,
+
+    #[error("Pending update is already finalized")]
+    PendingUpdateFinalized,
+
+    #[error("Pending update has not expired yet")]
+    PendingUpdateNotExpired,
+
+    #[error("Nebula is already registered")]
+    NebulaAlreadyRegistered,
+
+    #[error("Nebula registry is full")]
+    NebulaRegistryFull,
+
+    #[error("Reserved bytes are not zeroed")]
+    NonZeroReserved,
+
+    #[error("Consul set contains a duplicate")]
+    DuplicateConsul,
+
+    #[error("Account holds another state type")]
+    WrongAccountType,
+
+    #[error("Signer can't be recovered from the signature")]
+    InvalidSignature,
+
+    #[error("Consul key is not a valid ed25519 point")]
+    InvalidConsulKey,
+
+    #[error("Message is addressed to another chain")]
+    ChainMismatch,
+
+    #[error("Consul set is smaller than the minimum")]
+    TooFewConsuls,
+
+    #[error("Signature bundle indices are not strictly increasing")]
+    NonCanonicalBundle,
+
+    #[error("Account has already been written to")]
+    AccountAlreadyUsed,
+
+    #[error("No round is left past the last one")]
+    RoundExhausted,
+
+    #[error("Initializer is not on the allowlist")]
+    InitializerNotAllowed,
+
+    #[error("Update was signed past its deadline")]
+    UpdateExpired,
+
+    // no longer returned: a full rotation log drops its oldest entry instead
+    #[error("Rotation log has no room for another entry")]
+    RotationLogFull,
+
+    #[error("Consul shards are out of order or incomplete")]
+    InvalidShardSequence,
+
+    #[error("Consul set is larger than the contract layout has room for")]
+    TooManyConsuls,
+
+    #[error("Too few consuls are online to reach the quorum")]
+    QuorumUnreachable,
+
+    #[error("Fewer consuls signed than an emergency replacement requires")]
+    BelowEmergencyThreshold,
+
+    #[error("Signed message commits to another round than the update")]
+    RoundMismatch,
+
+    #[error("Signature scalar is not canonically reduced")]
+    MalleableSignature,
+
+    #[error("Gravity contract has not been initialized")]
+    NotInitialized,
+
+    #[error("Initializer is also one of the consuls")]
+    InitializerConsulOverlap,
+
+    #[error("Account carries a Gravity discriminator but another program owns it")]
+    SpoofedAccount,
+
+    #[error("Update was signed under an earlier consul epoch")]
+    StaleEpoch,
+
+    #[error("Consul set changed across an upgrade that doesn't rotate it")]
+    UnexpectedConsulChange,
+
+    #[error("Referenced account is not a Gravity contract")]
+    InvalidGravityReference,
+
+    #[error("Data source has no subscription registered")]
+    UnknownDataSource,
+
+    #[error("Consul epoch can't be incremented any further")]
+    EpochExhausted,
+
+    #[error("Pending update collects signatures for another update")]
+    PendingUpdateMismatch,
+
+    #[error("Key types don't match the consul set")]
+    KeyTypeMismatch,
+
+    #[error("Rotation log is not the one derived for the contract")]
+    RotationLogMismatch,
+
+    #[error("Target layout version has no room for data the contract holds")]
+    DowngradeLosesData,
+
+    #[error("Contract of the layout before the discriminator refused in strict mode")]
+    LegacyLayoutRejected,
+
+    #[error("Transaction budget leaves no room for a consul change")]
+    EmptyTxBudget,
 }
 
 impl From<GravityError> for ProgramError {
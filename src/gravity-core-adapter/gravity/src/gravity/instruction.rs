@@ -2,9 +2,13 @@
 
 
 use arrayref::array_ref;
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
+    secp256k1_program, system_program, sysvar,
 };
 
 
@@ -13,19 +17,78 @@ use gravity_misc::validation::{
 };
 
 use crate::gravity::allocs::allocation_by_instruction_index;
-use crate::gravity::error::GravityError::InvalidInstruction;
+use crate::gravity::error::GravityError::{self, InvalidInstruction};
+use crate::gravity::signature::{
+    ConsulKeyType, EthAddress, MixedSignature, RecoverableSignature, SignatureBundle,
+};
+use crate::gravity::state::{ConsulShard, ConsulSignature, GravityContract, RotationLog};
+use crate::gravity::verifier::{
+    ed25519_program, CURRENT_INSTRUCTION, ED25519_OFFSETS_LEN, SECP256K1_OFFSETS_LEN,
+};
 
 
 pub enum GravityContractInstruction {
+    /// `chain_id` trails the consuls, and reads as 0 from the data of clients that predate it.
     InitContract {
         new_consuls: Vec<Pubkey>,
         current_round: u64,
         bft: u8,
+        chain_id: u64,
     },
     UpdateConsuls {
         new_consuls: Vec<Pubkey>,
         current_round: u64,
     },
+    ResetPendingUpdate,
+    RegisterNebula {
+        nebula: Pubkey,
+    },
+    TransferOwnership {
+        new_owner: Pubkey,
+    },
+    /// Carries a packed contract that is validated against the current one but not applied.
+    ProposeConfig {
+        config: GravityContract,
+    },
+    /// `UpdateConsuls` signed by `GravityContract::emergency_threshold` current consuls
+    /// rather than `bft`, for replacing a compromised set outright.
+    EmergencyReplace {
+        new_consuls: Vec<Pubkey>,
+        current_round: u64,
+    },
+    /// Round updates applied in order within one transaction, each carrying the consul
+    /// signatures over its `round_update_message`, for catching up after downtime.
+    BatchUpdateRounds {
+        updates: Vec<(u64, SignatureBundle)>,
+    },
+    /// `consul`'s signature over the `PendingUpdate` rotating to `new_consuls` at
+    /// `current_round`, starting the accumulation on a blank account.
+    SignPendingUpdate {
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
+        consul: Pubkey,
+        signature: ConsulSignature,
+    },
+    /// The signature scheme of each current consul, by position, set by the owner.
+    SetConsulKeyTypes {
+        key_types: Vec<ConsulKeyType>,
+    },
+    /// A round update whose consul signatures over its `round_update_message` are each in the
+    /// scheme the contract records for their consul.
+    UpdateRoundMixed {
+        round: u64,
+        signatures: Vec<(u8, MixedSignature)>,
+    },
+    /// Moves a contract kept in an account of an earlier layout, too small for the current
+    /// one, over to a blank account of it, on behalf of its initializer, closing the old one.
+    Migrate,
+    /// Writes shard `index` out of `shard_count` of the contract's consul set to its
+    /// `ConsulShard::address`, creating the account on first use, on behalf of the initializer.
+    SetConsulShard {
+        index: u8,
+        shard_count: u8,
+        consuls: Vec<Pubkey>,
+    },
 }
 
 impl GravityContractInstruction {
@@ -49,14 +112,16 @@ impl GravityContractInstruction {
                 })?;
 
                 let initial_consuls = retrieve_consuls(rest, ranges[2].clone(), bft)?;
+                let chain_id = unpack_chain_id(rest, ranges[2].end)?;
 
                 Self::InitContract {
                     new_consuls: initial_consuls,
                     current_round,
                     bft,
+                    chain_id,
                 }
             }
-            1 => {
+            1 | 7 => {
                 let bft = extract_from_range(rest, 0..1, |x: &[u8]| {
                     u8::from_le_bytes(*array_ref![x, 0, 1])
                 })?;
@@ -68,12 +133,1013 @@ impl GravityContractInstruction {
                 })?;
                 let new_consuls = retrieve_consuls(rest, ranges[2].clone(), bft)?;
 
-                Self::UpdateConsuls {
-                    new_consuls,
+                if *tag == 1 {
+                    Self::UpdateConsuls {
+                        new_consuls,
+                        current_round,
+                    }
+                } else {
+                    Self::EmergencyReplace {
+                        new_consuls,
+                        current_round,
+                    }
+                }
+            }
+            2 => Self::ResetPendingUpdate,
+            3 => {
+                let nebula =
+                    extract_from_range(rest, 0..Self::PUBKEY_ALLOC, |x: &[u8]| Pubkey::new(x))?;
+
+                Self::RegisterNebula { nebula }
+            }
+            4 => {
+                let new_owner =
+                    extract_from_range(rest, 0..Self::PUBKEY_ALLOC, |x: &[u8]| Pubkey::new(x))?;
+
+                Self::TransferOwnership { new_owner }
+            }
+            5 => {
+                if rest.len() != GravityContract::LEN {
+                    return Err(InvalidInstruction.into());
+                }
+                let config = GravityContract::unpack_from_slice(rest)?;
+
+                Self::ProposeConfig { config }
+            }
+            // 6 took the consuls from a data account, which `MAX_CONSULS` never lets outgrow
+            // the instruction; it stays unassigned so no older instruction decodes as another
+            8 => {
+                let updates = Vec::<(u64, SignatureBundle)>::try_from_slice(rest)
+                    .map_err(|_| InvalidInstruction)?;
+
+                Self::BatchUpdateRounds { updates }
+            }
+            9 => {
+                let (current_round, new_consuls, consul, signature) =
+                    <(u64, Vec<Pubkey>, Pubkey, ConsulSignature)>::try_from_slice(rest)
+                        .map_err(|_| InvalidInstruction)?;
+
+                Self::SignPendingUpdate {
                     current_round,
+                    new_consuls,
+                    consul,
+                    signature,
+                }
+            }
+            10 => {
+                let key_types =
+                    Vec::<ConsulKeyType>::try_from_slice(rest).map_err(|_| InvalidInstruction)?;
+
+                Self::SetConsulKeyTypes { key_types }
+            }
+            11 => {
+                let (round, signatures) = <(u64, Vec<(u8, MixedSignature)>)>::try_from_slice(rest)
+                    .map_err(|_| InvalidInstruction)?;
+
+                Self::UpdateRoundMixed { round, signatures }
+            }
+            12 => Self::Migrate,
+            13 => {
+                let (index, shard_count, consuls) = <(u8, u8, Vec<Pubkey>)>::try_from_slice(rest)
+                    .map_err(|_| InvalidInstruction)?;
+
+                Self::SetConsulShard {
+                    index,
+                    shard_count,
+                    consuls,
                 }
             }
             _ => return Err(InvalidInstruction.into()),
         })
     }
 }
+
+impl GravityContractInstruction {
+    /// Whether the instruction acts on an initialized contract; only the inits are accepted
+    /// by a blank one.
+    pub fn requires_initialized(&self) -> bool {
+        !matches!(self, Self::InitContract { .. })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let (tag, bft, new_consuls, current_round) = match self {
+            Self::InitContract {
+                new_consuls,
+                current_round,
+                bft,
+                chain_id,
+            } => {
+                let mut buf = Self::pack_consuls(0, *bft, new_consuls, current_round);
+                buf.extend_from_slice(&chain_id.to_le_bytes());
+                return buf;
+            }
+            Self::UpdateConsuls {
+                new_consuls,
+                current_round,
+            } => (1, new_consuls.len() as u8, new_consuls, current_round),
+            Self::EmergencyReplace {
+                new_consuls,
+                current_round,
+            } => (7, new_consuls.len() as u8, new_consuls, current_round),
+            Self::ResetPendingUpdate => return vec![2],
+            Self::RegisterNebula { nebula } => {
+                let mut buf = vec![3];
+                buf.extend_from_slice(nebula.as_ref());
+                return buf;
+            }
+            Self::TransferOwnership { new_owner } => {
+                let mut buf = vec![4];
+                buf.extend_from_slice(new_owner.as_ref());
+                return buf;
+            }
+            Self::ProposeConfig { config } => {
+                let mut buf = vec![0; 1 + GravityContract::LEN];
+                buf[0] = 5;
+                config.pack_into_slice(&mut buf[1..]);
+                return buf;
+            }
+            Self::BatchUpdateRounds { updates } => {
+                let mut buf = vec![8];
+                buf.extend_from_slice(&updates.try_to_vec().unwrap());
+                return buf;
+            }
+            Self::SignPendingUpdate {
+                current_round,
+                new_consuls,
+                consul,
+                signature,
+            } => {
+                let mut buf = vec![9];
+                buf.extend_from_slice(&current_round.to_le_bytes());
+                buf.extend_from_slice(&new_consuls.try_to_vec().unwrap());
+                buf.extend_from_slice(consul.as_ref());
+                buf.extend_from_slice(signature);
+                return buf;
+            }
+            Self::SetConsulKeyTypes { key_types } => {
+                let mut buf = vec![10];
+                buf.extend_from_slice(&key_types.try_to_vec().unwrap());
+                return buf;
+            }
+            Self::UpdateRoundMixed { round, signatures } => {
+                let mut buf = vec![11];
+                buf.extend_from_slice(&round.to_le_bytes());
+                buf.extend_from_slice(&signatures.try_to_vec().unwrap());
+                return buf;
+            }
+            Self::Migrate => return vec![12],
+            Self::SetConsulShard {
+                index,
+                shard_count,
+                consuls,
+            } => {
+                let mut buf = vec![13, *index, *shard_count];
+                buf.extend_from_slice(&consuls.try_to_vec().unwrap());
+                return buf;
+            }
+        };
+
+        Self::pack_consuls(tag, bft, new_consuls, current_round)
+    }
+
+    fn pack_consuls(tag: u8, bft: u8, new_consuls: &[Pubkey], current_round: &u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            1 + Self::BFT_ALLOC + Self::LAST_ROUND_ALLOC + Self::PUBKEY_ALLOC * new_consuls.len(),
+        );
+        buf.push(tag);
+        buf.push(bft);
+        buf.extend_from_slice(&current_round.to_le_bytes());
+        for consul in new_consuls.iter() {
+            buf.extend_from_slice(consul.as_ref());
+        }
+        buf
+    }
+}
+
+/// The chain id an init carries from `offset` on, 0 when it ends there.
+fn unpack_chain_id(rest: &[u8], offset: usize) -> Result<u64, ProgramError> {
+    match rest.get(offset..) {
+        Some([]) => Ok(0),
+        Some(chain_id) if chain_id.len() == 8 => {
+            Ok(u64::from_le_bytes(*array_ref![chain_id, 0, 8]))
+        }
+        _ => Err(InvalidInstruction.into()),
+    }
+}
+
+impl GravityContract {
+    /// `UpdateConsuls` giving the contract at `target_key`, currently `target`, the consuls of
+    /// this one. The target moves to its own next round and keeps its epoch; `UpdateConsuls`
+    /// carries no `bft` either, so a config differing in it can't be reproduced this way.
+    pub fn to_update_instruction(
+        &self,
+        program_id: &Pubkey,
+        target_key: &Pubkey,
+        target: &GravityContract,
+        signer_pubkeys: &[&Pubkey],
+    ) -> Result<Instruction, ProgramError> {
+        if self.bft != target.bft {
+            return Err(GravityError::InvalidBFTCount.into());
+        }
+
+        update_consuls(
+            program_id,
+            &target.initializer_pubkey,
+            target_key,
+            &target.multisig_account,
+            signer_pubkeys,
+            &self.consuls,
+            target.last_round.saturating_add(1),
+        )
+    }
+}
+
+/// `chain_id` is the one the contract's consuls sign for, see `signature::signed_message`; it
+/// can't be changed once the contract is initialized.
+#[allow(clippy::too_many_arguments)]
+pub fn init_contract(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    multisig_account: &Pubkey,
+    new_consuls: &[Pubkey],
+    current_round: u64,
+    bft: u8,
+    chain_id: u64,
+) -> Result<Instruction, ProgramError> {
+    if new_consuls.len() > u8::MAX as usize {
+        return Err(InvalidInstruction.into());
+    }
+
+    let data = GravityContractInstruction::InitContract {
+        new_consuls: new_consuls.to_vec(),
+        current_round,
+        bft,
+        chain_id,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*gravity_contract_account, false),
+        AccountMeta::new(*multisig_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_consuls(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    multisig_account: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    new_consuls: &[Pubkey],
+    current_round: u64,
+) -> Result<Instruction, ProgramError> {
+    if new_consuls.len() > u8::MAX as usize {
+        return Err(InvalidInstruction.into());
+    }
+
+    let data = GravityContractInstruction::UpdateConsuls {
+        new_consuls: new_consuls.to_vec(),
+        current_round,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(6 + signer_pubkeys.len());
+    // the initializer pays for the rotation log when the first rotation creates it
+    accounts.push(AccountMeta::new(*initializer, true));
+    accounts.push(AccountMeta::new(*gravity_contract_account, false));
+    accounts.push(AccountMeta::new_readonly(*multisig_account, false));
+
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    let (rotation_log_account, _) = RotationLog::address(gravity_contract_account, program_id);
+    accounts.push(AccountMeta::new(rotation_log_account, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn emergency_replace(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    multisig_account: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    new_consuls: &[Pubkey],
+    current_round: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = update_consuls(
+        program_id,
+        initializer,
+        gravity_contract_account,
+        multisig_account,
+        signer_pubkeys,
+        new_consuls,
+        current_round,
+    )?;
+
+    instruction.data = GravityContractInstruction::EmergencyReplace {
+        new_consuls: new_consuls.to_vec(),
+        current_round,
+    }
+    .pack();
+    // the multisig is handed to the new consuls along with the contract
+    instruction.accounts[2] = AccountMeta::new(*multisig_account, false);
+
+    Ok(instruction)
+}
+
+pub fn batch_update_rounds(
+    program_id: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    updates: Vec<(u64, SignatureBundle)>,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::BatchUpdateRounds { updates }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*gravity_contract_account, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Has the ed25519 program check `signatures` over `message`. It goes into the transaction
+/// ahead of the instruction relying on the signatures, which finds them through the
+/// instructions sysvar.
+pub fn verify_ed25519_signatures(
+    message: &[u8],
+    signatures: &[(Pubkey, ConsulSignature)],
+) -> Instruction {
+    let offsets_len = 2 + signatures.len() * ED25519_OFFSETS_LEN;
+    let message_offset = offsets_len + signatures.len() * (64 + 32);
+
+    let mut data = Vec::with_capacity(message_offset + message.len());
+    data.push(signatures.len() as u8);
+    data.push(0);
+    for i in 0..signatures.len() {
+        let signature_offset = offsets_len + i * (64 + 32);
+        for field in [
+            signature_offset,
+            CURRENT_INSTRUCTION as usize,
+            signature_offset + 64,
+            CURRENT_INSTRUCTION as usize,
+            message_offset,
+            message.len(),
+            CURRENT_INSTRUCTION as usize,
+        ]
+        .iter()
+        {
+            data.extend_from_slice(&(*field as u16).to_le_bytes());
+        }
+    }
+    for (signer, signature) in signatures.iter() {
+        data.extend_from_slice(signature);
+        data.extend_from_slice(signer.as_ref());
+    }
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Has the secp256k1 program check that each signature over `message` recovers to the address
+/// it comes with. The program finds the data by position in the transaction, so
+/// `instruction_index` has to be where this instruction ends up.
+pub fn verify_secp256k1_signatures(
+    instruction_index: u8,
+    message: &[u8],
+    signatures: &[(EthAddress, RecoverableSignature)],
+) -> Instruction {
+    let offsets_len = 1 + signatures.len() * SECP256K1_OFFSETS_LEN;
+    let message_offset = offsets_len + signatures.len() * (20 + 65);
+
+    let mut data = Vec::with_capacity(message_offset + message.len());
+    data.push(signatures.len() as u8);
+    for i in 0..signatures.len() {
+        let address_offset = offsets_len + i * (20 + 65);
+        data.extend_from_slice(&((address_offset + 20) as u16).to_le_bytes());
+        data.push(instruction_index);
+        data.extend_from_slice(&(address_offset as u16).to_le_bytes());
+        data.push(instruction_index);
+        data.extend_from_slice(&(message_offset as u16).to_le_bytes());
+        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        data.push(instruction_index);
+    }
+    for (address, signature) in signatures.iter() {
+        data.extend_from_slice(address);
+        data.extend_from_slice(signature);
+    }
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: secp256k1_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Adds `consul`'s signature to the pending update, which the ed25519 program has to check
+/// earlier in the transaction, see `verify_ed25519_signatures`.
+pub fn sign_pending_update(
+    program_id: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    pending_update_account: &Pubkey,
+    new_consuls: &[Pubkey],
+    current_round: u64,
+    consul: &Pubkey,
+    signature: ConsulSignature,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::SignPendingUpdate {
+        current_round,
+        new_consuls: new_consuls.to_vec(),
+        consul: *consul,
+        signature,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*gravity_contract_account, false),
+        AccountMeta::new(*pending_update_account, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Sets the scheme each consul signs with, `key_types` listing them by position.
+pub fn set_consul_key_types(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    key_types: &[ConsulKeyType],
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::SetConsulKeyTypes {
+        key_types: key_types.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*gravity_contract_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Moves the contract to `round` on signatures of both schemes. The ed25519 and secp256k1
+/// programs have to check them earlier in the transaction, see `verify_ed25519_signatures`
+/// and `verify_secp256k1_signatures`.
+pub fn update_round_mixed(
+    program_id: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    round: u64,
+    signatures: Vec<(u8, MixedSignature)>,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::UpdateRoundMixed { round, signatures }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*gravity_contract_account, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Moves the contract in `legacy_contract_account` over to `gravity_contract_account`, a
+/// blank account of `GravityContract::LEN` owned by the program. The old account's lamports
+/// go to the initializer.
+pub fn migrate(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    legacy_contract_account: &Pubkey,
+    gravity_contract_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::Migrate.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*initializer, true),
+        AccountMeta::new(*legacy_contract_account, false),
+        AccountMeta::new(*gravity_contract_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Writes shard `index` out of `shard_count` of the consul set, `consuls`, to the account
+/// `ConsulShard::address` derives for it. The initializer pays for creating it.
+pub fn set_consul_shard(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    index: u8,
+    shard_count: u8,
+    consuls: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::SetConsulShard {
+        index,
+        shard_count,
+        consuls: consuls.to_vec(),
+    }
+    .pack();
+
+    let (consul_shard_account, _) =
+        ConsulShard::address(gravity_contract_account, index, program_id);
+    let accounts = vec![
+        AccountMeta::new(*initializer, true),
+        AccountMeta::new(*gravity_contract_account, false),
+        AccountMeta::new(consul_shard_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn reset_pending_update(
+    program_id: &Pubkey,
+    pending_update_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::ResetPendingUpdate.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*pending_update_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn register_nebula(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    nebula_registry_account: &Pubkey,
+    nebula: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::RegisterNebula { nebula: *nebula }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new_readonly(*gravity_contract_account, false),
+        AccountMeta::new(*nebula_registry_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn transfer_ownership(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    new_owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::TransferOwnership {
+        new_owner: *new_owner,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*gravity_contract_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn propose_config(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract_account: &Pubkey,
+    config: &GravityContract,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::ProposeConfig {
+        config: config.clone(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new_readonly(*gravity_contract_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Largest serialized transaction that fits into a packet.
+pub const MAX_TRANSACTION_SIZE: usize = 1232;
+
+fn compact_len_size(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+/// Serialized size of a transaction carrying a single `update_consuls` instruction paid by
+/// the initializer, so that clients can tell upfront whether it fits `MAX_TRANSACTION_SIZE`.
+pub fn estimate_update_consuls_tx_size(consul_count: usize, sig_count: usize) -> usize {
+    // the initializer pays and signs alongside the consuls
+    let signatures = 1 + sig_count;
+    // initializer, contract, multisig, consul signers, rotation log, clock, system program
+    // and the program itself
+    let account_keys = 7 + sig_count;
+    let instruction_accounts = 6 + sig_count;
+    let data_len = 1
+        + GravityContractInstruction::BFT_ALLOC
+        + GravityContractInstruction::LAST_ROUND_ALLOC
+        + GravityContractInstruction::PUBKEY_ALLOC * consul_count;
+
+    let instruction = 1
+        + compact_len_size(instruction_accounts)
+        + instruction_accounts
+        + compact_len_size(data_len)
+        + data_len;
+
+    compact_len_size(signatures)
+        + 64 * signatures
+        + 3
+        + compact_len_size(account_keys)
+        + 32 * account_keys
+        + 32
+        + compact_len_size(1)
+        + instruction
+}
+
+/// Fewest transactions replacing a set of `current` consuls with `target` entirely new ones,
+/// removing every old consul and adding every new one at up to `max_ops_per_tx` operations
+/// a transaction. A lower bound on `plan_consul_update`, which may need extra steps to keep
+/// the set within its limits. Fails, as `plan_consul_update` does, if `max_ops_per_tx` is zero.
+// `usize::div_ceil` needs a newer compiler than the program is built with
+#[allow(clippy::manual_div_ceil)]
+pub fn plan_rotation_tx_count(
+    current: usize,
+    target: usize,
+    max_ops_per_tx: usize,
+) -> Result<usize, GravityError> {
+    if max_ops_per_tx == 0 {
+        return Err(GravityError::EmptyTxBudget);
+    }
+
+    Ok((current + target + max_ops_per_tx - 1) / max_ops_per_tx)
+}
+
+/// Breaks moving from `current` to `target` into `UpdateConsuls` steps starting at `next_round`,
+/// each adding or removing at most `max_per_tx` consuls, for rotations that shouldn't replace
+/// too much of the set at once. Every step leaves between `bft` and `MAX_CONSULS` consuls.
+pub fn plan_consul_update(
+    current: &[Pubkey],
+    target: &[Pubkey],
+    bft: u8,
+    max_per_tx: usize,
+    next_round: u64,
+) -> Result<Vec<GravityContractInstruction>, GravityError> {
+    let bft = bft as usize;
+    if max_per_tx == 0 {
+        return Err(GravityError::EmptyTxBudget);
+    }
+    if target.len() < bft || target.len() > GravityContract::MAX_CONSULS {
+        return Err(GravityError::InvalidBFTCount);
+    }
+
+    let mut consuls = current.to_vec();
+    let mut steps = vec![];
+    let mut round = next_round;
+
+    let is_done = |consuls: &[Pubkey]| {
+        consuls.len() == target.len() && target.iter().all(|consul| consuls.contains(consul))
+    };
+
+    while !is_done(&consuls) {
+        let before = consuls.clone();
+        let mut budget = max_per_tx;
+        while budget > 0 {
+            let to_add = target
+                .iter()
+                .find(|consul| !consuls.contains(consul))
+                .copied();
+            let to_remove = consuls.iter().position(|consul| !target.contains(consul));
+
+            match (to_add, to_remove) {
+                (None, None) => break,
+                (Some(consul), _) if consuls.len() < target.len() => consuls.push(consul),
+                (_, Some(position)) if consuls.len() > target.len() => {
+                    consuls.remove(position);
+                }
+                (Some(consul), Some(position)) if budget >= 2 => {
+                    consuls.remove(position);
+                    consuls.push(consul);
+                    budget -= 1;
+                }
+                // no room left for a swap, so the set grows or shrinks by one for a step
+                (Some(consul), _) if consuls.len() < GravityContract::MAX_CONSULS => {
+                    consuls.push(consul)
+                }
+                (_, Some(position)) if consuls.len() > bft => {
+                    consuls.remove(position);
+                }
+                _ => return Err(GravityError::InvalidBFTCount),
+            }
+            budget -= 1;
+        }
+
+        // duplicates on either side leave nothing that can be done
+        if consuls == before {
+            return Err(GravityError::DuplicateConsul);
+        }
+
+        steps.push(GravityContractInstruction::UpdateConsuls {
+            new_consuls: if is_done(&consuls) {
+                target.to_vec()
+            } else {
+                consuls.clone()
+            },
+            current_round: round,
+        });
+        round += 1;
+    }
+
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use solana_sdk::{hash::Hash, message::Message, transaction::Transaction};
+
+    fn assert_plan(current: &[Pubkey], target: &[Pubkey], bft: u8, max_per_tx: usize) {
+        let steps = plan_consul_update(current, target, bft, max_per_tx, 8).unwrap();
+
+        let mut previous = current.to_vec();
+        for (i, step) in steps.iter().enumerate() {
+            let (new_consuls, current_round) = match step {
+                GravityContractInstruction::UpdateConsuls {
+                    new_consuls,
+                    current_round,
+                } => (new_consuls, *current_round),
+                _ => panic!("unexpected instruction"),
+            };
+            assert_eq!(current_round, 8 + i as u64);
+
+            let intermediate = GravityContract {
+                bft,
+                consuls: new_consuls.clone(),
+                ..GravityContract::default()
+            };
+            assert!(intermediate.validate().is_ok());
+            assert!(new_consuls.len() <= GravityContract::MAX_CONSULS);
+
+            let changes = new_consuls.iter().filter(|c| !previous.contains(c)).count()
+                + previous.iter().filter(|c| !new_consuls.contains(c)).count();
+            assert!(changes <= max_per_tx);
+
+            previous = new_consuls.clone();
+        }
+
+        assert_eq!(previous, target);
+    }
+
+    #[test]
+    fn test_requires_initialized() {
+        let init = GravityContractInstruction::InitContract {
+            new_consuls: vec![Pubkey::new_unique()],
+            current_round: 0,
+            bft: 1,
+            chain_id: 0,
+        };
+        assert!(!init.requires_initialized());
+
+        let update = GravityContractInstruction::UpdateConsuls {
+            new_consuls: vec![Pubkey::new_unique()],
+            current_round: 1,
+        };
+        assert!(update.requires_initialized());
+        assert!(
+            GravityContractInstruction::BatchUpdateRounds { updates: vec![] }
+                .requires_initialized()
+        );
+    }
+
+    #[test]
+    fn test_init_chain_id() {
+        let consuls = vec![Pubkey::new_unique()];
+        let data = GravityContractInstruction::InitContract {
+            new_consuls: consuls.clone(),
+            current_round: 0,
+            bft: 1,
+            chain_id: 7,
+        }
+        .pack();
+        assert!(matches!(
+            GravityContractInstruction::unpack(&data).unwrap(),
+            GravityContractInstruction::InitContract { new_consuls, chain_id: 7, .. } if new_consuls == consuls
+        ));
+        // data packed before the chain id was added ends with the consuls
+        assert!(matches!(
+            GravityContractInstruction::unpack(&data[..data.len() - 8]).unwrap(),
+            GravityContractInstruction::InitContract { chain_id: 0, .. }
+        ));
+        assert_eq!(
+            GravityContractInstruction::unpack(&data[..data.len() - 1]).err(),
+            Some(InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_to_update_instruction() {
+        let program_id = Pubkey::new_unique();
+        let target_key = Pubkey::new_unique();
+        let build_contract = |consul_count| GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls: (0..consul_count).map(|_| Pubkey::new_unique()).collect(),
+            last_round: 7,
+            multisig_account: Pubkey::new_unique(),
+            ..GravityContract::default()
+        };
+        let source = GravityContract {
+            last_round: 100,
+            consul_epoch: 4,
+            ..build_contract(4)
+        };
+        let target = build_contract(3);
+        let signers: Vec<&Pubkey> = target.consuls[..2].iter().collect();
+
+        let instruction = source
+            .to_update_instruction(&program_id, &target_key, &target, &signers)
+            .unwrap();
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts[0].pubkey, target.initializer_pubkey);
+        assert_eq!(instruction.accounts[1].pubkey, target_key);
+        assert_eq!(instruction.accounts[2].pubkey, target.multisig_account);
+
+        match GravityContractInstruction::unpack(&instruction.data).unwrap() {
+            GravityContractInstruction::UpdateConsuls {
+                new_consuls,
+                current_round,
+            } => {
+                assert_eq!(new_consuls, source.consuls);
+                // the round comes from the target, not the source
+                assert_eq!(current_round, target.last_round + 1);
+            }
+            _ => panic!("unexpected instruction"),
+        }
+
+        let stricter = GravityContract { bft: 3, ..source };
+        assert_eq!(
+            stricter
+                .to_update_instruction(&program_id, &target_key, &target, &signers)
+                .unwrap_err(),
+            GravityError::InvalidBFTCount.into()
+        );
+    }
+
+    #[test]
+    fn test_plan_consul_update() {
+        let keys: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+
+        // replaced outright, grown and shrunk
+        assert_plan(&keys[0..3], &keys[3..6], 2, 2);
+        assert_plan(&keys[0..2], &keys[1..6], 2, 3);
+        assert_plan(&keys[0..6], &keys[4..7], 3, 2);
+        // one change per step has to pass through a larger set to swap
+        assert_plan(&keys[0..3], &keys[2..5], 3, 1);
+
+        // a full set at its threshold can't swap one consul at a time
+        assert!(matches!(
+            plan_consul_update(&keys[0..6], &keys[1..7], 6, 1, 8),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(matches!(
+            plan_consul_update(&keys[0..3], &keys[3..4], 2, 2, 8),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(plan_consul_update(&keys[0..3], &keys[0..3], 2, 1, 8)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_plan_rotation_tx_count() {
+        // a swap is a removal and an addition
+        assert_eq!(plan_rotation_tx_count(3, 3, 2).unwrap(), 3);
+        assert_eq!(plan_rotation_tx_count(3, 3, 6).unwrap(), 1);
+        assert_eq!(plan_rotation_tx_count(3, 3, 4).unwrap(), 2);
+
+        assert_eq!(plan_rotation_tx_count(2, 5, 2).unwrap(), 4);
+        assert_eq!(plan_rotation_tx_count(6, 3, 3).unwrap(), 3);
+        assert_eq!(plan_rotation_tx_count(0, 0, 1).unwrap(), 0);
+
+        let keys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        let steps = plan_consul_update(&keys[0..3], &keys[3..6], 2, 2, 8).unwrap();
+        assert!(steps.len() >= plan_rotation_tx_count(3, 3, 2).unwrap());
+    }
+
+    #[test]
+    fn test_plan_empty_tx_budget() {
+        let keys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        assert!(matches!(
+            plan_rotation_tx_count(3, 3, 0),
+            Err(GravityError::EmptyTxBudget)
+        ));
+        assert!(matches!(
+            plan_consul_update(&keys[0..3], &keys[3..6], 2, 0, 8),
+            Err(GravityError::EmptyTxBudget)
+        ));
+        // even with nothing to change
+        assert!(matches!(
+            plan_consul_update(&keys[0..3], &keys[0..3], 2, 0, 8),
+            Err(GravityError::EmptyTxBudget)
+        ));
+    }
+
+    #[test]
+    fn test_estimate_update_consuls_tx_size() {
+        let initializer = Pubkey::new_unique();
+        let signer_pubkeys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let new_consuls: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+
+        let instruction = update_consuls(
+            &Pubkey::new_unique(),
+            &initializer,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+            &new_consuls,
+            1,
+        )
+        .unwrap();
+
+        let mut transaction =
+            Transaction::new_unsigned(Message::new(&[instruction], Some(&initializer)));
+        transaction.message.recent_blockhash = Hash::default();
+
+        assert_eq!(
+            estimate_update_consuls_tx_size(5, 3),
+            bincode::serialize(&transaction).unwrap().len()
+        );
+        assert!(estimate_update_consuls_tx_size(5, 3) <= MAX_TRANSACTION_SIZE);
+
+        // a large set has to be split across transactions
+        assert!(estimate_update_consuls_tx_size(11, 11) > MAX_TRANSACTION_SIZE);
+    }
+}
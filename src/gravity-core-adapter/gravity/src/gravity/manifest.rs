@@ -0,0 +1,237 @@
+use std::str::FromStr;
+
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::gravity::state::GravityContract;
+
+/// Contract config a deployment is meant to have, as declared next to the infrastructure
+/// it runs on. Parsed from `key = value` lines, `#` starting a comment:
+///
+/// ```text
+/// initializer = <base58 key>
+/// bft = 2
+/// consuls = <base58 key>, <base58 key>, <base58 key>
+/// ```
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct DeploymentManifest {
+    pub initializer: Pubkey,
+    pub bft: u8,
+    pub consuls: Vec<Pubkey>,
+}
+
+/// A field of the deployed contract that differs from what the manifest declares.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ManifestMismatch {
+    Initializer {
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+    Bft {
+        expected: u8,
+        actual: u8,
+    },
+    Consuls {
+        expected: Vec<Pubkey>,
+        actual: Vec<Pubkey>,
+    },
+}
+
+impl DeploymentManifest {
+    /// Reports every diverging field rather than stopping at the first one. Consuls are
+    /// compared in order, since signature bundles address them by position.
+    pub fn verify_against(&self, contract: &GravityContract) -> Result<(), Vec<ManifestMismatch>> {
+        let mut mismatches = vec![];
+
+        if self.initializer != contract.initializer_pubkey {
+            mismatches.push(ManifestMismatch::Initializer {
+                expected: self.initializer,
+                actual: contract.initializer_pubkey,
+            });
+        }
+        if self.bft != contract.bft {
+            mismatches.push(ManifestMismatch::Bft {
+                expected: self.bft,
+                actual: contract.bft,
+            });
+        }
+        if self.consuls != contract.consuls {
+            mismatches.push(ManifestMismatch::Consuls {
+                expected: self.consuls.clone(),
+                actual: contract.consuls.clone(),
+            });
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, ProgramError> {
+    Pubkey::from_str(value).map_err(|_| {
+        msg!("Error: manifest holds an invalid key: {}", value);
+        ProgramError::InvalidArgument
+    })
+}
+
+impl FromStr for DeploymentManifest {
+    type Err = ProgramError;
+
+    /// Every field has to be given exactly once; unknown keys are rejected.
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let (mut initializer, mut bft, mut consuls) = (None, None, None);
+
+        for line in src.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => {
+                    msg!("Error: manifest line is not `key = value`: {}", line);
+                    return Err(ProgramError::InvalidArgument);
+                }
+            };
+
+            let duplicated = match key {
+                "initializer" => initializer.replace(parse_pubkey(value)?).is_some(),
+                "bft" => bft
+                    .replace(value.parse().map_err(|_| ProgramError::InvalidArgument)?)
+                    .is_some(),
+                "consuls" => consuls
+                    .replace(
+                        value
+                            .split(',')
+                            .map(|consul| parse_pubkey(consul.trim()))
+                            .collect::<Result<Vec<Pubkey>, ProgramError>>()?,
+                    )
+                    .is_some(),
+                _ => {
+                    msg!("Error: unknown manifest key: {}", key);
+                    return Err(ProgramError::InvalidArgument);
+                }
+            };
+            if duplicated {
+                msg!("Error: manifest key given twice: {}", key);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        match (initializer, bft, consuls) {
+            (Some(initializer), Some(bft), Some(consuls)) => Ok(DeploymentManifest {
+                initializer,
+                bft,
+                consuls,
+            }),
+            _ => {
+                msg!("Error: manifest needs initializer, bft and consuls");
+                Err(ProgramError::InvalidArgument)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployed() -> GravityContract {
+        GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls: vec![
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            ],
+            ..GravityContract::default()
+        }
+    }
+
+    fn manifest_source(initializer: &Pubkey, bft: u8, consuls: &[Pubkey]) -> String {
+        let consuls: Vec<String> = consuls.iter().map(|consul| consul.to_string()).collect();
+
+        format!(
+            "# mainnet gravity\ninitializer = {}\nbft = {}\n\nconsuls = {}\n",
+            initializer,
+            bft,
+            consuls.join(", ")
+        )
+    }
+
+    #[test]
+    fn test_verify_against() {
+        let contract = deployed();
+
+        let manifest: DeploymentManifest = manifest_source(
+            &contract.initializer_pubkey,
+            contract.bft,
+            &contract.consuls,
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(manifest.consuls, contract.consuls);
+        assert_eq!(manifest.verify_against(&contract), Ok(()));
+
+        let drifted = vec![
+            contract.consuls[0],
+            contract.consuls[1],
+            Pubkey::new_unique(),
+        ];
+        let manifest: DeploymentManifest =
+            manifest_source(&contract.initializer_pubkey, 3, &drifted)
+                .parse()
+                .unwrap();
+        assert_eq!(
+            manifest.verify_against(&contract),
+            Err(vec![
+                ManifestMismatch::Bft {
+                    expected: 3,
+                    actual: 2
+                },
+                ManifestMismatch::Consuls {
+                    expected: drifted.clone(),
+                    actual: contract.consuls.clone()
+                },
+            ])
+        );
+
+        // the same consuls in another order are a different deployment
+        let manifest = DeploymentManifest {
+            consuls: contract.consuls.iter().rev().copied().collect(),
+            ..manifest
+        };
+        assert_eq!(manifest.verify_against(&contract).unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let contract = deployed();
+        let source = manifest_source(&contract.initializer_pubkey, 2, &contract.consuls);
+
+        assert!(format!("{}bft = 3\n", source)
+            .parse::<DeploymentManifest>()
+            .is_err());
+        assert!(
+            format!("{}owner = {}\n", source, contract.initializer_pubkey)
+                .parse::<DeploymentManifest>()
+                .is_err()
+        );
+        assert!(source
+            .replace("bft = 2", "")
+            .parse::<DeploymentManifest>()
+            .is_err());
+        assert!(source
+            .replace("bft = 2", "bft 2")
+            .parse::<DeploymentManifest>()
+            .is_err());
+        assert!(format!("initializer = not-a-key\n{}", source)
+            .parse::<DeploymentManifest>()
+            .is_err());
+    }
+}
@@ -1,5 +1,11 @@
 pub mod allocs;
+#[cfg(feature = "rpc")]
+pub mod client;
 pub mod error;
 pub mod instruction;
+pub mod manifest;
 pub mod processor;
+pub mod signature;
 pub mod state;
+pub mod time;
+pub mod verifier;
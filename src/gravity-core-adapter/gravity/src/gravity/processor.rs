@@ -2,9 +2,13 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
 };
 
 use spl_token::{
@@ -14,11 +18,24 @@ use spl_token::{
 };
 
 use crate::gravity::{
-    error::GravityError, instruction::GravityContractInstruction, state::GravityContract,
+    error::GravityError,
+    instruction::GravityContractInstruction,
+    signature::{round_update_message, ConsulKeyType, MixedSignature, SignatureBundle},
+    state::{
+        ConsulShard, ConsulSignature, ContractEvent, EventRecord, GravityContract, NebulaRegistry,
+        PendingUpdate, RotationEntry, RotationLog,
+    },
+    time::{SysvarClock, TimeSource},
+    verifier::NativeVerifier,
 };
 
 use gravity_misc::model::PulseID;
-use gravity_misc::validation::{validate_contract_emptiness, validate_contract_non_emptiness};
+use gravity_misc::validation::{
+    is_contract_empty, validate_contract_non_emptiness, verify_account_layout, AccountRole,
+};
+
+/// Keys allowed to initialize Gravity contracts in managed deployments; empty lets anyone in.
+pub const INITIALIZER_ALLOWLIST: &[Pubkey] = &[];
 
 pub struct GravityProcessor;
 
@@ -30,11 +47,16 @@ impl GravityProcessor {
     ) -> ProgramResult {
         let instruction = GravityContractInstruction::unpack(instruction_data)?;
 
+        if instruction.requires_initialized() {
+            Self::check_initialized(&instruction, accounts)?;
+        }
+
         match instruction {
             GravityContractInstruction::InitContract {
                 new_consuls,
                 current_round,
                 bft,
+                chain_id,
             } => {
                 msg!("Instruction: Init Gravity Contract");
 
@@ -43,6 +65,7 @@ impl GravityProcessor {
                     new_consuls,
                     current_round,
                     bft,
+                    chain_id,
                     program_id,
                 )
             }
@@ -54,7 +77,113 @@ impl GravityProcessor {
 
                 Self::process_update_consuls(accounts, current_round, new_consuls, program_id)
             }
+            GravityContractInstruction::ResetPendingUpdate => {
+                msg!("Instruction: Reset Pending Update");
+
+                Self::process_reset_pending_update(accounts, program_id)
+            }
+            GravityContractInstruction::RegisterNebula { nebula } => {
+                msg!("Instruction: Register Nebula");
+
+                Self::process_register_nebula(accounts, nebula, program_id)
+            }
+            GravityContractInstruction::TransferOwnership { new_owner } => {
+                msg!("Instruction: Transfer Gravity Ownership");
+
+                Self::process_transfer_ownership(accounts, new_owner, program_id)
+            }
+            GravityContractInstruction::ProposeConfig { config } => {
+                msg!("Instruction: Propose Gravity Config");
+
+                Self::process_propose_config(accounts, config, program_id)
+            }
+            GravityContractInstruction::EmergencyReplace {
+                new_consuls,
+                current_round,
+            } => {
+                msg!("Instruction: Emergency Replace Gravity Consuls");
+
+                Self::process_emergency_replace(accounts, current_round, new_consuls, program_id)
+            }
+            GravityContractInstruction::BatchUpdateRounds { updates } => {
+                msg!("Instruction: Batch Update Gravity Rounds");
+
+                Self::process_batch_update_rounds(accounts, updates, program_id)
+            }
+            GravityContractInstruction::SignPendingUpdate {
+                current_round,
+                new_consuls,
+                consul,
+                signature,
+            } => {
+                msg!("Instruction: Sign Pending Update");
+
+                Self::process_sign_pending_update(
+                    accounts,
+                    current_round,
+                    new_consuls,
+                    consul,
+                    signature,
+                    program_id,
+                )
+            }
+            GravityContractInstruction::SetConsulKeyTypes { key_types } => {
+                msg!("Instruction: Set Consul Key Types");
+
+                Self::process_set_consul_key_types(accounts, key_types, program_id)
+            }
+            GravityContractInstruction::UpdateRoundMixed { round, signatures } => {
+                msg!("Instruction: Update Gravity Round With Mixed Signatures");
+
+                Self::process_update_round_mixed(accounts, round, signatures, program_id)
+            }
+            GravityContractInstruction::Migrate => {
+                msg!("Instruction: Migrate Gravity Contract");
+
+                Self::process_migrate(accounts, program_id)
+            }
+            GravityContractInstruction::SetConsulShard {
+                index,
+                shard_count,
+                consuls,
+            } => {
+                msg!("Instruction: Set Consul Shard");
+
+                Self::process_set_consul_shard(accounts, index, shard_count, consuls, program_id)
+            }
+        }
+    }
+
+    /// Turns an instruction away from a contract account that was allocated but never
+    /// initialized, before its handler reads the zeroed data as a contract. Missing or
+    /// misplaced accounts are left for the handler's own layout check to report.
+    fn check_initialized(
+        instruction: &GravityContractInstruction,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let position = match instruction {
+            // acts on the pending update account alone
+            GravityContractInstruction::ResetPendingUpdate => return Ok(()),
+            GravityContractInstruction::BatchUpdateRounds { .. }
+            | GravityContractInstruction::SignPendingUpdate { .. }
+            | GravityContractInstruction::UpdateRoundMixed { .. } => 0,
+            _ => 1,
+        };
+
+        if let Some(gravity_contract_account) = accounts.get(position) {
+            let data = gravity_contract_account.try_borrow_data()?;
+            if data.len() >= GravityContract::LEN
+                && is_contract_empty(&data[..GravityContract::LEN])
+            {
+                msg!(
+                    "Error: gravity contract {} is not initialized",
+                    gravity_contract_account.key
+                );
+                return Err(GravityError::NotInitialized.into());
+            }
         }
+
+        Ok(())
     }
 
     fn process_init_gravity_contract(
@@ -62,31 +191,46 @@ impl GravityProcessor {
         new_consuls: Vec<Pubkey>,
         _current_round: PulseID,
         bft: u8,
-        _program_id: &Pubkey,
+        chain_id: u64,
+        program_id: &Pubkey,
     ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+                AccountRole::writable("multisig"),
+            ],
+        )?;
+
         let account_info_iter = &mut accounts.iter();
         let initializer = next_account_info(account_info_iter)?;
 
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
         let gravity_contract_account = next_account_info(account_info_iter)?;
 
-        validate_contract_emptiness(&gravity_contract_account.try_borrow_data()?[..])?;
+        // only an account freshly created for the program can be initialized
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !is_contract_empty(&gravity_contract_account.try_borrow_data()?[..]) {
+            return Err(GravityError::AccountAlreadyUsed.into());
+        }
 
-        let mut gravity_contract_info = GravityContract::default();
+        msg!("picking multisig account");
+        let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
 
-        gravity_contract_info.initializer_pubkey = *initializer.key;
-        gravity_contract_info.bft = bft;
+        let (gravity_contract_info, records) = GravityContract::initialize(
+            *initializer.key,
+            bft,
+            new_consuls.clone(),
+            *gravity_contract_multisig_account.key,
+            chain_id,
+        )?;
 
-        gravity_contract_info.consuls = new_consuls.clone();
+        Self::check_initializer(&gravity_contract_info, INITIALIZER_ALLOWLIST)?;
 
         msg!("instantiated gravity contract");
 
-        msg!("picking multisig account");
-        let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
-
         msg!("initializing multisig program");
         MiscProcessor::process_init_multisig(
             &gravity_contract_multisig_account,
@@ -95,12 +239,23 @@ impl GravityProcessor {
         )?;
         msg!("initialized multisig program!");
 
-        gravity_contract_info.multisig_account = *gravity_contract_multisig_account.key;
+        Self::emit_events(&records);
 
-        GravityContract::pack(
-            gravity_contract_info,
-            &mut gravity_contract_account.try_borrow_mut_data()?[0..GravityContract::LEN],
-        )?;
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// Same accounts as `InitContract`, followed by the account holding the consul list.
+    fn check_initializer(
+        gravity_contract_info: &GravityContract,
+        allowlist: &[Pubkey],
+    ) -> ProgramResult {
+        if !allowlist.is_empty() && !gravity_contract_info.initializer_in(allowlist) {
+            return Err(GravityError::InitializerNotAllowed.into());
+        }
 
         Ok(())
     }
@@ -111,19 +266,27 @@ impl GravityProcessor {
         new_consuls: Vec<Pubkey>,
         program_id: &Pubkey,
     ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+                AccountRole::readonly("multisig"),
+            ],
+        )?;
+
         let account_info_iter = &mut accounts.iter();
         let initializer = next_account_info(account_info_iter)?;
 
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
 
-        let gravity_contract_account = next_account_info(account_info_iter)?;
-        
         validate_contract_non_emptiness(&gravity_contract_account.try_borrow_data()?[..])?;
 
         let mut gravity_contract_info = GravityContract::unpack(
-            &gravity_contract_account.try_borrow_data()?[0..GravityContract::LEN],
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
         )?;
         if !gravity_contract_info.is_initialized() {
             return Err(ProgramError::UninitializedAccount);
@@ -131,99 +294,2357 @@ impl GravityProcessor {
 
         msg!("picking multisig account");
         let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
+        if gravity_contract_info.multisig_account != *gravity_contract_multisig_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if gravity_contract_multisig_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
+        let signers = accounts
+            .get(3..3 + gravity_contract_info.bft as usize)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
         match MiscProcessor::validate_owner(
             program_id,
-            &gravity_contract_multisig_account.key,
+            &gravity_contract_info.multisig_account,
             &gravity_contract_multisig_account,
-            &accounts[3..3 + gravity_contract_info.bft as usize].to_vec(),
+            signers,
         ) {
             Err(_) => return Err(GravityError::InvalidBFTCount.into()),
             _ => {}
         };
 
-        if current_round <= gravity_contract_info.last_round {
-            return Err(GravityError::InputRoundMismatch.into());
-        }
+        // the rotation log, the clock sysvar and the system program follow the signers
+        let rotation_log_account = account_info_iter
+            .nth(gravity_contract_info.bft as usize)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let clock = SysvarClock::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_account = next_account_info(account_info_iter)?;
 
-        gravity_contract_info.last_round = current_round;
-        gravity_contract_info.consuls = new_consuls.clone();
+        let old_commitment = gravity_contract_info.consul_commitment();
+        let old_epoch = gravity_contract_info.consul_epoch;
+        let records = gravity_contract_info.rotate_consuls(
+            current_round,
+            new_consuls,
+            clock.current_slot(),
+        )?;
+        Self::emit_events(&records);
 
-        GravityContract::pack(
-            gravity_contract_info,
-            &mut gravity_contract_account.try_borrow_mut_data()?[0..GravityContract::LEN],
+        Self::log_rotation(
+            rotation_log_account,
+            initializer,
+            system_program_account,
+            gravity_contract_account.key,
+            RotationEntry {
+                epoch: gravity_contract_info.consul_epoch,
+                old_commitment,
+                new_commitment: gravity_contract_info.consul_commitment(),
+                initiator: *initializer.key,
+                slot: clock.current_slot(),
+            },
+            old_epoch,
+            program_id,
         )?;
 
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
         Ok(())
     }
-}
 
-pub struct MiscProcessor;
+    /// The `LEN` bytes of a gravity contract account. Accounts created before the layout
+    /// gained its discriminator are shorter and have to go through `Migrate` first.
+    fn contract_data(data: &[u8]) -> Result<&[u8], ProgramError> {
+        data.get(..GravityContract::LEN).ok_or_else(|| {
+            msg!("Error: account too short for a gravity contract, migrate it first");
+            ProgramError::InvalidAccountData
+        })
+    }
 
-impl MiscProcessor {
-    pub fn process_init_multisig(
-        multisig_account: &AccountInfo,
-        signer_pubkeys: &[Pubkey],
-        minumum_bft: u8,
+    fn contract_data_mut(data: &mut [u8]) -> Result<&mut [u8], ProgramError> {
+        data.get_mut(..GravityContract::LEN).ok_or_else(|| {
+            msg!("Error: account too short for a gravity contract, migrate it first");
+            ProgramError::InvalidAccountData
+        })
+    }
+
+    /// Writes `records` to the program log, for an indexer to assemble the contract's
+    /// `EventLog` from the transaction logs.
+    fn emit_events(records: &[EventRecord]) {
+        for record in records.iter() {
+            msg!("{}", record.to_log());
+        }
+    }
+
+    /// Appends `entry` to the rotation log of `gravity_contract` if it moved the contract past
+    /// `old_epoch`. The log has to sit at `RotationLog::address` even when nothing rotated;
+    /// the first rotation creates it there, paid for by `payer`.
+    #[allow(clippy::too_many_arguments)]
+    fn log_rotation<'a>(
+        rotation_log_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        gravity_contract: &Pubkey,
+        entry: RotationEntry,
+        old_epoch: u64,
+        program_id: &Pubkey,
     ) -> ProgramResult {
-        let mut multisig = Multisig::unpack_unchecked(&multisig_account.try_borrow_data()?)?;
+        let (rotation_log_key, bump_seed) = RotationLog::address(gravity_contract, program_id);
+        if *rotation_log_account.key != rotation_log_key {
+            return Err(GravityError::RotationLogMismatch.into());
+        }
+        if entry.epoch == old_epoch {
+            return Ok(());
+        }
 
-        if multisig.is_initialized {
-            return Err(TokenError::AlreadyInUse.into());
+        if rotation_log_account.data_is_empty() {
+            let seeds: &[&[u8]] = &[RotationLog::SEED, gravity_contract.as_ref(), &[bump_seed]];
+            Self::create_program_account(
+                rotation_log_account,
+                payer,
+                system_program_account,
+                seeds,
+                RotationLog::LEN,
+                program_id,
+            )?;
         }
 
-        multisig.m = minumum_bft;
-        multisig.n = signer_pubkeys.len() as u8;
-        if !is_valid_signer_index(multisig.n as usize) {
-            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        Self::append_rotation(rotation_log_account, gravity_contract, entry, program_id)
+    }
+
+    /// Creates the rent exempt program account of `len` bytes at the address `seeds` derive,
+    /// `payer` funding it. Funded, allocated and assigned one by one, so lamports sent to the
+    /// address beforehand can't keep the account from being created.
+    fn create_program_account<'a>(
+        account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        seeds: &[&[u8]],
+        len: usize,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let accounts = [
+            payer.clone(),
+            account.clone(),
+            system_program_account.clone(),
+        ];
+        let shortfall = Rent::get()?
+            .minimum_balance(len)
+            .saturating_sub(account.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, shortfall),
+                &accounts,
+            )?;
         }
-        if !is_valid_signer_index(multisig.m as usize) {
-            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        invoke_signed(
+            &system_instruction::allocate(account.key, len as u64),
+            &accounts,
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, program_id),
+            &accounts,
+            &[seeds],
+        )
+    }
+
+    fn append_rotation(
+        rotation_log_account: &AccountInfo,
+        gravity_contract: &Pubkey,
+        entry: RotationEntry,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if rotation_log_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-        for (i, signer_pubkey) in signer_pubkeys.iter().enumerate() {
-            multisig.signers[i] = *signer_pubkey;
+
+        let mut rotation_log_info = {
+            let rotation_log_data = &rotation_log_account.try_borrow_data()?[0..RotationLog::LEN];
+            if is_contract_empty(rotation_log_data) {
+                RotationLog::default()
+            } else {
+                RotationLog::unpack(rotation_log_data)?
+            }
+        };
+
+        if !rotation_log_info.is_initialized() {
+            rotation_log_info.gravity_contract = *gravity_contract;
+        } else if rotation_log_info.gravity_contract != *gravity_contract {
+            return Err(ProgramError::InvalidAccountData);
         }
-        multisig.is_initialized = true;
 
-        Multisig::pack(multisig, &mut multisig_account.try_borrow_mut_data()?)?;
+        rotation_log_info.append(entry);
+
+        RotationLog::pack(
+            rotation_log_info,
+            &mut rotation_log_account.try_borrow_mut_data()?[0..RotationLog::LEN],
+        )?;
 
         Ok(())
     }
 
-    const MAX_SIGNERS: usize = 11;
-    pub fn validate_owner(
+    /// Replaces the whole consul set on behalf of `emergency_threshold` current consuls. Same
+    /// accounts as `UpdateConsuls`, with every signer between the multisig account and the
+    /// rotation log. The multisig is written too: it is handed to the new set in the same
+    /// instruction.
+    pub fn process_emergency_replace(
+        accounts: &[AccountInfo],
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
         program_id: &Pubkey,
-        expected_owner: &Pubkey,
-        owner_account_info: &AccountInfo,
-        signers: &[AccountInfo],
     ) -> ProgramResult {
-        if expected_owner != owner_account_info.key {
-            return Err(TokenError::OwnerMismatch.into());
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+                AccountRole::writable("multisig"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-        if program_id == owner_account_info.owner
-            && owner_account_info.data_len() == Multisig::get_packed_len()
+
+        // the rotation log, the clock sysvar and the system program close the list
+        let (signers, trailing) = accounts[3..].split_at(accounts.len().saturating_sub(6));
+        let (rotation_log_account, clock_account, system_program_account) = match trailing {
+            [rotation_log, clock, system_program] => (rotation_log, clock, system_program),
+            _ => return Err(ProgramError::NotEnoughAccountKeys),
+        };
+
+        let mut gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
+        if gravity_contract_info.multisig_account != *gravity_contract_multisig_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if gravity_contract_multisig_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let multisig = Multisig::unpack(&gravity_contract_multisig_account.try_borrow_data()?)?;
+
+        if MiscProcessor::validate_owner_with_threshold(
+            program_id,
+            gravity_contract_multisig_account.key,
+            gravity_contract_multisig_account,
+            signers,
+            gravity_contract_info.emergency_threshold(multisig.n),
+        )
+        .is_err()
         {
-            let multisig = Multisig::unpack(&owner_account_info.try_borrow_data()?)?;
-            let mut num_signers = 0;
-            let mut matched = [false; Self::MAX_SIGNERS];
-            for signer in signers.iter() {
-                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
-                    if key == signer.key && !matched[position] {
-                        if !signer.is_signer {
-                            return Err(ProgramError::MissingRequiredSignature);
-                        }
-                        matched[position] = true;
-                        num_signers += 1;
-                    }
-                }
-            }
-            if num_signers < multisig.m {
-                return Err(ProgramError::MissingRequiredSignature);
-            }
-            return Ok(());
-        } else if !owner_account_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(GravityError::BelowEmergencyThreshold.into());
+        }
+
+        let old_commitment = gravity_contract_info.consul_commitment();
+        let old_epoch = gravity_contract_info.consul_epoch;
+        let clock = SysvarClock::from_account_info(clock_account)?;
+        let records = gravity_contract_info.rotate_consuls(
+            current_round,
+            new_consuls.clone(),
+            clock.current_slot(),
+        )?;
+        MiscProcessor::process_replace_multisig_signers(
+            gravity_contract_multisig_account,
+            &new_consuls,
+            gravity_contract_info.bft,
+        )?;
+        Self::log_rotation(
+            rotation_log_account,
+            initializer,
+            system_program_account,
+            gravity_contract_account.key,
+            RotationEntry {
+                epoch: gravity_contract_info.consul_epoch,
+                old_commitment,
+                new_commitment: gravity_contract_info.consul_commitment(),
+                initiator: *initializer.key,
+                slot: clock.current_slot(),
+            },
+            old_epoch,
+            program_id,
+        )?;
+        Self::emit_events(&records);
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// Applies the updates in order, each checked against the round the previous one left
+    /// and signed by a quorum of the consuls. The contract is written once at the end, so
+    /// a single bad step rejects the whole batch. The consul signatures count once the
+    /// ed25519 program checked them earlier in the transaction, see `verify_ed25519_signatures`.
+    ///
+    /// No multisig authority is asked for: each step carries a quorum of consul signatures
+    /// over a message binding the chain, the round, the epoch and the consul set, which is the
+    /// same quorum the multisig stands for. Any relayer can submit a batch, but none can apply
+    /// a round the consuls didn't sign for.
+    pub fn process_batch_update_rounds(
+        accounts: &[AccountInfo],
+        updates: Vec<(u64, SignatureBundle)>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::writable("gravity contract"),
+                AccountRole::readonly("instructions"),
+            ],
+        )?;
+
+        let gravity_contract_account = &accounts[0];
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let verifier = NativeVerifier::from_account_info(&accounts[1])?;
+        if updates.is_empty() {
+            return Err(GravityError::InvalidInstruction.into());
         }
+
+        let mut gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        let slot = SysvarClock::get()?.current_slot();
+        for (round, bundle) in updates.iter() {
+            let message = round_update_message(
+                gravity_contract_info.chain_id,
+                *round,
+                gravity_contract_info.consul_epoch,
+                &gravity_contract_info.consuls,
+            );
+            gravity_contract_info.check_update_round(&verifier, *round, bundle, &message)?;
+            Self::emit_events(&gravity_contract_info.advance_round(*round, slot)?);
+        }
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// Records the scheme each consul signs with, on behalf of the owner. Round updates
+    /// through `UpdateRoundMixed` take every consul's signature in its recorded scheme.
+    pub fn process_set_consul_key_types(
+        accounts: &[AccountInfo],
+        key_types: Vec<ConsulKeyType>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        gravity_contract_info.assert_owner(initializer.key)?;
+        gravity_contract_info.set_key_types(&key_types)?;
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// Writes a shard of the consul set to the account derived for it, creating the account
+    /// on first use, and records the shard count in the contract, on behalf of the
+    /// initializer. See `GravityContract::load_consuls_from_shards` for reading them back.
+    pub fn process_set_consul_shard(
+        accounts: &[AccountInfo],
+        index: u8,
+        shard_count: u8,
+        consuls: Vec<Pubkey>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+                AccountRole::writable("consul shard"),
+                AccountRole::readonly("system program"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+        let consul_shard_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut gravity_contract_info = GravityContract::unpack(Self::contract_data(
+            &gravity_contract_account.try_borrow_data()?,
+        )?)?;
+
+        gravity_contract_info.assert_owner(initializer.key)?;
+        gravity_contract_info.set_consul_shard(index, shard_count, &consuls)?;
+
+        let (consul_shard_key, bump_seed) =
+            ConsulShard::address(gravity_contract_account.key, index, program_id);
+        if *consul_shard_account.key != consul_shard_key {
+            return Err(GravityError::InvalidShardSequence.into());
+        }
+        if consul_shard_account.data_is_empty() {
+            let seeds: &[&[u8]] = &[
+                ConsulShard::SEED,
+                gravity_contract_account.key.as_ref(),
+                &[index],
+                &[bump_seed],
+            ];
+            Self::create_program_account(
+                consul_shard_account,
+                initializer,
+                system_program_account,
+                seeds,
+                ConsulShard::LEN,
+                program_id,
+            )?;
+        }
+        if consul_shard_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        ConsulShard {
+            gravity_contract: *gravity_contract_account.key,
+            index,
+            shard_count,
+            consuls,
+        }
+        .try_pack_into_slice(&mut consul_shard_account.try_borrow_mut_data()?)?;
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
         Ok(())
     }
+
+    /// Moves a contract of the layout before the discriminator, whose accounts are smaller
+    /// than `LEN`, into a blank account of the current one. Accounts can't grow, so the
+    /// contract changes address; the old account is zeroed and its lamports go to the
+    /// initializer, which leaves nothing behind to migrate or act on twice.
+    pub fn process_migrate(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("legacy gravity contract"),
+                AccountRole::writable("gravity contract"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        let legacy_contract_account = next_account_info(account_info_iter)?;
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if legacy_contract_account.owner != program_id
+            || gravity_contract_account.owner != program_id
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // contracts of the current layout take more room, and never need moving
+        if legacy_contract_account.data_len() != GravityContract::PRE_DISCRIMINATOR_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let gravity_contract_info =
+            GravityContract::unpack_pre_discriminator(&legacy_contract_account.try_borrow_data()?)?;
+        gravity_contract_info.assert_owner(initializer.key)?;
+
+        if !is_contract_empty(&gravity_contract_account.try_borrow_data()?) {
+            return Err(GravityError::AccountAlreadyUsed.into());
+        }
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        for byte in legacy_contract_account.try_borrow_mut_data()?.iter_mut() {
+            *byte = 0;
+        }
+        let lamports = initializer
+            .lamports()
+            .checked_add(legacy_contract_account.lamports())
+            .ok_or(ProgramError::InvalidArgument)?;
+        **legacy_contract_account.try_borrow_mut_lamports()? = 0;
+        **initializer.try_borrow_mut_lamports()? = lamports;
+
+        Ok(())
+    }
+
+    /// Applies a round update signed by a quorum of consuls of either scheme, each signature
+    /// checked in the scheme the contract records for its consul. The signatures count once
+    /// the ed25519 or secp256k1 program checked them earlier in the transaction.
+    pub fn process_update_round_mixed(
+        accounts: &[AccountInfo],
+        round: u64,
+        signatures: Vec<(u8, MixedSignature)>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::writable("gravity contract"),
+                AccountRole::readonly("instructions"),
+            ],
+        )?;
+
+        let gravity_contract_account = &accounts[0];
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let verifier = NativeVerifier::from_account_info(&accounts[1])?;
+
+        let mut gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        gravity_contract_info.check_round(round)?;
+        let message = round_update_message(
+            gravity_contract_info.chain_id,
+            round,
+            gravity_contract_info.consul_epoch,
+            &gravity_contract_info.consuls,
+        );
+        gravity_contract_info.verify_multisig_mixed(&verifier, &message, &signatures)?;
+
+        let slot = SysvarClock::get()?.current_slot();
+        Self::emit_events(&gravity_contract_info.advance_round(round, slot)?);
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// Adds `consul`'s signature to the `PendingUpdate` collecting them for rotating to
+    /// `new_consuls` at `current_round`, starting the accumulation on a blank account at the
+    /// current slot; an expired accumulation has to be reset first. The signature counts once
+    /// the ed25519 program checked it earlier in the transaction, and the update is finalized
+    /// as soon as `bft` consuls have signed.
+    pub fn process_sign_pending_update(
+        accounts: &[AccountInfo],
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
+        consul: Pubkey,
+        signature: ConsulSignature,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::readonly("gravity contract"),
+                AccountRole::writable("pending update"),
+                AccountRole::readonly("instructions"),
+                AccountRole::readonly("clock"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+        let pending_update_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id
+            || pending_update_account.owner != program_id
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let verifier = NativeVerifier::from_account_info(next_account_info(account_info_iter)?)?;
+        let clock = SysvarClock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        let pending_update_data =
+            &mut pending_update_account.try_borrow_mut_data()?[0..PendingUpdate::LEN];
+        let mut pending_update_info = if is_contract_empty(pending_update_data) {
+            PendingUpdate {
+                gravity_contract: *gravity_contract_account.key,
+                current_round,
+                new_consuls,
+                started_at: clock.current_slot(),
+                ..PendingUpdate::default()
+            }
+        } else {
+            let pending_update_info = PendingUpdate::unpack(pending_update_data)?;
+            if pending_update_info.is_expired(&clock, PendingUpdate::TTL) {
+                return Err(GravityError::UpdateExpired.into());
+            }
+            if pending_update_info.gravity_contract != *gravity_contract_account.key
+                || pending_update_info.current_round != current_round
+                || pending_update_info.new_consuls != new_consuls
+            {
+                return Err(GravityError::PendingUpdateMismatch.into());
+            }
+            pending_update_info
+        };
+
+        let message = pending_update_info.signing_message(&gravity_contract_info);
+        pending_update_info.add_signature(
+            &verifier,
+            &gravity_contract_info.consuls,
+            &consul,
+            &message,
+            signature,
+        )?;
+        if pending_update_info.is_ready(gravity_contract_info.bft) {
+            pending_update_info.finalize(gravity_contract_info.bft)?;
+        }
+
+        PendingUpdate::pack(pending_update_info, pending_update_data)?;
+
+        Ok(())
+    }
+
+    /// Clears an accumulation that has outlived `PendingUpdate::TTL`, so the account can be reused.
+    pub fn process_reset_pending_update(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::writable("pending update"),
+                AccountRole::readonly("clock"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let pending_update_account = next_account_info(account_info_iter)?;
+
+        if pending_update_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let clock = SysvarClock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let pending_update_info = PendingUpdate::unpack(
+            &pending_update_account.try_borrow_data()?[0..PendingUpdate::LEN],
+        )?;
+
+        if !pending_update_info.is_expired(&clock, PendingUpdate::TTL) {
+            return Err(GravityError::PendingUpdateNotExpired.into());
+        }
+
+        for byte in pending_update_account.try_borrow_mut_data()?.iter_mut() {
+            *byte = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Adds `nebula` to the registry of the Gravity contract, claiming an empty registry account.
+    pub fn process_register_nebula(
+        accounts: &[AccountInfo],
+        nebula: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::readonly("gravity contract"),
+                AccountRole::writable("nebula registry"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        gravity_contract_info.assert_owner(initializer.key)?;
+
+        let nebula_registry_account = next_account_info(account_info_iter)?;
+
+        if nebula_registry_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let nebula_registry_data =
+            &nebula_registry_account.try_borrow_data()?[0..NebulaRegistry::LEN];
+        let mut nebula_registry_info = if is_contract_empty(nebula_registry_data) {
+            NebulaRegistry::default()
+        } else {
+            NebulaRegistry::unpack(nebula_registry_data)?
+        };
+
+        if !nebula_registry_info.is_initialized() {
+            nebula_registry_info.gravity_contract = *gravity_contract_account.key;
+        } else if nebula_registry_info.gravity_contract != *gravity_contract_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        nebula_registry_info.register(nebula)?;
+
+        NebulaRegistry::pack(
+            nebula_registry_info,
+            &mut nebula_registry_account.try_borrow_mut_data()?[0..NebulaRegistry::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_transfer_ownership(
+        accounts: &[AccountInfo],
+        new_owner: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::writable("gravity contract"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        gravity_contract_info.transfer_ownership(initializer.key, new_owner)?;
+        Self::emit_events(&[EventRecord::new(
+            ContractEvent::OwnershipTransferred { new_owner },
+            &gravity_contract_info,
+        )?]);
+
+        gravity_contract_info.try_pack_into_slice(Self::contract_data_mut(
+            &mut gravity_contract_account.try_borrow_mut_data()?,
+        )?)?;
+
+        Ok(())
+    }
+
+    /// First phase of a config change: the owner gets the proposal checked, nothing is written.
+    pub fn process_propose_config(
+        accounts: &[AccountInfo],
+        config: GravityContract,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        verify_account_layout(
+            accounts,
+            &[
+                AccountRole::signer("initializer"),
+                AccountRole::readonly("gravity contract"),
+            ],
+        )?;
+
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let gravity_contract_info = GravityContract::unpack(
+            Self::contract_data(&gravity_contract_account.try_borrow_data()?)?,
+        )?;
+
+        gravity_contract_info.assert_owner(initializer.key)?;
+        config.validate()?;
+
+        msg!("proposed config is valid");
+
+        Ok(())
+    }
+}
+
+pub struct MiscProcessor;
+
+impl MiscProcessor {
+    pub fn process_init_multisig(
+        multisig_account: &AccountInfo,
+        signer_pubkeys: &[Pubkey],
+        minumum_bft: u8,
+    ) -> ProgramResult {
+        let mut multisig = Multisig::unpack_unchecked(&multisig_account.try_borrow_data()?)?;
+
+        if multisig.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        multisig.m = minumum_bft;
+        multisig.n = signer_pubkeys.len() as u8;
+        if !is_valid_signer_index(multisig.n as usize) {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if !is_valid_signer_index(multisig.m as usize) {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+        for (i, signer_pubkey) in signer_pubkeys.iter().enumerate() {
+            multisig.signers[i] = *signer_pubkey;
+        }
+        multisig.is_initialized = true;
+
+        Multisig::pack(multisig, &mut multisig_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// `process_init_multisig` over a multisig already in use, handing it to `signer_pubkeys`.
+    /// Signers past the new set are cleared, so none of the old ones is left behind.
+    pub fn process_replace_multisig_signers(
+        multisig_account: &AccountInfo,
+        signer_pubkeys: &[Pubkey],
+        minumum_bft: u8,
+    ) -> ProgramResult {
+        let mut multisig = Multisig::unpack(&multisig_account.try_borrow_data()?)?;
+
+        if !is_valid_signer_index(signer_pubkeys.len()) {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if !is_valid_signer_index(minumum_bft as usize) {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+        multisig.m = minumum_bft;
+        multisig.n = signer_pubkeys.len() as u8;
+        multisig.signers = Default::default();
+        multisig.signers[..signer_pubkeys.len()].copy_from_slice(signer_pubkeys);
+
+        Multisig::pack(multisig, &mut multisig_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    const MAX_SIGNERS: usize = 11;
+    pub fn validate_owner(
+        program_id: &Pubkey,
+        expected_owner: &Pubkey,
+        owner_account_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
+        Self::validate_owner_with_threshold(
+            program_id,
+            expected_owner,
+            owner_account_info,
+            signers,
+            0,
+        )
+    }
+
+    /// `validate_owner` that takes at least `threshold` multisig signers, even when its `m` is lower.
+    pub fn validate_owner_with_threshold(
+        program_id: &Pubkey,
+        expected_owner: &Pubkey,
+        owner_account_info: &AccountInfo,
+        signers: &[AccountInfo],
+        threshold: u8,
+    ) -> ProgramResult {
+        if expected_owner != owner_account_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if program_id == owner_account_info.owner
+            && owner_account_info.data_len() == Multisig::get_packed_len()
+        {
+            let multisig = Multisig::unpack(&owner_account_info.try_borrow_data()?)?;
+            let mut num_signers = 0;
+            let mut matched = [false; Self::MAX_SIGNERS];
+            for signer in signers.iter() {
+                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
+                    if key == signer.key && !matched[position] {
+                        if !signer.is_signer {
+                            return Err(ProgramError::MissingRequiredSignature);
+                        }
+                        matched[position] = true;
+                        num_signers += 1;
+                    }
+                }
+            }
+            if num_signers < multisig.m.max(threshold) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            return Ok(());
+        } else if !owner_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        sync::Once,
+    };
+
+    use solana_program::{
+        clock::Clock,
+        entrypoint::SUCCESS,
+        instruction::Instruction,
+        message::Message,
+        program_error::UNSUPPORTED_SYSVAR,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        sysvar::{self, Sysvar},
+    };
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::gravity::instruction::{
+        init_contract, migrate, propose_config, reset_pending_update,
+        set_consul_key_types, set_consul_shard, sign_pending_update, transfer_ownership,
+        update_consuls, verify_ed25519_signatures,
+    };
+    use crate::gravity::state::EventLog;
+
+    #[test]
+    fn test_propose_config() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: initializer_key,
+            bft: 1,
+            consuls: vec![Pubkey::new_unique()],
+            ..GravityContract::default()
+        };
+        let mut initializer_lamports = 0;
+        let mut initializer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut gravity_contract_data);
+        let packed = gravity_contract_data.clone();
+
+        let config = GravityContract {
+            bft: 2,
+            consuls: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            ..gravity_contract_info.clone()
+        };
+        let invalid_config = GravityContract {
+            bft: 3,
+            ..config.clone()
+        };
+
+        for (proposal, expected) in [
+            (&config, Ok(())),
+            (&invalid_config, Err(GravityError::InvalidBFTCount.into())),
+        ] {
+            let instruction = propose_config(
+                &program_id,
+                &initializer_key,
+                &gravity_contract_key,
+                proposal,
+            )
+            .unwrap();
+            match GravityContractInstruction::unpack(&instruction.data).unwrap() {
+                GravityContractInstruction::ProposeConfig { config } => {
+                    assert_eq!(&config, proposal)
+                }
+                _ => panic!("unexpected instruction"),
+            }
+
+            let initializer_account = AccountInfo::new(
+                &initializer_key,
+                true,
+                false,
+                &mut initializer_lamports,
+                &mut initializer_data,
+                &program_id,
+                false,
+                0,
+            );
+            let gravity_contract_account = AccountInfo::new(
+                &gravity_contract_key,
+                false,
+                false,
+                &mut gravity_contract_lamports,
+                &mut gravity_contract_data,
+                &program_id,
+                false,
+                0,
+            );
+
+            assert_eq!(
+                GravityProcessor::process(
+                    &program_id,
+                    &[initializer_account, gravity_contract_account],
+                    &instruction.data,
+                ),
+                expected
+            );
+        }
+
+        // proposals leave the contract as it was
+        assert_eq!(gravity_contract_data, packed);
+    }
+
+    #[test]
+    fn test_set_consul_key_types() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: initializer_key,
+            bft: 1,
+            consuls: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            ..GravityContract::default()
+        };
+        let mut signer_lamports = 0;
+        let mut signer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut gravity_contract_data);
+
+        let stranger_key = Pubkey::new_unique();
+        let key_types = [ConsulKeyType::Ed25519, ConsulKeyType::Secp256k1];
+        for (signer_key, key_types, expected) in [
+            (
+                &stranger_key,
+                &key_types[..],
+                Err(ProgramError::MissingRequiredSignature),
+            ),
+            (
+                &initializer_key,
+                &key_types[1..],
+                Err(GravityError::KeyTypeMismatch.into()),
+            ),
+            (&initializer_key, &key_types[..], Ok(())),
+        ] {
+            let instruction =
+                set_consul_key_types(&program_id, signer_key, &gravity_contract_key, key_types)
+                    .unwrap();
+
+            let signer_account = AccountInfo::new(
+                signer_key,
+                true,
+                false,
+                &mut signer_lamports,
+                &mut signer_data,
+                &program_id,
+                false,
+                0,
+            );
+            let gravity_contract_account = AccountInfo::new(
+                &gravity_contract_key,
+                false,
+                true,
+                &mut gravity_contract_lamports,
+                &mut gravity_contract_data,
+                &program_id,
+                false,
+                0,
+            );
+
+            assert_eq!(
+                GravityProcessor::process(
+                    &program_id,
+                    &[signer_account, gravity_contract_account],
+                    &instruction.data,
+                ),
+                expected
+            );
+        }
+
+        let gravity_contract_info = GravityContract::unpack(&gravity_contract_data).unwrap();
+        assert_eq!(gravity_contract_info.secp256k1_consuls, 0b10);
+        assert_eq!(gravity_contract_info.key_type(0), ConsulKeyType::Ed25519);
+        assert_eq!(gravity_contract_info.key_type(1), ConsulKeyType::Secp256k1);
+    }
+
+    #[test]
+    fn test_set_consul_shard() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: initializer_key,
+            bft: 1,
+            consuls: consuls.clone(),
+            ..GravityContract::default()
+        };
+        let mut signer_lamports = 0;
+        let mut signer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut gravity_contract_data);
+        // allocated upfront, creating it takes a CPI into the system program
+        let mut consul_shard_lamports = 0;
+        let mut consul_shard_data = vec![0; ConsulShard::LEN];
+        let mut system_program_lamports = 0;
+        let mut system_program_data = vec![];
+        let system_program_key = solana_program::system_program::id();
+
+        let stranger_key = Pubkey::new_unique();
+        for (signer_key, index, shard_count, shard_consuls, expected) in [
+            (
+                &stranger_key,
+                0,
+                1,
+                &consuls[..],
+                Err(ProgramError::MissingRequiredSignature),
+            ),
+            (
+                &initializer_key,
+                1,
+                1,
+                &consuls[..],
+                Err(GravityError::InvalidShardSequence.into()),
+            ),
+            (
+                &initializer_key,
+                0,
+                1,
+                &consuls[..0],
+                Err(GravityError::TooFewConsuls.into()),
+            ),
+            (&initializer_key, 0, 1, &consuls[..], Ok(())),
+        ]
+        .iter()
+        {
+            let instruction = set_consul_shard(
+                &program_id,
+                signer_key,
+                &gravity_contract_key,
+                *index,
+                *shard_count,
+                shard_consuls,
+            )
+            .unwrap();
+            let consul_shard_key = instruction.accounts[2].pubkey;
+
+            let signer_account = AccountInfo::new(
+                signer_key,
+                true,
+                true,
+                &mut signer_lamports,
+                &mut signer_data,
+                &program_id,
+                false,
+                0,
+            );
+            let gravity_contract_account = AccountInfo::new(
+                &gravity_contract_key,
+                false,
+                true,
+                &mut gravity_contract_lamports,
+                &mut gravity_contract_data,
+                &program_id,
+                false,
+                0,
+            );
+            let consul_shard_account = AccountInfo::new(
+                &consul_shard_key,
+                false,
+                true,
+                &mut consul_shard_lamports,
+                &mut consul_shard_data,
+                &program_id,
+                false,
+                0,
+            );
+            let system_program_account = AccountInfo::new(
+                &system_program_key,
+                false,
+                false,
+                &mut system_program_lamports,
+                &mut system_program_data,
+                &program_id,
+                true,
+                0,
+            );
+
+            assert_eq!(
+                GravityProcessor::process(
+                    &program_id,
+                    &[
+                        signer_account,
+                        gravity_contract_account,
+                        consul_shard_account,
+                        system_program_account,
+                    ],
+                    &instruction.data,
+                ),
+                *expected
+            );
+        }
+
+        // the shard written reads back as the contract's consul set
+        let gravity_contract_info = GravityContract::unpack(&gravity_contract_data).unwrap();
+        assert_eq!(gravity_contract_info.consul_shards, 1);
+
+        let consul_shard_key = ConsulShard::address(&gravity_contract_key, 0, &program_id).0;
+        let consul_shard_account = AccountInfo::new(
+            &consul_shard_key,
+            false,
+            false,
+            &mut consul_shard_lamports,
+            &mut consul_shard_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            gravity_contract_info.load_consuls_from_shards(
+                &gravity_contract_key,
+                &[&consul_shard_account],
+                &program_id
+            ),
+            Ok(consuls)
+        );
+    }
+
+    #[test]
+    fn test_swapped_accounts() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let instruction = transfer_ownership(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &Pubkey::new_unique(),
+        )
+        .unwrap();
+
+        let mut initializer_lamports = 0;
+        let mut initializer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        GravityContract {
+            initializer_pubkey: initializer_key,
+            bft: 1,
+            consuls: vec![Pubkey::new_unique()],
+            ..GravityContract::default()
+        }
+        .pack_into_slice(&mut gravity_contract_data);
+
+        let initializer_account = AccountInfo::new(
+            &initializer_key,
+            true,
+            false,
+            &mut initializer_lamports,
+            &mut initializer_data,
+            &program_id,
+            false,
+            0,
+        );
+        let gravity_contract_account = AccountInfo::new(
+            &gravity_contract_key,
+            false,
+            true,
+            &mut gravity_contract_lamports,
+            &mut gravity_contract_data,
+            &program_id,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            GravityProcessor::process(
+                &program_id,
+                &[
+                    gravity_contract_account.clone(),
+                    initializer_account.clone()
+                ],
+                &instruction.data,
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            GravityProcessor::process(
+                &program_id,
+                &[initializer_account, gravity_contract_account],
+                &instruction.data,
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_blank_contract() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let instruction = transfer_ownership(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &Pubkey::new_unique(),
+        )
+        .unwrap();
+
+        let mut initializer_lamports = 0;
+        let mut initializer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+
+        let initializer_account = AccountInfo::new(
+            &initializer_key,
+            true,
+            false,
+            &mut initializer_lamports,
+            &mut initializer_data,
+            &program_id,
+            false,
+            0,
+        );
+        let gravity_contract_account = AccountInfo::new(
+            &gravity_contract_key,
+            false,
+            true,
+            &mut gravity_contract_lamports,
+            &mut gravity_contract_data,
+            &program_id,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            GravityProcessor::process(
+                &program_id,
+                &[initializer_account, gravity_contract_account],
+                &instruction.data,
+            ),
+            Err(GravityError::NotInitialized.into())
+        );
+    }
+
+    fn run_migrate(
+        signer_key: &Pubkey,
+        signer_lamports: &mut u64,
+        legacy_contract_lamports: &mut u64,
+        legacy_contract_data: &mut [u8],
+        gravity_contract_data: &mut [u8],
+    ) -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let legacy_contract_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+
+        let instruction = migrate(
+            &program_id,
+            signer_key,
+            &legacy_contract_key,
+            &gravity_contract_key,
+        )
+        .unwrap();
+
+        let mut signer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let signer_account = AccountInfo::new(
+            signer_key,
+            true,
+            true,
+            signer_lamports,
+            &mut signer_data,
+            &program_id,
+            false,
+            0,
+        );
+        let legacy_contract_account = AccountInfo::new(
+            &legacy_contract_key,
+            false,
+            true,
+            legacy_contract_lamports,
+            legacy_contract_data,
+            &program_id,
+            false,
+            0,
+        );
+        let gravity_contract_account = AccountInfo::new(
+            &gravity_contract_key,
+            false,
+            true,
+            &mut gravity_contract_lamports,
+            gravity_contract_data,
+            &program_id,
+            false,
+            0,
+        );
+
+        GravityProcessor::process(
+            &program_id,
+            &[
+                signer_account,
+                legacy_contract_account,
+                gravity_contract_account,
+            ],
+            &instruction.data,
+        )
+    }
+
+    #[test]
+    fn test_migrate() {
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: initializer_key,
+            bft: 2,
+            consuls: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            last_round: 9,
+            multisig_account: Pubkey::new_unique(),
+            ..GravityContract::default()
+        };
+        let legacy_data = gravity_contract_info.pack_pre_discriminator().unwrap();
+
+        // instructions other than `Migrate` refuse the old account instead of reading past it
+        let mut stranded_data = legacy_data.clone();
+        let mut lamports = 0;
+        let mut signer_data = vec![];
+        let program_id = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let instruction = set_consul_key_types(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &[ConsulKeyType::Ed25519; 2],
+        )
+        .unwrap();
+        let mut gravity_contract_lamports = 0;
+        assert_eq!(
+            GravityProcessor::process(
+                &program_id,
+                &[
+                    AccountInfo::new(
+                        &initializer_key,
+                        true,
+                        false,
+                        &mut lamports,
+                        &mut signer_data,
+                        &program_id,
+                        false,
+                        0,
+                    ),
+                    AccountInfo::new(
+                        &gravity_contract_key,
+                        false,
+                        true,
+                        &mut gravity_contract_lamports,
+                        &mut stranded_data,
+                        &program_id,
+                        false,
+                        0,
+                    ),
+                ],
+                &instruction.data,
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut signer_lamports = 10;
+        let mut legacy_contract_lamports = 5;
+        let mut legacy_contract_data = legacy_data.clone();
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        assert_eq!(
+            run_migrate(
+                &Pubkey::new_unique(),
+                &mut signer_lamports,
+                &mut legacy_contract_lamports,
+                &mut legacy_contract_data,
+                &mut gravity_contract_data,
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        // an account of the current layout is never taken for an old one
+        let mut current_data = gravity_contract_data.clone();
+        gravity_contract_info.pack_into_slice(&mut current_data);
+        assert_eq!(
+            run_migrate(
+                &initializer_key,
+                &mut signer_lamports,
+                &mut legacy_contract_lamports,
+                &mut current_data,
+                &mut gravity_contract_data,
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        assert_eq!(
+            run_migrate(
+                &initializer_key,
+                &mut signer_lamports,
+                &mut legacy_contract_lamports,
+                &mut legacy_contract_data,
+                &mut gravity_contract_data,
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            GravityContract::unpack(&gravity_contract_data),
+            Ok(gravity_contract_info)
+        );
+        assert!(is_contract_empty(&legacy_contract_data));
+        assert_eq!((signer_lamports, legacy_contract_lamports), (15, 0));
+
+        // the emptied account has nothing left to migrate, and the new one is taken
+        assert_eq!(
+            run_migrate(
+                &initializer_key,
+                &mut signer_lamports,
+                &mut legacy_contract_lamports,
+                &mut legacy_contract_data,
+                &mut vec![0; GravityContract::LEN],
+            ),
+            Err(GravityError::TooFewConsuls.into())
+        );
+        assert_eq!(
+            run_migrate(
+                &initializer_key,
+                &mut signer_lamports,
+                &mut 5,
+                &mut legacy_data.clone(),
+                &mut gravity_contract_data,
+            ),
+            Err(GravityError::AccountAlreadyUsed.into())
+        );
+    }
+
+    #[test]
+    fn test_check_initializer() {
+        let allowed = Pubkey::new_unique();
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: allowed,
+            ..GravityContract::default()
+        };
+
+        assert_eq!(
+            GravityProcessor::check_initializer(&gravity_contract_info, &[]),
+            Ok(())
+        );
+        assert_eq!(
+            GravityProcessor::check_initializer(
+                &gravity_contract_info,
+                &[Pubkey::new_unique(), allowed]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            GravityProcessor::check_initializer(&gravity_contract_info, &[Pubkey::new_unique()]),
+            Err(GravityError::InitializerNotAllowed.into())
+        );
+    }
+
+    #[test]
+    fn test_init_used_account() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let instruction = init_contract(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+            0,
+            2,
+            0,
+        )
+        .unwrap();
+
+        let mut initializer_lamports = 0;
+        let mut initializer_data = vec![];
+        let mut gravity_contract_lamports = 0;
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        GravityContract {
+            bft: 2,
+            consuls: consuls.clone(),
+            ..GravityContract::default()
+        }
+        .pack_into_slice(&mut gravity_contract_data);
+        let mut multisig_lamports = 0;
+        let mut multisig_data = vec![0; Multisig::LEN];
+        let system_program = Pubkey::default();
+
+        for (owner, expected) in [
+            (&system_program, ProgramError::IncorrectProgramId),
+            (&program_id, GravityError::AccountAlreadyUsed.into()),
+        ] {
+            let initializer_account = AccountInfo::new(
+                &initializer_key,
+                true,
+                false,
+                &mut initializer_lamports,
+                &mut initializer_data,
+                &system_program,
+                false,
+                0,
+            );
+            let gravity_contract_account = AccountInfo::new(
+                &gravity_contract_key,
+                false,
+                true,
+                &mut gravity_contract_lamports,
+                &mut gravity_contract_data,
+                owner,
+                false,
+                0,
+            );
+
+            let multisig_account = AccountInfo::new(
+                &multisig_key,
+                false,
+                true,
+                &mut multisig_lamports,
+                &mut multisig_data,
+                &program_id,
+                false,
+                0,
+            );
+
+            assert_eq!(
+                GravityProcessor::process(
+                    &program_id,
+                    &[
+                        initializer_account,
+                        gravity_contract_account,
+                        multisig_account
+                    ],
+                    &instruction.data,
+                ),
+                Err(expected)
+            );
+        }
+    }
+
+    /// Runs `transaction`, ending in a `SignPendingUpdate`, against the contract and pending
+    /// update accounts with the clock at `slot`.
+    fn run_sign_pending_update(
+        program_id: &Pubkey,
+        gravity_contract_key: &Pubkey,
+        gravity_contract_data: &mut [u8],
+        pending_update_key: &Pubkey,
+        pending_update_data: &mut [u8],
+        transaction: &[Instruction],
+        slot: u64,
+    ) -> ProgramResult {
+        let instructions_key = sysvar::instructions::id();
+        let clock_key = sysvar::clock::id();
+        let sysvar_owner = sysvar::id();
+        let mut gravity_contract_lamports = 0;
+        let mut pending_update_lamports = 0;
+        let mut instructions_lamports = 0;
+        let mut instructions_data = Message::new(transaction, None).serialize_instructions(true);
+        let mut clock_lamports = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+
+        let gravity_contract_account = AccountInfo::new(
+            gravity_contract_key,
+            false,
+            false,
+            &mut gravity_contract_lamports,
+            gravity_contract_data,
+            program_id,
+            false,
+            0,
+        );
+        let pending_update_account = AccountInfo::new(
+            pending_update_key,
+            false,
+            true,
+            &mut pending_update_lamports,
+            pending_update_data,
+            program_id,
+            false,
+            0,
+        );
+        let instructions_account = AccountInfo::new(
+            &instructions_key,
+            false,
+            false,
+            &mut instructions_lamports,
+            &mut instructions_data,
+            &sysvar_owner,
+            false,
+            0,
+        );
+        let mut clock_account = AccountInfo::new(
+            &clock_key,
+            false,
+            false,
+            &mut clock_lamports,
+            &mut clock_data,
+            &sysvar_owner,
+            false,
+            0,
+        );
+        Clock {
+            slot,
+            ..Clock::default()
+        }
+        .to_account_info(&mut clock_account)
+        .unwrap();
+
+        GravityProcessor::process(
+            program_id,
+            &[
+                gravity_contract_account,
+                pending_update_account,
+                instructions_account,
+                clock_account,
+            ],
+            &transaction[transaction.len() - 1].data,
+        )
+    }
+
+    #[test]
+    fn test_reset_expired_pending_update() {
+        let program_id = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let pending_update_key = Pubkey::new_unique();
+        let clock_key = sysvar::clock::id();
+
+        let consul = Keypair::new();
+        let gravity_contract_info = GravityContract {
+            bft: 1,
+            consuls: vec![consul.pubkey()],
+            last_round: 7,
+            ..GravityContract::default()
+        };
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut gravity_contract_data);
+
+        let new_consuls = vec![Pubkey::new_unique()];
+        let mut pending_update_lamports = 0;
+        let mut pending_update_data = vec![0; PendingUpdate::LEN];
+        PendingUpdate {
+            gravity_contract: gravity_contract_key,
+            current_round: 8,
+            new_consuls: new_consuls.clone(),
+            started_at: 10,
+            ..PendingUpdate::default()
+        }
+        .pack_into_slice(&mut pending_update_data);
+
+        let message = PendingUpdate {
+            current_round: 8,
+            new_consuls: new_consuls.clone(),
+            ..PendingUpdate::default()
+        }
+        .signing_message(&gravity_contract_info);
+        let mut signature = [0; 64];
+        signature.copy_from_slice(consul.sign_message(&message).as_ref());
+        let transaction = [
+            verify_ed25519_signatures(&message, &[(consul.pubkey(), signature)]),
+            sign_pending_update(
+                &program_id,
+                &gravity_contract_key,
+                &pending_update_key,
+                &new_consuls,
+                8,
+                &consul.pubkey(),
+                signature,
+            )
+            .unwrap(),
+        ];
+
+        // an expired accumulation takes no more signatures until it is reset
+        assert_eq!(
+            run_sign_pending_update(
+                &program_id,
+                &gravity_contract_key,
+                &mut gravity_contract_data,
+                &pending_update_key,
+                &mut pending_update_data,
+                &transaction,
+                11 + PendingUpdate::TTL,
+            ),
+            Err(GravityError::UpdateExpired.into())
+        );
+
+        let mut clock_lamports = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+        let sysvar_owner = sysvar::id();
+
+        let instruction = reset_pending_update(&program_id, &pending_update_key).unwrap();
+
+        for (slot, expired) in [
+            (10 + PendingUpdate::TTL, false),
+            (11 + PendingUpdate::TTL, true),
+        ] {
+            let pending_update_account = AccountInfo::new(
+                &pending_update_key,
+                false,
+                true,
+                &mut pending_update_lamports,
+                &mut pending_update_data,
+                &program_id,
+                false,
+                0,
+            );
+            let mut clock_account = AccountInfo::new(
+                &clock_key,
+                false,
+                false,
+                &mut clock_lamports,
+                &mut clock_data,
+                &sysvar_owner,
+                false,
+                0,
+            );
+            Clock {
+                slot,
+                ..Clock::default()
+            }
+            .to_account_info(&mut clock_account)
+            .unwrap();
+
+            let result = GravityProcessor::process(
+                &program_id,
+                &[pending_update_account, clock_account],
+                &instruction.data,
+            );
+            assert_eq!(result.is_ok(), expired);
+        }
+
+        assert!(pending_update_data.iter().all(|byte| *byte == 0));
+
+        // the reset account takes a fresh accumulation, started at the current slot
+        assert_eq!(
+            run_sign_pending_update(
+                &program_id,
+                &gravity_contract_key,
+                &mut gravity_contract_data,
+                &pending_update_key,
+                &mut pending_update_data,
+                &transaction,
+                20 + PendingUpdate::TTL,
+            ),
+            Ok(())
+        );
+
+        let pending_update_info = PendingUpdate::unpack(&pending_update_data).unwrap();
+        assert_eq!(pending_update_info.started_at, 20 + PendingUpdate::TTL);
+        assert_eq!(pending_update_info.signatures, vec![(0, signature)]);
+        assert!(pending_update_info.is_finalized);
+    }
+
+    #[test]
+    fn test_sign_pending_update() {
+        let program_id = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let pending_update_key = Pubkey::new_unique();
+
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = GravityContract {
+            bft: 2,
+            consuls: consuls.iter().map(|consul| consul.pubkey()).collect(),
+            last_round: 7,
+            ..GravityContract::default()
+        };
+        let mut gravity_contract_data = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut gravity_contract_data);
+        let mut pending_update_data = vec![0; PendingUpdate::LEN];
+
+        let new_consuls: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let message = PendingUpdate {
+            current_round: 8,
+            new_consuls: new_consuls.clone(),
+            ..PendingUpdate::default()
+        }
+        .signing_message(&gravity_contract_info);
+        let sign = |consul: &Keypair| {
+            let mut signature = [0; 64];
+            signature.copy_from_slice(consul.sign_message(&message).as_ref());
+            signature
+        };
+        let other_consuls = vec![Pubkey::new_unique()];
+
+        // (signer, consuls signed for, whether the ed25519 program checked it, outcome)
+        let steps: [(usize, &[Pubkey], bool, ProgramResult); 6] = [
+            (0, &new_consuls, true, Ok(())),
+            (
+                0,
+                &new_consuls,
+                true,
+                Err(GravityError::DuplicateSignature.into()),
+            ),
+            (
+                1,
+                &new_consuls,
+                false,
+                Err(GravityError::InvalidSignature.into()),
+            ),
+            (
+                1,
+                &other_consuls,
+                true,
+                Err(GravityError::PendingUpdateMismatch.into()),
+            ),
+            (1, &new_consuls, true, Ok(())),
+            (
+                2,
+                &new_consuls,
+                true,
+                Err(GravityError::PendingUpdateFinalized.into()),
+            ),
+        ];
+
+        for (slot, (signer, signed_consuls, checked, expected)) in (100..).zip(steps.iter()) {
+            let consul = &consuls[*signer];
+            let signature = sign(consul);
+            let instruction = sign_pending_update(
+                &program_id,
+                &gravity_contract_key,
+                &pending_update_key,
+                signed_consuls,
+                8,
+                &consul.pubkey(),
+                signature,
+            )
+            .unwrap();
+
+            let mut transaction = vec![instruction];
+            if *checked {
+                transaction.insert(
+                    0,
+                    verify_ed25519_signatures(&message, &[(consul.pubkey(), signature)]),
+                );
+            }
+
+            assert_eq!(
+                run_sign_pending_update(
+                    &program_id,
+                    &gravity_contract_key,
+                    &mut gravity_contract_data,
+                    &pending_update_key,
+                    &mut pending_update_data,
+                    &transaction,
+                    slot,
+                ),
+                *expected
+            );
+        }
+
+        let pending_update_info = PendingUpdate::unpack(&pending_update_data).unwrap();
+        assert_eq!(pending_update_info.gravity_contract, gravity_contract_key);
+        assert_eq!(pending_update_info.new_consuls, new_consuls);
+        assert_eq!(
+            pending_update_info
+                .signatures
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert!(pending_update_info.is_finalized);
+        assert_eq!(pending_update_info.started_at, 100);
+    }
+
+    thread_local! {
+        static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+        static CLOCK_SLOT: Cell<Option<u64>> = const { Cell::new(None) };
+    }
+
+    /// Syscall stubs that, on the thread of a `process_logged` call, keep the lines the
+    /// program logs and hand out the `Clock` it runs at; every other thread sees the defaults.
+    struct CapturingStubs;
+
+    impl SyscallStubs for CapturingStubs {
+        fn sol_log(&self, message: &str) {
+            CAPTURED_LOGS.with(|logs| match logs.borrow_mut().as_mut() {
+                // the way the runtime puts it in the transaction's log messages
+                Some(logs) => logs.push(format!("{}{}", EventRecord::PROGRAM_LOG_PREFIX, message)),
+                None => println!("{}", message),
+            })
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            match CLOCK_SLOT.with(Cell::get) {
+                Some(slot) => {
+                    let clock = Clock {
+                        slot,
+                        ..Clock::default()
+                    };
+                    unsafe { *(var_addr as *mut Clock) = clock };
+                    SUCCESS
+                }
+                None => UNSUPPORTED_SYSVAR,
+            }
+        }
+    }
+
+    /// Runs `instruction` through `GravityProcessor::process` at `slot`, over `accounts` by
+    /// key as (lamports, data, owner), and returns the lines it logged.
+    fn process_logged(
+        instruction: &Instruction,
+        accounts: &mut HashMap<Pubkey, (u64, Vec<u8>, Pubkey)>,
+        slot: u64,
+    ) -> (ProgramResult, Vec<String>) {
+        static INSTALL_STUBS: Once = Once::new();
+        INSTALL_STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(CapturingStubs));
+        });
+
+        let account_infos: HashMap<_, _> = accounts
+            .iter_mut()
+            .map(|(key, (lamports, data, owner))| {
+                let account_info =
+                    AccountInfo::new(key, false, false, lamports, data, owner, false, 0);
+                (*key, account_info)
+            })
+            .collect();
+        let mut instruction_accounts = vec![];
+        for account_meta in instruction.accounts.iter() {
+            let mut account_info = account_infos[&account_meta.pubkey].clone();
+            account_info.is_signer = account_meta.is_signer;
+            account_info.is_writable = account_meta.is_writable;
+            if *account_info.key == sysvar::clock::id() {
+                Clock {
+                    slot,
+                    ..Clock::default()
+                }
+                .to_account_info(&mut account_info)
+                .unwrap();
+            }
+            instruction_accounts.push(account_info);
+        }
+
+        CAPTURED_LOGS.with(|logs| *logs.borrow_mut() = Some(vec![]));
+        CLOCK_SLOT.with(|clock_slot| clock_slot.set(Some(slot)));
+        let result = GravityProcessor::process(
+            &instruction.program_id,
+            &instruction_accounts,
+            &instruction.data,
+        );
+        CLOCK_SLOT.with(|clock_slot| clock_slot.set(None));
+        let logs = CAPTURED_LOGS.with(|logs| logs.borrow_mut().take().unwrap());
+
+        (result, logs)
+    }
+
+    /// The accounts `InitContract` and `UpdateConsuls` signed by `consuls` take, as
+    /// `process_logged` wants them, the contract and its multisig still blank.
+    fn contract_accounts(
+        program_id: &Pubkey,
+        initializer_key: &Pubkey,
+        gravity_contract_key: &Pubkey,
+        multisig_key: &Pubkey,
+        consuls: &[Pubkey],
+    ) -> HashMap<Pubkey, (u64, Vec<u8>, Pubkey)> {
+        let (rotation_log_key, _) = RotationLog::address(gravity_contract_key, program_id);
+        let system_program = solana_program::system_program::id();
+
+        let mut accounts = HashMap::new();
+        for key in consuls
+            .iter()
+            .chain([*initializer_key, system_program].iter())
+        {
+            accounts.insert(*key, (0, vec![], system_program));
+        }
+        accounts.insert(
+            *gravity_contract_key,
+            (0, vec![0; GravityContract::LEN], *program_id),
+        );
+        accounts.insert(*multisig_key, (0, vec![0; Multisig::LEN], *program_id));
+        // allocated upfront, creating it takes a CPI into the system program
+        accounts.insert(
+            rotation_log_key,
+            (0, vec![0; RotationLog::LEN], *program_id),
+        );
+        accounts.insert(
+            sysvar::clock::id(),
+            (0, vec![0; Clock::size_of()], sysvar::id()),
+        );
+
+        accounts
+    }
+
+    #[test]
+    fn test_event_log_from_process() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let rotated_consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let new_owner = Pubkey::new_unique();
+        let mut accounts = contract_accounts(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+        );
+
+        let signers = [&consuls[0], &consuls[1]];
+        let steps = [
+            (
+                init_contract(
+                    &program_id,
+                    &initializer_key,
+                    &gravity_contract_key,
+                    &multisig_key,
+                    &consuls,
+                    0,
+                    2,
+                    7,
+                ),
+                0,
+            ),
+            (
+                update_consuls(
+                    &program_id,
+                    &initializer_key,
+                    &gravity_contract_key,
+                    &multisig_key,
+                    &signers,
+                    &consuls,
+                    5,
+                ),
+                10,
+            ),
+            (
+                update_consuls(
+                    &program_id,
+                    &initializer_key,
+                    &gravity_contract_key,
+                    &multisig_key,
+                    &signers,
+                    &rotated_consuls,
+                    9,
+                ),
+                20,
+            ),
+            (
+                transfer_ownership(
+                    &program_id,
+                    &initializer_key,
+                    &gravity_contract_key,
+                    &new_owner,
+                ),
+                20,
+            ),
+        ];
+
+        let mut logs = vec![];
+        for (instruction, slot) in steps.iter() {
+            let (result, step_logs) =
+                process_logged(instruction.as_ref().unwrap(), &mut accounts, *slot);
+            assert_eq!(result, Ok(()));
+            logs.extend(step_logs);
+        }
+
+        let gravity_contract_info =
+            GravityContract::unpack(&accounts[&gravity_contract_key].1).unwrap();
+        assert_eq!(gravity_contract_info.consuls, rotated_consuls);
+        assert_eq!(gravity_contract_info.initializer_pubkey, new_owner);
+
+        let event_log = EventLog::from_logs(&logs).unwrap();
+        assert_eq!(
+            event_log
+                .records
+                .iter()
+                .map(|record| (record.event.clone(), record.slot))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ContractEvent::Initialized {
+                        initializer: initializer_key,
+                        bft: 2,
+                        consuls: consuls.clone(),
+                        multisig_account: multisig_key,
+                        chain_id: 7,
+                    },
+                    0
+                ),
+                (ContractEvent::RoundUpdated { round: 5 }, 10),
+                (ContractEvent::RoundUpdated { round: 9 }, 20),
+                (
+                    ContractEvent::ConsulsRotated {
+                        consuls: rotated_consuls,
+                    },
+                    20
+                ),
+                (ContractEvent::OwnershipTransferred { new_owner }, 20),
+            ]
+        );
+        assert!(event_log.verify_against(&gravity_contract_info));
+
+        // the second round update dropped, the rotation is replayed onto the wrong round
+        let mut incomplete_log = event_log.clone();
+        incomplete_log.records.remove(2);
+        assert!(!incomplete_log.verify_against(&gravity_contract_info));
+
+        // anything else the program logs is passed over, with or without the runtime's prefix
+        let record = &event_log.records[0];
+        assert_eq!(
+            EventRecord::from_log(&record.to_log()),
+            Ok(Some(record.clone()))
+        );
+        assert_eq!(
+            EventRecord::from_log("Program log: picking multisig account"),
+            Ok(None)
+        );
+        assert_eq!(
+            EventRecord::from_log("Program log: Event: OwnershipTransferred"),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_update_consuls_too_many() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut accounts = contract_accounts(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+        );
+
+        let instruction = init_contract(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+            0,
+            2,
+            7,
+        )
+        .unwrap();
+        assert_eq!(process_logged(&instruction, &mut accounts, 0).0, Ok(()));
+        let initialized = accounts[&gravity_contract_key].1.clone();
+
+        // one consul past what the layout holds is refused, rather than aborting the write
+        let too_many_consuls: Vec<_> = (0..=GravityContract::MAX_CONSULS)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        let instruction = update_consuls(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &[&consuls[0], &consuls[1]],
+            &too_many_consuls,
+            5,
+        )
+        .unwrap();
+        assert_eq!(
+            process_logged(&instruction, &mut accounts, 10).0,
+            Err(GravityError::TooManyConsuls.into())
+        );
+        assert_eq!(accounts[&gravity_contract_key].1, initialized);
+    }
+
+    #[test]
+    fn test_update_consuls_checks_accounts() {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let gravity_contract_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let consuls = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut accounts = contract_accounts(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+        );
+
+        let instruction = init_contract(
+            &program_id,
+            &initializer_key,
+            &gravity_contract_key,
+            &multisig_key,
+            &consuls,
+            0,
+            2,
+            7,
+        )
+        .unwrap();
+        assert_eq!(process_logged(&instruction, &mut accounts, 0).0, Ok(()));
+
+        let update_with = |multisig_key: &Pubkey| {
+            update_consuls(
+                &program_id,
+                &initializer_key,
+                &gravity_contract_key,
+                multisig_key,
+                &[&consuls[0], &consuls[1]],
+                &[Pubkey::new_unique(), Pubkey::new_unique()],
+                5,
+            )
+            .unwrap()
+        };
+
+        // the same signers behind a multisig the contract doesn't name
+        let other_multisig_key = Pubkey::new_unique();
+        let multisig_account = accounts[&multisig_key].clone();
+        accounts.insert(other_multisig_key, multisig_account);
+        assert_eq!(
+            process_logged(&update_with(&other_multisig_key), &mut accounts, 10).0,
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // a contract this program doesn't own
+        let owner = accounts[&gravity_contract_key].2;
+        accounts.get_mut(&gravity_contract_key).unwrap().2 = Pubkey::new_unique();
+        assert_eq!(
+            process_logged(&update_with(&multisig_key), &mut accounts, 10).0,
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        accounts.get_mut(&gravity_contract_key).unwrap().2 = owner;
+        assert_eq!(
+            process_logged(&update_with(&multisig_key), &mut accounts, 10).0,
+            Ok(())
+        );
+    }
 }
@@ -0,0 +1,1689 @@
+use std::convert::TryFrom;
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+#[cfg(any(test, feature = "client"))]
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+#[cfg(feature = "client")]
+use solana_program::program_pack::Pack;
+#[cfg(any(test, feature = "client"))]
+use solana_program::keccak;
+use solana_program::{clock::Slot, program_error::ProgramError, pubkey::Pubkey};
+
+use gravity_misc::merkle::{verify_merkle_inclusion, MerkleHash};
+
+use crate::gravity::{
+    error::GravityError,
+    state::{ConsulSignature, GravityContract, RotationLog},
+    time::TimeSource,
+    verifier::SignatureVerifier,
+};
+
+/// Consul signatures over one message, each tagged with the index of its consul.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct SignatureBundle {
+    pub signatures: Vec<(u8, ConsulSignature)>,
+}
+
+impl SignatureBundle {
+    /// Canonical bundles list their consul indices strictly increasing, which rules out duplicates.
+    pub fn is_canonical(&self) -> bool {
+        self.signatures.windows(2).all(|pair| pair[0].0 < pair[1].0)
+    }
+
+    /// Whether any signature carries an unreduced scalar, which `verify_multisig` refuses
+    /// rather than leaving to the verifier.
+    pub fn has_malleable_signature(&self) -> bool {
+        self.signatures
+            .iter()
+            .any(|(_, signature)| !is_canonical_scalar(signature))
+    }
+
+    /// Sorts by consul index and keeps the first signature of each consul, making the bundle
+    /// canonical before it is submitted.
+    pub fn canonicalize(&mut self) {
+        self.signatures.sort_by_key(|(index, _)| *index);
+        self.signatures.dedup_by_key(|(index, _)| *index);
+    }
+
+    /// Bytes the bundle is smaller by than one tagging each signature with its signer's key,
+    /// a one byte index standing in for every 32 byte key.
+    pub fn size_saved_vs_full(&self) -> usize {
+        self.signatures.len() * (std::mem::size_of::<Pubkey>() - std::mem::size_of::<u8>())
+    }
+}
+
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct VerifyOutcome {
+    pub reached_quorum: bool,
+    pub valid_indices: Vec<u8>,
+    pub invalid_indices: Vec<u8>,
+    /// The signers credited with the quorum: the lowest `bft` valid consul indices, so the
+    /// recorded set does not depend on which extra signatures came along.
+    pub participating: Vec<u8>,
+}
+
+/// Length of an ed25519 signature.
+#[cfg(feature = "client")]
+const SIGNATURE_LEN: usize = 64;
+
+/// Signed messages open with the little-endian id of the chain they are addressed to,
+/// so that a signature gathered for one chain can't be replayed on another.
+pub const CHAIN_ID_LEN: usize = 8;
+
+pub fn signed_message(dest_chain_id: u64, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(CHAIN_ID_LEN + payload.len());
+    message.extend_from_slice(&dest_chain_id.to_le_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Message consuls sign to move the contract on `dest_chain_id` to `new_round` with `new_consuls`,
+/// committing to the `consul_epoch` it was signed under so that a set rotated away can't
+/// keep advancing rounds. It is the `signing_preimage` of `Action::RoundUpdate`, tagged the
+/// same way as the preimages of the other actions.
+pub fn round_update_message(
+    dest_chain_id: u64,
+    new_round: u64,
+    consul_epoch: u64,
+    new_consuls: &[Pubkey],
+) -> Vec<u8> {
+    let mut payload = vec![Action::RoundUpdate.tag()];
+    payload.extend_from_slice(&new_round.to_le_bytes());
+    payload.extend_from_slice(&consul_epoch.to_le_bytes());
+    for consul in new_consuls.iter() {
+        payload.extend_from_slice(consul.as_ref());
+    }
+
+    signed_message(dest_chain_id, &payload)
+}
+
+/// Mutating actions consuls sign off on, as tagged in a `signing_preimage`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Action {
+    RoundUpdate,
+    ConsulRotation,
+    BftChange,
+}
+
+impl Action {
+    fn tag(&self) -> u8 {
+        match self {
+            Action::RoundUpdate => 0,
+            Action::ConsulRotation => 1,
+            Action::BftChange => 2,
+        }
+    }
+}
+
+/// What an action moves the contract to. Each action takes only the fields it changes:
+/// a round update `new_round`, a rotation `new_round` and `new_consuls`, a bft change `new_bft`.
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct ActionParams {
+    pub new_round: u64,
+    pub new_consuls: Vec<Pubkey>,
+    pub new_bft: u8,
+}
+
+/// Round a `round_update_message` commits to, `None` for a message that isn't one.
+pub fn message_round(message: &[u8]) -> Option<u64> {
+    round_update_field(message, 0)
+}
+
+/// Consul epoch a `round_update_message` was signed under, `None` for a message that isn't one.
+pub fn message_epoch(message: &[u8]) -> Option<u64> {
+    round_update_field(message, 8)
+}
+
+/// The `u64` at `offset` past the action tag of a `round_update_message`.
+fn round_update_field(message: &[u8], offset: usize) -> Option<u64> {
+    let (tag, fields) = message.get(CHAIN_ID_LEN..)?.split_first()?;
+    if *tag != Action::RoundUpdate.tag() {
+        return None;
+    }
+    let field = fields.get(offset..offset + 8)?;
+
+    Some(u64::from_le_bytes(<[u8; 8]>::try_from(field).ok()?))
+}
+
+/// Signs the message moving `contract` to `new_round` with its current consuls, ready to go
+/// into a `SignatureBundle` under the index of the returned key.
+#[cfg(feature = "client")]
+pub fn sign_round_update(
+    contract: &GravityContract,
+    new_round: u64,
+    keypair: &solana_sdk::signature::Keypair,
+) -> (Pubkey, ConsulSignature) {
+    use solana_sdk::signature::Signer;
+
+    let message = round_update_message(
+        contract.chain_id,
+        new_round,
+        contract.consul_epoch,
+        &contract.consuls,
+    );
+    let mut signature = [0; 64];
+    signature.copy_from_slice(keypair.sign_message(&message).as_ref());
+
+    (keypair.pubkey(), signature)
+}
+
+/// Consul indices that started and stopped signing between two rounds, given the signers of
+/// each as a bitmap with bit `i` set for consul `i`.
+pub fn signer_churn(prev_bitmap: u32, curr_bitmap: u32) -> (Vec<usize>, Vec<usize>) {
+    let indices = |bitmap: u32| (0..32).filter(move |i| bitmap & (1 << i) != 0).collect();
+
+    (
+        indices(curr_bitmap & !prev_bitmap),
+        indices(prev_bitmap & !curr_bitmap),
+    )
+}
+
+/// The check a round update failed on, in enough detail for relayers to act on.
+#[derive(PartialEq, Debug, Clone)]
+pub enum VerifyError {
+    Expired,
+    RoundRegression,
+    RoundMismatch,
+    StaleEpoch,
+    ChainMismatch,
+    NonConsul { key: Pubkey },
+    BadSignature { index: u8 },
+    BelowQuorum { got: usize, need: usize },
+    TooManyConsuls,
+}
+
+impl From<VerifyError> for ProgramError {
+    fn from(e: VerifyError) -> Self {
+        let e = match e {
+            VerifyError::Expired => GravityError::UpdateExpired,
+            VerifyError::RoundRegression => GravityError::InputRoundMismatch,
+            VerifyError::RoundMismatch => GravityError::RoundMismatch,
+            VerifyError::StaleEpoch => GravityError::StaleEpoch,
+            VerifyError::ChainMismatch => GravityError::ChainMismatch,
+            VerifyError::NonConsul { .. } => GravityError::UnknownConsul,
+            VerifyError::BadSignature { .. } => GravityError::InvalidSignature,
+            VerifyError::BelowQuorum { .. } => GravityError::InvalidBFTCount,
+            VerifyError::TooManyConsuls => GravityError::TooManyConsuls,
+        };
+        e.into()
+    }
+}
+
+/// Order of the ed25519 base point, little-endian. A signature scalar at or above it is
+/// the canonical one plus a multiple of the order, a second encoding of the same signature.
+const ED25519_ORDER: [u8; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+
+/// Whether the S half of `signature` is reduced below the group order.
+pub fn is_canonical_scalar(signature: &ConsulSignature) -> bool {
+    // compare from the most significant byte down
+    signature[32..].iter().rev().lt(ED25519_ORDER.iter().rev())
+}
+
+/// Checks `signature` in software, which on-chain costs more compute than a transaction has;
+/// programs take the result of the ed25519 program instead, through a `NativeVerifier`.
+#[cfg(any(test, feature = "client"))]
+pub fn verify_signature(signer: &Pubkey, message: &[u8], signature: &ConsulSignature) -> bool {
+    let public_key = match PublicKey::from_bytes(signer.as_ref()) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    let signature = match Signature::try_from(&signature[..]) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    public_key.verify(message, &signature).is_ok()
+}
+
+/// A secp256k1 signature followed by its recovery id, as the EVM side produces them.
+pub type RecoverableSignature = [u8; 65];
+
+/// The Ethereum address of a secp256k1 signer, the last 20 bytes of the keccak256 of its key.
+pub type EthAddress = [u8; 20];
+
+/// The address a secp256k1 consul signs as, the last 20 bytes of its `secp256k1_consul` key.
+pub fn consul_eth_address(consul: &Pubkey) -> EthAddress {
+    let mut address = [0; 20];
+    address.copy_from_slice(&consul.as_ref()[12..]);
+    address
+}
+
+/// Signature scheme a consul signs with, recorded per consul in `secp256k1_consuls`.
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ConsulKeyType {
+    Ed25519,
+    /// The consul key is the `secp256k1_consul` of the signer.
+    Secp256k1,
+}
+
+/// A consul signature in whichever scheme its consul uses.
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Clone)]
+pub enum MixedSignature {
+    Ed25519(ConsulSignature),
+    Secp256k1(RecoverableSignature),
+}
+
+/// Consul key standing for a secp256k1 signer: the keccak256 of its uncompressed public key,
+/// whose last 20 bytes are the signer's Ethereum address.
+#[cfg(any(test, feature = "client"))]
+pub fn secp256k1_consul(public_key: &secp256k1::PublicKey) -> Pubkey {
+    Pubkey::new(&keccak::hash(&public_key.serialize()[1..]).to_bytes())
+}
+
+/// Recovers the consul key of whoever signed the keccak256 of `message`, in software;
+/// programs take the secp256k1 program's recovery instead, through a `NativeVerifier`.
+#[cfg(any(test, feature = "client"))]
+pub fn recover_consul(message: &[u8], signature: &RecoverableSignature) -> Option<Pubkey> {
+    let message = secp256k1::Message::parse(&keccak::hash(message).to_bytes());
+    let recovery_id = secp256k1::RecoveryId::parse(signature[64]).ok()?;
+    let signature = secp256k1::Signature::parse_slice(&signature[..64]).ok()?;
+
+    let public_key = secp256k1::recover(&message, &signature, &recovery_id).ok()?;
+    Some(secp256k1_consul(&public_key))
+}
+
+impl GravityContract {
+    fn assert_destination(&self, message: &[u8]) -> Result<(), ProgramError> {
+        if message.get(..CHAIN_ID_LEN) != Some(&self.chain_id.to_le_bytes()[..]) {
+            return Err(GravityError::ChainMismatch.into());
+        }
+
+        Ok(())
+    }
+
+    /// Exact bytes to sign for `action`, e.g. by an HSM: the destination chain id, so the
+    /// result passes `assert_destination`, then the action tag, the round and consul epoch
+    /// the action applies to, and the parameters the action takes. Binding the current state
+    /// keeps a preimage from being reused once the contract has moved on. A round update is
+    /// the `round_update_message` the processors verify: it binds the consul set in place of
+    /// the round it moves from, rounds only moving forward already keep it from being reused.
+    pub fn signing_preimage(&self, action: Action, params: &ActionParams) -> Vec<u8> {
+        let mut payload = vec![action.tag()];
+        payload.extend_from_slice(&self.last_round.to_le_bytes());
+        payload.extend_from_slice(&self.consul_epoch.to_le_bytes());
+
+        match action {
+            Action::RoundUpdate => {
+                return round_update_message(
+                    self.chain_id,
+                    params.new_round,
+                    self.consul_epoch,
+                    &self.consuls,
+                )
+            }
+            Action::ConsulRotation => {
+                payload.extend_from_slice(&params.new_round.to_le_bytes());
+                payload.extend_from_slice(&(params.new_consuls.len() as u32).to_le_bytes());
+                for consul in params.new_consuls.iter() {
+                    payload.extend_from_slice(consul.as_ref());
+                }
+            }
+            Action::BftChange => payload.push(params.new_bft),
+        }
+
+        signed_message(self.chain_id, &payload)
+    }
+
+    /// Packs the contract followed by `signer`'s signature over the packed bytes, for
+    /// distributing state off-chain in a form `unpack_signed` can authenticate.
+    #[cfg(feature = "client")]
+    pub fn pack_signed(&self, signer: &solana_sdk::signature::Keypair) -> Vec<u8> {
+        use solana_sdk::signature::Signer;
+
+        let mut packed = vec![];
+        self.pack_into_vec(&mut packed);
+        let signature = signer.sign_message(&packed);
+        packed.extend_from_slice(signature.as_ref());
+
+        packed
+    }
+
+    /// Decodes a `pack_signed` blob, only once its signature checks out against
+    /// `expected_signer`.
+    #[cfg(feature = "client")]
+    pub fn unpack_signed(data: &[u8], expected_signer: &Pubkey) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN + SIGNATURE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (packed, signature) = data.split_at(Self::LEN);
+        let signature = ConsulSignature::try_from(signature).unwrap();
+        if !verify_signature(expected_signer, packed, &signature) {
+            return Err(GravityError::InvalidSignature.into());
+        }
+
+        Self::unpack(packed)
+    }
+
+    /// Succeeds once at least `bft` distinct consuls have validly signed `message`.
+    pub fn verify_multisig(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        message: &[u8],
+        bundle: &SignatureBundle,
+    ) -> Result<(), ProgramError> {
+        if !self
+            .verify_multisig_detailed(verifier, message, bundle)?
+            .reached_quorum
+        {
+            return Err(GravityError::InvalidBFTCount.into());
+        }
+
+        Ok(())
+    }
+
+    /// `verify_multisig` that, for `grace_slots` after the rotation into the current set,
+    /// also accepts a quorum of the set the contract rotated away from, so that updates signed
+    /// before the rotation still land. The rotation is the last entry of `rotation_log`, whose
+    /// new commitment has to be the contract's own, and the window opens at the slot it
+    /// recorded. `prev_consuls` only stand in for the old set when they hash to the entry's
+    /// old commitment; `bundle` indices then address `prev_consuls`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_multisig_with_grace(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        rotation_log: &RotationLog,
+        prev_consuls: &[Pubkey],
+        grace_slots: Slot,
+        time_source: &dyn TimeSource,
+        bundle: &SignatureBundle,
+        message: &[u8],
+    ) -> Result<(), ProgramError> {
+        let current = self.verify_multisig(verifier, message, bundle);
+        if current.is_ok() {
+            return current;
+        }
+
+        let (rotation, prev_epoch) = match (
+            rotation_log.entries.last(),
+            self.consul_epoch.checked_sub(1),
+        ) {
+            (Some(rotation), Some(prev_epoch)) => (rotation, prev_epoch),
+            _ => return current,
+        };
+        let in_grace = rotation.epoch == self.consul_epoch
+            && rotation.new_commitment == self.consul_commitment()
+            && time_source.current_slot() <= rotation.slot.saturating_add(grace_slots);
+        if !in_grace {
+            return current;
+        }
+
+        let prev = GravityContract {
+            consuls: prev_consuls.to_vec(),
+            consul_epoch: prev_epoch,
+            ..self.clone()
+        };
+        if prev.consul_commitment() != rotation.old_commitment {
+            return current;
+        }
+
+        prev.verify_multisig(verifier, message, bundle)
+    }
+
+    /// Reports which consuls signed validly, so that bad signatures surface even when
+    /// the rest of the bundle is enough for the quorum.
+    pub fn verify_multisig_detailed(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        message: &[u8],
+        bundle: &SignatureBundle,
+    ) -> Result<VerifyOutcome, ProgramError> {
+        self.assert_destination(message)?;
+
+        if !bundle.is_canonical() {
+            return Err(GravityError::NonCanonicalBundle.into());
+        }
+        if bundle.has_malleable_signature() {
+            return Err(GravityError::MalleableSignature.into());
+        }
+
+        let checked = bundle
+            .signatures
+            .iter()
+            .map(|(consul_index, signature)| {
+                self.check_signature(verifier, message, *consul_index, signature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.tally(&checked))
+    }
+
+    /// `verify_multisig_detailed` checking the signatures across threads, for relayers going
+    /// through many updates. The outcome is the same as the sequential one.
+    #[cfg(feature = "rayon")]
+    pub fn verify_multisig_parallel(
+        &self,
+        verifier: &(dyn SignatureVerifier + Sync),
+        message: &[u8],
+        bundle: &SignatureBundle,
+    ) -> Result<VerifyOutcome, ProgramError> {
+        use rayon::prelude::*;
+
+        self.assert_destination(message)?;
+
+        if !bundle.is_canonical() {
+            return Err(GravityError::NonCanonicalBundle.into());
+        }
+        if bundle.has_malleable_signature() {
+            return Err(GravityError::MalleableSignature.into());
+        }
+
+        // collecting an indexed parallel iterator keeps the bundle order
+        let checked = bundle
+            .signatures
+            .par_iter()
+            .map(|(consul_index, signature)| {
+                self.check_signature(verifier, message, *consul_index, signature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.tally(&checked))
+    }
+
+    fn check_signature(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        message: &[u8],
+        consul_index: u8,
+        signature: &ConsulSignature,
+    ) -> Result<(u8, bool), GravityError> {
+        let consul = self
+            .consuls
+            .get(consul_index as usize)
+            .ok_or(GravityError::UnknownConsul)?;
+
+        Ok((
+            consul_index,
+            verifier.verify_ed25519(consul, message, signature),
+        ))
+    }
+
+    fn tally(&self, checked: &[(u8, bool)]) -> VerifyOutcome {
+        let mut outcome = VerifyOutcome::default();
+
+        for (consul_index, is_valid) in checked.iter() {
+            if *is_valid {
+                outcome.valid_indices.push(*consul_index);
+            } else {
+                outcome.invalid_indices.push(*consul_index);
+            }
+        }
+
+        outcome.reached_quorum = outcome.valid_indices.len() >= self.bft as usize;
+        if outcome.reached_quorum {
+            outcome.participating = outcome.valid_indices[..self.bft as usize].to_vec();
+        }
+
+        outcome
+    }
+
+    /// Same as `verify_multisig` for consuls signing with secp256k1, where each signer is
+    /// recovered from its signature, so no consul indices have to be sent along. Consuls are
+    /// told apart by their `consul_eth_address`, which is what the recovery yields.
+    pub fn verify_multisig_recoverable(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        message: &[u8],
+        signatures: &[RecoverableSignature],
+    ) -> Result<(), ProgramError> {
+        self.assert_destination(message)?;
+
+        let mut signed = vec![false; self.consuls.len()];
+
+        for signature in signatures.iter() {
+            let address = verifier
+                .recover_secp256k1(message, signature)
+                .ok_or(GravityError::InvalidSignature)?;
+            let position = self
+                .consuls
+                .iter()
+                .position(|consul| consul_eth_address(consul) == address)
+                .ok_or(GravityError::UnknownConsul)?;
+
+            if signed[position] {
+                return Err(GravityError::DuplicateSignature.into());
+            }
+            signed[position] = true;
+        }
+
+        if signatures.len() < self.bft as usize {
+            return Err(GravityError::InvalidBFTCount.into());
+        }
+
+        Ok(())
+    }
+
+    /// `verify_multisig` for a set mixing ed25519 and secp256k1 consuls, each signature checked
+    /// in the scheme the contract records for its consul. Signatures are tagged with consul
+    /// indices, strictly increasing as in a canonical bundle, and count toward the quorum
+    /// whichever scheme they are in; one in another scheme than its consul's counts as invalid.
+    pub fn verify_multisig_mixed(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        message: &[u8],
+        signatures: &[(u8, MixedSignature)],
+    ) -> Result<(), ProgramError> {
+        self.assert_destination(message)?;
+
+        if !signatures.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            return Err(GravityError::NonCanonicalBundle.into());
+        }
+
+        let checked = signatures
+            .iter()
+            .map(|(consul_index, signature)| {
+                let position = *consul_index as usize;
+                let consul = self
+                    .consuls
+                    .get(position)
+                    .ok_or(GravityError::UnknownConsul)?;
+
+                let is_valid = match (self.key_type(position), signature) {
+                    (ConsulKeyType::Ed25519, MixedSignature::Ed25519(signature)) => {
+                        if !is_canonical_scalar(signature) {
+                            return Err(GravityError::MalleableSignature);
+                        }
+                        verifier.verify_ed25519(consul, message, signature)
+                    }
+                    (ConsulKeyType::Secp256k1, MixedSignature::Secp256k1(signature)) => {
+                        verifier.recover_secp256k1(message, signature)
+                            == Some(consul_eth_address(consul))
+                    }
+                    _ => false,
+                };
+
+                Ok((*consul_index, is_valid))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !self.tally(&checked).reached_quorum {
+            return Err(GravityError::InvalidBFTCount.into());
+        }
+
+        Ok(())
+    }
+
+    /// Runs every check `apply_update_round` does without touching the contract,
+    /// so that clients can tell upfront whether a round update would go through: the round
+    /// moving forward, the message binding that round and the current epoch, and the quorum.
+    /// There is no expiry check, a round update message carries no deadline; callers holding
+    /// one go through `verify_round_update`, which also refuses it once the deadline passed.
+    pub fn check_update_round(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        new_round: u64,
+        bundle: &SignatureBundle,
+        message: &[u8],
+    ) -> Result<(), ProgramError> {
+        self.check_round(new_round)?;
+        // the round argument has to be the one the consuls actually signed for
+        if message_round(message) != Some(new_round) {
+            return Err(GravityError::RoundMismatch.into());
+        }
+        if message_epoch(message) != Some(self.consul_epoch) {
+            return Err(GravityError::StaleEpoch.into());
+        }
+
+        self.verify_multisig(verifier, message, bundle)
+    }
+
+    pub fn apply_update_round(
+        &mut self,
+        verifier: &dyn SignatureVerifier,
+        new_round: u64,
+        bundle: &SignatureBundle,
+        message: &[u8],
+    ) -> Result<(), ProgramError> {
+        self.check_update_round(verifier, new_round, bundle, message)?;
+
+        Ok(self.update_round(new_round)?)
+    }
+
+    /// Same checks as `check_update_round` for signatures tagged with their signer's key,
+    /// together with a `deadline` slot, reporting exactly which one failed.
+    pub fn verify_round_update(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        new_round: u64,
+        message: &[u8],
+        signatures: &[(Pubkey, ConsulSignature)],
+        time_source: &dyn TimeSource,
+        deadline: Slot,
+    ) -> Result<(), VerifyError> {
+        if time_source.current_slot() > deadline {
+            return Err(VerifyError::Expired);
+        }
+        if self.check_round(new_round).is_err() {
+            return Err(VerifyError::RoundRegression);
+        }
+        if message_round(message) != Some(new_round) {
+            return Err(VerifyError::RoundMismatch);
+        }
+        if message_epoch(message) != Some(self.consul_epoch) {
+            return Err(VerifyError::StaleEpoch);
+        }
+        if self.assert_destination(message).is_err() {
+            return Err(VerifyError::ChainMismatch);
+        }
+
+        let consul_index = self
+            .consul_index()
+            .map_err(|_| VerifyError::TooManyConsuls)?;
+        let mut signed = vec![false; self.consuls.len()];
+
+        for (key, signature) in signatures.iter() {
+            let index = consul_index
+                .by_key(key)
+                .ok_or(VerifyError::NonConsul { key: *key })?;
+
+            if !verifier.verify_ed25519(key, message, signature) {
+                return Err(VerifyError::BadSignature { index });
+            }
+            signed[index as usize] = true;
+        }
+
+        let got = signed.iter().filter(|signed| **signed).count();
+        let need = self.bft as usize;
+        if got < need {
+            return Err(VerifyError::BelowQuorum { got, need });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the quorum signed `root` for this chain and that `leaf` belongs to the tree behind it.
+    pub fn verify_merkle_data(
+        &self,
+        verifier: &dyn SignatureVerifier,
+        root: MerkleHash,
+        bundle: &SignatureBundle,
+        leaf: &[u8],
+        proof: &[MerkleHash],
+    ) -> Result<(), ProgramError> {
+        self.verify_multisig(verifier, &signed_message(self.chain_id, &root), bundle)?;
+
+        if !verify_merkle_inclusion(root, leaf, proof) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gravity_misc::merkle::{hash_leaf, hash_node};
+    use solana_program::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::gravity::{state::RotationEntry, time::MockClock, verifier::SoftwareVerifier};
+
+    fn build_contract(consuls: &[Keypair], bft: u8) -> GravityContract {
+        GravityContract {
+            bft,
+            consuls: consuls.iter().map(|consul| consul.pubkey()).collect(),
+            ..GravityContract::default()
+        }
+    }
+
+    fn sign(consul: &Keypair, message: &[u8]) -> ConsulSignature {
+        let mut signature = [0; 64];
+        signature.copy_from_slice(consul.sign_message(message).as_ref());
+        signature
+    }
+
+    #[test]
+    fn test_verify_multisig() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+        let message = &signed_message(0, b"round 8");
+
+        let bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], message)),
+                (2, sign(&consuls[2], message)),
+            ],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle),
+            Ok(())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig(
+                &SoftwareVerifier,
+                &signed_message(0, b"round 9"),
+                &bundle
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+
+        let duplicated = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], message)),
+                (0, sign(&consuls[0], message)),
+            ],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &duplicated),
+            Err(GravityError::NonCanonicalBundle.into())
+        );
+
+        let unknown = SignatureBundle {
+            signatures: vec![(3, sign(&Keypair::new(), message))],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &unknown),
+            Err(GravityError::UnknownConsul.into())
+        );
+    }
+
+    #[test]
+    fn test_verify_multisig_with_grace() {
+        let old_consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let new_consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let prev_consuls: Vec<Pubkey> = old_consuls.iter().map(|consul| consul.pubkey()).collect();
+
+        let mut gravity_contract_info = GravityContract {
+            consul_epoch: 4,
+            ..build_contract(&old_consuls, 2)
+        };
+        let old_commitment = gravity_contract_info.consul_commitment();
+        gravity_contract_info
+            .set_consuls(new_consuls.iter().map(|consul| consul.pubkey()).collect())
+            .unwrap();
+        let rotation = RotationEntry {
+            epoch: gravity_contract_info.consul_epoch,
+            old_commitment,
+            new_commitment: gravity_contract_info.consul_commitment(),
+            initiator: Pubkey::new_unique(),
+            slot: 100,
+        };
+        let log_of = |entries: Vec<RotationEntry>| RotationLog {
+            gravity_contract: Pubkey::new_unique(),
+            entries,
+        };
+        let rotation_log = log_of(vec![rotation.clone()]);
+
+        let message = &signed_message(0, b"round 8");
+        let quorum = |consuls: &[Keypair]| SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], message)),
+                (1, sign(&consuls[1], message)),
+            ],
+        };
+        let mut clock = MockClock { slot: 100 };
+        let verify = |clock: &MockClock,
+                      rotation_log: &RotationLog,
+                      prev_consuls: &[Pubkey],
+                      bundle: &SignatureBundle| {
+            gravity_contract_info.verify_multisig_with_grace(
+                &SoftwareVerifier,
+                rotation_log,
+                prev_consuls,
+                10,
+                clock,
+                bundle,
+                message,
+            )
+        };
+
+        assert_eq!(
+            verify(&clock, &rotation_log, &prev_consuls, &quorum(&new_consuls)),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&clock, &rotation_log, &prev_consuls, &quorum(&old_consuls)),
+            Ok(())
+        );
+
+        clock.advance(10);
+        assert_eq!(
+            verify(&clock, &rotation_log, &prev_consuls, &quorum(&old_consuls)),
+            Ok(())
+        );
+
+        clock.advance(1);
+        assert_eq!(
+            verify(&clock, &rotation_log, &prev_consuls, &quorum(&old_consuls)),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+        assert_eq!(
+            verify(&clock, &rotation_log, &prev_consuls, &quorum(&new_consuls)),
+            Ok(())
+        );
+
+        // the caller can't pass off another set as the previous one
+        let clock = MockClock { slot: 100 };
+        let impostors: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let impostor_keys: Vec<Pubkey> = impostors.iter().map(|consul| consul.pubkey()).collect();
+        assert_eq!(
+            verify(&clock, &rotation_log, &impostor_keys, &quorum(&impostors)),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+
+        // nor a log whose last entry is some other rotation, or without any
+        let earlier = RotationEntry {
+            epoch: rotation.epoch - 1,
+            ..rotation.clone()
+        };
+        let foreign = RotationEntry {
+            new_commitment: Hash::default(),
+            ..rotation.clone()
+        };
+        for rotation_log in [
+            log_of(vec![rotation.clone(), earlier]),
+            log_of(vec![foreign]),
+            log_of(vec![]),
+        ] {
+            assert_eq!(
+                verify(&clock, &rotation_log, &prev_consuls, &quorum(&old_consuls)),
+                Err(GravityError::InvalidBFTCount.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_multisig_detailed() {
+        let consuls: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+        let message = &signed_message(0, b"round 8");
+
+        let bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], message)),
+                (1, sign(&consuls[1], &signed_message(0, b"round 9"))),
+                (3, sign(&consuls[3], message)),
+            ],
+        };
+
+        assert_eq!(
+            gravity_contract_info.verify_multisig_detailed(&SoftwareVerifier, message, &bundle),
+            Ok(VerifyOutcome {
+                reached_quorum: true,
+                valid_indices: vec![0, 3],
+                invalid_indices: vec![1],
+                participating: vec![0, 3],
+            })
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_verify_multisig_participating() {
+        let consuls: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+        let message = &signed_message(0, b"round 8");
+
+        // consul #0 signs another round, so it can't take part in the quorum
+        let bundles = [vec![1, 2, 3], vec![0, 1, 2, 3], vec![0, 2, 3]];
+        let expected = [vec![1, 2], vec![1, 2], vec![2, 3]];
+
+        for (indices, participating) in bundles.iter().zip(expected.iter()) {
+            let bundle = SignatureBundle {
+                signatures: indices
+                    .iter()
+                    .map(|i| match i {
+                        0 => (0, sign(&consuls[0], &signed_message(0, b"round 9"))),
+                        _ => (*i, sign(&consuls[*i as usize], message)),
+                    })
+                    .collect(),
+            };
+
+            let outcome = gravity_contract_info
+                .verify_multisig_detailed(&SoftwareVerifier, message, &bundle)
+                .unwrap();
+            assert_eq!(&outcome.participating, participating);
+        }
+
+        let short = SignatureBundle {
+            signatures: vec![(2, sign(&consuls[2], message))],
+        };
+        let outcome = gravity_contract_info
+            .verify_multisig_detailed(&SoftwareVerifier, message, &short)
+            .unwrap();
+        assert!(!outcome.reached_quorum);
+        assert!(outcome.participating.is_empty());
+    }
+
+    #[test]
+    fn test_non_canonical_bundle() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+        let message = &signed_message(0, b"round 8");
+
+        let bundle = |indices: &[u8]| SignatureBundle {
+            signatures: indices
+                .iter()
+                .map(|i| (*i, sign(&consuls[*i as usize], message)))
+                .collect(),
+        };
+
+        assert!(bundle(&[0, 1, 2]).is_canonical());
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle(&[0, 1, 2])),
+            Ok(())
+        );
+
+        for indices in [[0, 1, 1], [2, 1, 0]].iter() {
+            assert!(!bundle(indices).is_canonical());
+            assert_eq!(
+                gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle(indices)),
+                Err(GravityError::NonCanonicalBundle.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_malleable_signature() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+        let message = &signed_message(0, b"round 8");
+
+        let signature = sign(&consuls[1], message);
+        assert!(is_canonical_scalar(&signature));
+
+        // S + L encodes the same signature under a different scalar
+        let mut malleated = signature;
+        let mut carry = 0;
+        for (byte, order) in malleated[32..].iter_mut().zip(ED25519_ORDER.iter()) {
+            let sum = *byte as u16 + *order as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!is_canonical_scalar(&malleated));
+
+        let mut at_order = signature;
+        at_order[32..].copy_from_slice(&ED25519_ORDER);
+        assert!(!is_canonical_scalar(&at_order));
+
+        let bundle = SignatureBundle {
+            signatures: vec![(0, sign(&consuls[0], message)), (1, malleated)],
+        };
+        assert!(bundle.has_malleable_signature());
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle),
+            Err(GravityError::MalleableSignature.into())
+        );
+    }
+
+    #[test]
+    fn test_size_saved_vs_full() {
+        let bundle = SignatureBundle {
+            signatures: (0..5).map(|i| (i, [i; 64])).collect(),
+        };
+        assert_eq!(bundle.size_saved_vs_full(), 155);
+
+        let full: Vec<(Pubkey, ConsulSignature)> =
+            (0..5).map(|i| (Pubkey::new_unique(), [i; 64])).collect();
+        assert_eq!(
+            full.try_to_vec().unwrap().len() - bundle.try_to_vec().unwrap().len(),
+            bundle.size_saved_vs_full()
+        );
+        assert_eq!(SignatureBundle::default().size_saved_vs_full(), 0);
+    }
+
+    #[test]
+    fn test_canonicalize_bundle() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 3);
+        let message = &signed_message(0, b"round 8");
+
+        let signatures: Vec<(u8, ConsulSignature)> = (0..3)
+            .map(|i| (i, sign(&consuls[i as usize], message)))
+            .collect();
+        let mut bundle = SignatureBundle {
+            signatures: vec![
+                signatures[2],
+                signatures[0],
+                signatures[2],
+                signatures[1],
+                signatures[0],
+            ],
+        };
+        assert!(!bundle.is_canonical());
+
+        bundle.canonicalize();
+        assert!(bundle.is_canonical());
+        assert_eq!(bundle.signatures, signatures);
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, message, &bundle),
+            Ok(())
+        );
+
+        let canonical = bundle.clone();
+        bundle.canonicalize();
+        assert_eq!(bundle, canonical);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_pack_signed() {
+        let signer = Keypair::new();
+        let gravity_contract_info = GravityContract {
+            last_round: 7,
+            ..build_contract(&[Keypair::new(), Keypair::new()], 2)
+        };
+
+        let signed = gravity_contract_info.pack_signed(&signer);
+        assert_eq!(signed.len(), GravityContract::LEN + SIGNATURE_LEN);
+        assert_eq!(
+            GravityContract::unpack_signed(&signed, &signer.pubkey()),
+            Ok(gravity_contract_info)
+        );
+        assert_eq!(
+            GravityContract::unpack_signed(&signed, &Keypair::new().pubkey()),
+            Err(GravityError::InvalidSignature.into())
+        );
+
+        // lowering the bft in the blob breaks the signature
+        let mut tampered = signed.clone();
+        tampered[gravity_misc::discriminator::DISCRIMINATOR_LEN + 32] = 1;
+        assert_eq!(
+            GravityContract::unpack_signed(&tampered, &signer.pubkey()),
+            Err(GravityError::InvalidSignature.into())
+        );
+        assert_eq!(
+            GravityContract::unpack_signed(&signed[..signed.len() - 1], &signer.pubkey()),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_sign_round_update() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let mut gravity_contract_info = build_contract(&consuls, 2);
+        gravity_contract_info.chain_id = 7;
+
+        let index = gravity_contract_info.consul_index().unwrap();
+        let mut bundle = SignatureBundle::default();
+        for consul in consuls[1..].iter() {
+            let (key, signature) = sign_round_update(&gravity_contract_info, 8, consul);
+            bundle
+                .signatures
+                .push((index.by_key(&key).unwrap(), signature));
+        }
+
+        let message = round_update_message(7, 8, 0, &gravity_contract_info.consuls);
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, &message, &bundle),
+            Ok(())
+        );
+
+        let other_round = round_update_message(7, 9, 0, &gravity_contract_info.consuls);
+        assert!(gravity_contract_info
+            .verify_multisig(&SoftwareVerifier, &other_round, &bundle)
+            .is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_verify_multisig_parallel() {
+        let consuls: Vec<Keypair> = (0..64).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 40);
+        let message = &signed_message(0, b"round 8");
+
+        // every fifth consul signs something else
+        let signatures: Vec<(u8, ConsulSignature)> = (0..64)
+            .map(|i| {
+                let signed = if i % 5 == 0 { &b"round 9"[..] } else { message };
+                (i as u8, sign(&consuls[i], signed))
+            })
+            .collect();
+
+        for bft in [40, 60].iter() {
+            let gravity_contract_info = GravityContract {
+                bft: *bft,
+                ..gravity_contract_info.clone()
+            };
+            let bundle = SignatureBundle {
+                signatures: signatures.clone(),
+            };
+
+            let sequential = gravity_contract_info
+                .verify_multisig_detailed(&SoftwareVerifier, message, &bundle)
+                .unwrap();
+            let parallel = gravity_contract_info
+                .verify_multisig_parallel(&SoftwareVerifier, message, &bundle)
+                .unwrap();
+            assert_eq!(parallel, sequential);
+            assert_eq!(parallel.valid_indices.len(), 51);
+            assert_eq!(parallel.reached_quorum, *bft == 40);
+        }
+
+        // a consul counted twice is refused rather than double-counted
+        let mut duplicated = SignatureBundle {
+            signatures: signatures[1..3].to_vec(),
+        };
+        duplicated.signatures.push(signatures[2]);
+        assert_eq!(
+            gravity_contract_info.verify_multisig_parallel(&SoftwareVerifier, message, &duplicated),
+            Err(GravityError::NonCanonicalBundle.into())
+        );
+
+        let unknown = SignatureBundle {
+            signatures: vec![(64, signatures[0].1)],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_multisig_parallel(&SoftwareVerifier, message, &unknown),
+            Err(GravityError::UnknownConsul.into())
+        );
+    }
+
+    #[test]
+    fn test_signer_churn() {
+        // consul 3 joins, consul 1 drops, consuls 0 and 2 keep signing
+        assert_eq!(signer_churn(0b0111, 0b1101), (vec![3], vec![1]));
+        assert_eq!(signer_churn(0b0101, 0b0101), (vec![], vec![]));
+        assert_eq!(signer_churn(0, 1 << 31), (vec![31], vec![]));
+        assert_eq!(signer_churn(0b11, 0), (vec![], vec![0, 1]));
+    }
+
+    #[test]
+    fn test_verify_multisig_destination() {
+        let consuls: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let new_consuls: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+        let mut gravity_contract_info = build_contract(&consuls, 2);
+        gravity_contract_info.chain_id = 1;
+
+        let message = round_update_message(2, 8, 0, &new_consuls);
+        let bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], &message)),
+                (1, sign(&consuls[1], &message)),
+            ],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, &message, &bundle),
+            Err(GravityError::ChainMismatch.into())
+        );
+
+        // the same signatures can't be passed off as covering this chain's message
+        assert_eq!(
+            gravity_contract_info
+                .verify_multisig(&SoftwareVerifier, &round_update_message(1, 8, 0, &new_consuls), &bundle),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+
+        gravity_contract_info.chain_id = 2;
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, &message, &bundle),
+            Ok(())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig(&SoftwareVerifier, &[], &bundle),
+            Err(GravityError::ChainMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_check_update_round() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = GravityContract {
+            last_round: 7,
+            ..build_contract(&consuls, 2)
+        };
+        let message = round_update_message(0, 8, 0, &gravity_contract_info.consuls);
+
+        let quorum = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], &message)),
+                (1, sign(&consuls[1], &message)),
+            ],
+        };
+        let partial = SignatureBundle {
+            signatures: vec![(2, sign(&consuls[2], &message))],
+        };
+
+        for (new_round, bundle, accepted) in [
+            (8, &quorum, true),
+            (7, &quorum, false),
+            (8, &partial, false),
+        ] {
+            let dry_run = gravity_contract_info.check_update_round(
+                &SoftwareVerifier,
+                new_round,
+                bundle,
+                &message,
+            );
+
+            let mut applied = gravity_contract_info.clone();
+            let result = applied.apply_update_round(&SoftwareVerifier, new_round, bundle, &message);
+
+            assert_eq!(dry_run, result);
+            assert_eq!(dry_run.is_ok(), accepted);
+            assert_eq!(applied.last_round, if accepted { new_round } else { 7 });
+        }
+
+        // signed before the consuls were rotated into epoch 1
+        let rotated = GravityContract {
+            consul_epoch: 1,
+            ..gravity_contract_info.clone()
+        };
+        let mut applied = rotated.clone();
+        assert_eq!(
+            applied.apply_update_round(&SoftwareVerifier, 8, &quorum, &message),
+            Err(GravityError::StaleEpoch.into())
+        );
+        assert_eq!(applied.last_round, 7);
+        let current = round_update_message(0, 8, 1, &rotated.consuls);
+        let quorum_current = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], &current)),
+                (1, sign(&consuls[1], &current)),
+            ],
+        };
+        assert_eq!(
+            applied.apply_update_round(&SoftwareVerifier, 8, &quorum_current, &current),
+            Ok(())
+        );
+
+        // the instruction asks for round 9 while the consuls signed for round 8
+        let mut applied = gravity_contract_info.clone();
+        assert_eq!(
+            applied.apply_update_round(&SoftwareVerifier, 9, &quorum, &message),
+            Err(GravityError::RoundMismatch.into())
+        );
+        assert_eq!(applied.last_round, 7);
+        assert_eq!(
+            gravity_contract_info.check_update_round(
+                &SoftwareVerifier,
+                9,
+                &quorum,
+                &message[..CHAIN_ID_LEN]
+            ),
+            Err(GravityError::RoundMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_signing_preimage() {
+        let consul = Pubkey::new_from_array([9; 32]);
+        let gravity_contract_info = GravityContract {
+            last_round: 7,
+            consul_epoch: 2,
+            chain_id: 5,
+            ..build_contract(&[], 1)
+        };
+        let params = ActionParams {
+            new_round: 8,
+            new_consuls: vec![consul],
+            new_bft: 3,
+        };
+
+        let preimage = |action| gravity_contract_info.signing_preimage(action, &params);
+
+        let state = [7u64.to_le_bytes().as_ref(), 2u64.to_le_bytes().as_ref()].concat();
+        // a round update binds the consul set rather than the round it moves from
+        assert_eq!(
+            preimage(Action::RoundUpdate),
+            [
+                &5u64.to_le_bytes()[..],
+                &[0],
+                &8u64.to_le_bytes(),
+                &2u64.to_le_bytes()
+            ]
+            .concat()
+        );
+        assert_eq!(
+            preimage(Action::RoundUpdate),
+            round_update_message(5, 8, 2, &gravity_contract_info.consuls)
+        );
+        assert_eq!(message_round(&preimage(Action::RoundUpdate)), Some(8));
+        assert_eq!(message_epoch(&preimage(Action::RoundUpdate)), Some(2));
+        // no other action reads as a round update
+        assert_eq!(message_round(&preimage(Action::ConsulRotation)), None);
+        assert_eq!(message_epoch(&preimage(Action::BftChange)), None);
+        assert_eq!(
+            preimage(Action::ConsulRotation),
+            [
+                &5u64.to_le_bytes()[..],
+                &[1],
+                &state,
+                &8u64.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                consul.as_ref(),
+            ]
+            .concat()
+        );
+        assert_eq!(
+            preimage(Action::BftChange),
+            [&5u64.to_le_bytes()[..], &[2], &state, &[3]].concat()
+        );
+
+        let actions = [
+            Action::RoundUpdate,
+            Action::ConsulRotation,
+            Action::BftChange,
+        ];
+        for (i, action) in actions.iter().enumerate() {
+            assert!(gravity_contract_info
+                .assert_destination(&preimage(*action))
+                .is_ok());
+            for other in actions[i + 1..].iter() {
+                assert_ne!(preimage(*action), preimage(*other));
+            }
+        }
+
+        // the same action is another preimage once the contract has moved on
+        let moved = GravityContract {
+            last_round: 8,
+            ..gravity_contract_info.clone()
+        };
+        assert_ne!(
+            moved.signing_preimage(Action::BftChange, &params),
+            preimage(Action::BftChange)
+        );
+    }
+
+    #[test]
+    fn test_verify_round_update() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = GravityContract {
+            last_round: 7,
+            ..build_contract(&consuls, 2)
+        };
+        let message = round_update_message(0, 8, 0, &gravity_contract_info.consuls);
+        let clock = MockClock { slot: 100 };
+
+        let signed = |i: usize, message: &[u8]| (consuls[i].pubkey(), sign(&consuls[i], message));
+        let quorum = [signed(0, &message), signed(2, &message)];
+
+        let verify =
+            |new_round, message: &[u8], signatures: &[(Pubkey, ConsulSignature)], deadline| {
+                gravity_contract_info
+                    .verify_round_update(&SoftwareVerifier, new_round, message, signatures, &clock, deadline)
+            };
+
+        assert_eq!(verify(8, &message, &quorum, 100), Ok(()));
+        assert_eq!(verify(8, &message, &quorum, 99), Err(VerifyError::Expired));
+        assert_eq!(
+            verify(7, &message, &quorum, 100),
+            Err(VerifyError::RoundRegression)
+        );
+        assert_eq!(
+            verify(9, &message, &quorum, 100),
+            Err(VerifyError::RoundMismatch)
+        );
+
+        let stale = GravityContract {
+            consul_epoch: 1,
+            ..gravity_contract_info.clone()
+        };
+        assert_eq!(
+            stale.verify_round_update(&SoftwareVerifier, 8, &message, &quorum, &clock, 100),
+            Err(VerifyError::StaleEpoch)
+        );
+
+        let elsewhere = round_update_message(1, 8, 0, &gravity_contract_info.consuls);
+        assert_eq!(
+            verify(8, &elsewhere, &[signed(0, &elsewhere)], 100),
+            Err(VerifyError::ChainMismatch)
+        );
+
+        let stranger = Keypair::new();
+        assert_eq!(
+            verify(
+                8,
+                &message,
+                &[(stranger.pubkey(), sign(&stranger, &message))],
+                100
+            ),
+            Err(VerifyError::NonConsul {
+                key: stranger.pubkey()
+            })
+        );
+        assert_eq!(
+            verify(
+                8,
+                &message,
+                &[signed(0, &message), signed(1, &elsewhere)],
+                100
+            ),
+            Err(VerifyError::BadSignature { index: 1 })
+        );
+        assert_eq!(
+            verify(
+                8,
+                &message,
+                &[signed(1, &message), signed(1, &message)],
+                100
+            ),
+            Err(VerifyError::BelowQuorum { got: 1, need: 2 })
+        );
+
+        assert_eq!(
+            ProgramError::from(VerifyError::BelowQuorum { got: 1, need: 2 }),
+            GravityError::InvalidBFTCount.into()
+        );
+    }
+
+    fn secp256k1_signer(seed: u8) -> (secp256k1::SecretKey, Pubkey) {
+        let secret_key = secp256k1::SecretKey::parse(&[seed; 32]).unwrap();
+        let consul = secp256k1_consul(&secp256k1::PublicKey::from_secret_key(&secret_key));
+        (secret_key, consul)
+    }
+
+    fn secp256k1_sign(secret_key: &secp256k1::SecretKey, message: &[u8]) -> RecoverableSignature {
+        let message = secp256k1::Message::parse(&keccak::hash(message).to_bytes());
+        let (signature, recovery_id) = secp256k1::sign(&message, secret_key);
+
+        let mut recoverable = [0; 65];
+        recoverable[..64].copy_from_slice(&signature.serialize());
+        recoverable[64] = recovery_id.serialize();
+        recoverable
+    }
+
+    #[test]
+    fn test_verify_multisig_recoverable() {
+        let signers: Vec<(secp256k1::SecretKey, Pubkey)> = (1..4).map(secp256k1_signer).collect();
+        let gravity_contract_info = GravityContract {
+            bft: 2,
+            consuls: signers.iter().map(|(_, consul)| *consul).collect(),
+            ..GravityContract::default()
+        };
+        let message = &signed_message(0, b"round 8");
+
+        let signature = secp256k1_sign(&signers[1].0, message);
+        assert_eq!(recover_consul(message, &signature), Some(signers[1].1));
+
+        let signatures = [signature, secp256k1_sign(&signers[2].0, message)];
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(
+                &SoftwareVerifier,
+                message,
+                &signatures
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(
+                &SoftwareVerifier,
+                message,
+                &signatures[..1]
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(
+                &SoftwareVerifier,
+                message,
+                &[signature, signature]
+            ),
+            Err(GravityError::DuplicateSignature.into())
+        );
+
+        let (stranger, _) = secp256k1_signer(9);
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(
+                &SoftwareVerifier,
+                message,
+                &[signature, secp256k1_sign(&stranger, message)]
+            ),
+            Err(GravityError::UnknownConsul.into())
+        );
+
+        let mut malformed = signature;
+        malformed[64] = 7;
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(
+                &SoftwareVerifier,
+                message,
+                &[malformed]
+            ),
+            Err(GravityError::InvalidSignature.into())
+        );
+    }
+
+    #[test]
+    fn test_verify_multisig_mixed() {
+        let ed25519_consuls: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let secp256k1_consuls: Vec<(secp256k1::SecretKey, Pubkey)> =
+            (1..3).map(secp256k1_signer).collect();
+
+        let mut gravity_contract_info = build_contract(&ed25519_consuls, 3);
+        gravity_contract_info
+            .consuls
+            .extend(secp256k1_consuls.iter().map(|(_, consul)| *consul));
+        let key_types = [
+            ConsulKeyType::Ed25519,
+            ConsulKeyType::Ed25519,
+            ConsulKeyType::Secp256k1,
+            ConsulKeyType::Secp256k1,
+        ];
+        let unmarked = gravity_contract_info.clone();
+        gravity_contract_info.set_key_types(&key_types).unwrap();
+        assert_eq!(gravity_contract_info.secp256k1_consuls, 0b1100);
+        let message = &signed_message(0, b"round 8");
+
+        let ed25519 = |i: usize| {
+            (
+                i as u8,
+                MixedSignature::Ed25519(sign(&ed25519_consuls[i], message)),
+            )
+        };
+        let secp256k1 = |i: usize| {
+            (
+                i as u8 + 2,
+                MixedSignature::Secp256k1(secp256k1_sign(&secp256k1_consuls[i].0, message)),
+            )
+        };
+
+        // neither scheme reaches the quorum of three on its own
+        assert_eq!(
+            gravity_contract_info.verify_multisig_mixed(
+                &SoftwareVerifier,
+                message,
+                &[ed25519(0), secp256k1(0), secp256k1(1)]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig_mixed(
+                &SoftwareVerifier,
+                message,
+                &[ed25519(0), ed25519(1)]
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+
+        // an ed25519 signature handed in for a secp256k1 consul doesn't count
+        let misrouted = (
+            2,
+            MixedSignature::Ed25519(sign(&ed25519_consuls[1], message)),
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig_mixed(
+                &SoftwareVerifier,
+                message,
+                &[ed25519(0), ed25519(1), misrouted]
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+
+        assert_eq!(
+            gravity_contract_info.verify_multisig_mixed(
+                &SoftwareVerifier,
+                message,
+                &[secp256k1(0), ed25519(0), ed25519(1)]
+            ),
+            Err(GravityError::NonCanonicalBundle.into())
+        );
+        // the scheme comes from the contract, which takes every consul for ed25519 by default
+        assert_eq!(
+            unmarked.verify_multisig_mixed(
+                &SoftwareVerifier,
+                message,
+                &[ed25519(0), secp256k1(0), secp256k1(1)]
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+        let mut rotated = gravity_contract_info.clone();
+        rotated.set_consuls(unmarked.consuls[1..].to_vec()).unwrap();
+        assert_eq!(rotated.secp256k1_consuls, 0);
+        assert!(matches!(
+            unmarked.clone().set_key_types(&key_types[..3]),
+            Err(GravityError::KeyTypeMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_verify_merkle_data() {
+        let consuls: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let gravity_contract_info = build_contract(&consuls, 2);
+
+        let leaves = [hash_leaf(b"btc"), hash_leaf(b"eth")];
+        let root = hash_node(&leaves[0], &leaves[1]);
+        let message = signed_message(0, &root);
+
+        let bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], &message)),
+                (1, sign(&consuls[1], &message)),
+            ],
+        };
+
+        assert_eq!(
+            gravity_contract_info.verify_merkle_data(
+                &SoftwareVerifier,
+                root,
+                &bundle,
+                b"btc",
+                &[leaves[1]]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            gravity_contract_info.verify_merkle_data(
+                &SoftwareVerifier,
+                root,
+                &bundle,
+                b"sol",
+                &[leaves[1]]
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        let partial = SignatureBundle {
+            signatures: vec![(0, sign(&consuls[0], &message))],
+        };
+        assert_eq!(
+            gravity_contract_info.verify_merkle_data(
+                &SoftwareVerifier,
+                root,
+                &partial,
+                b"btc",
+                &[leaves[1]]
+            ),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+    }
+}
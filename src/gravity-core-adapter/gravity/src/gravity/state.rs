@@ -1,12 +1,38 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
 
+use arrayref::array_ref;
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use solana_program::{
+    account_info::AccountInfo,
+    clock::Slot,
+    hash::{self, hashv, Hash},
+    keccak,
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
+    rent::Rent,
+    system_program,
 };
+use spl_token::state::Multisig;
+
+use gravity_misc::discriminator::{
+    AccountKind, Discriminator, CONSUL_SHARD, DISCRIMINATOR_LEN, GRAVITY_CONTRACT, NEBULA_REGISTRY,
+    PENDING_UPDATE, ROTATION_LOG,
+};
+use gravity_misc::merkle::{
+    hash_leaf, merkle_proof, merkle_root, verify_merkle_inclusion, MerkleHash,
+};
+use gravity_misc::validation::is_contract_empty;
+
+use crate::gravity::error::GravityError;
+use crate::gravity::instruction::GravityContractInstruction;
+use crate::gravity::signature::{is_canonical_scalar, Action, ActionParams, ConsulKeyType};
+use crate::gravity::time::TimeSource;
+use crate::gravity::verifier::SignatureVerifier;
 
 #[derive(
     BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, PartialOrd, Default, Debug, Clone,
@@ -19,6 +45,62 @@ pub struct GravityContract {
     pub consuls: Vec<Pubkey>,
     pub last_round: u64,
     pub multisig_account: Pubkey,
+    pub consul_epoch: u64,
+    /// Chain that consul signatures verified by this contract must be addressed to, set by
+    /// the init instruction. Contracts migrated from layout version 1 read as chain 0.
+    pub chain_id: u64,
+    /// Slot of the last round update, set by the processor.
+    pub last_update_slot: Slot,
+    /// Consuls signing with secp256k1 rather than ed25519, bit `i` standing for consul `i`.
+    /// Cleared by any change to the set, whose positions it no longer matches.
+    pub secp256k1_consuls: u8,
+    /// Number of `ConsulShard` accounts the consul set is also kept across, see
+    /// `load_consuls_from_shards`; 0 when it isn't.
+    pub consul_shards: u8,
+}
+
+/// How hard it is for a colluding minority of consuls to push an update through.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum SecurityLevel {
+    Weak,
+    Adequate,
+    Strong,
+}
+
+/// Digest the other side of the bridge commits to the consul set with.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum HashAlgo {
+    Sha256,
+    Keccak256,
+}
+
+/// Whether `unpack_checked` still falls back to the layout before the discriminator.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum StrictMode {
+    /// Accounts not migrated yet decode through `unpack_pre_discriminator`.
+    Off,
+    /// Such accounts fail with `LegacyLayoutRejected`, for once every one was migrated.
+    On,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FieldType {
+    Discriminator,
+    Pubkey,
+    U8,
+    U64,
+    /// A little-endian `u32` count followed by that many 32-byte keys.
+    PubkeyVec,
+    Zeroed,
+}
+
+/// Where one field of a packed account lives, for generating decoders in other languages.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub offset: usize,
+    pub len: usize,
+    pub field_type: FieldType,
 }
 
 impl fmt::Display for GravityContract {
@@ -28,8 +110,21 @@ impl fmt::Display for GravityContract {
             "initializer_pubkey: {:};
              consuls: {:?};
              bft: {:};
-             last_round: {:}",
-            self.initializer_pubkey, self.consuls, self.bft, self.last_round
+             last_round: {:};
+             consul_epoch: {:};
+             chain_id: {:};
+             last_update_slot: {:};
+             secp256k1_consuls: {:#010b};
+             consul_shards: {:}",
+            self.initializer_pubkey,
+            self.consuls,
+            self.bft,
+            self.last_round,
+            self.consul_epoch,
+            self.chain_id,
+            self.last_update_slot,
+            self.secp256k1_consuls,
+            self.consul_shards
         )
     }
 }
@@ -47,7 +142,7 @@ pub trait PartialStorage {
 }
 
 impl PartialStorage for GravityContract {
-    const DATA_RANGE: std::ops::Range<usize> = 0..299;
+    const DATA_RANGE: std::ops::Range<usize> = 0..<Self as Pack>::LEN;
 }
 
 impl Sealed for GravityContract {}
@@ -58,22 +153,5301 @@ impl IsInitialized for GravityContract {
     }
 }
 
+/// Splits off the leading discriminator, failing when `src` holds another state type.
+fn strip_discriminator<'a>(
+    src: &'a [u8],
+    expected: &Discriminator,
+) -> Result<&'a [u8], ProgramError> {
+    if src.len() < DISCRIMINATOR_LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let (discriminator, src) = src.split_at(DISCRIMINATOR_LEN);
+    if discriminator != expected {
+        return Err(GravityError::WrongAccountType.into());
+    }
+
+    Ok(src)
+}
+
+fn write_discriminator<'a>(dst: &'a mut [u8], discriminator: &Discriminator) -> &'a mut [u8] {
+    let (prefix, dst) = dst.split_at_mut(DISCRIMINATOR_LEN);
+    prefix.copy_from_slice(discriminator);
+    dst
+}
+
+/// Whether `key` decompresses to a point of the ed25519 curve, as every signing key does.
+fn is_ed25519_point(key: &Pubkey) -> bool {
+    CompressedEdwardsY::from_slice(key.as_ref())
+        .decompress()
+        .is_some()
+}
+
 impl Pack for GravityContract {
-    const LEN: usize = 299;
+    const LEN: usize = DISCRIMINATOR_LEN + 295 + Self::RESERVED_LEN;
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let mut mut_src: &[u8] = src;
-        Self::deserialize(&mut mut_src).map_err(|err| {
+        let result = Self::unpack_contract(src);
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::increment_counter!("gravity_unpack_total");
+            if result.is_err() {
+                metrics::increment_counter!("gravity_unpack_errors_total");
+            }
+        }
+
+        result
+    }
+
+    /// `try_pack_into_slice`, panicking when the contract doesn't fit `dst`. The processors
+    /// call `try_pack_into_slice` itself, so that a contract they can't write fails the
+    /// instruction instead.
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        self.try_pack_into_slice(dst).unwrap();
+    }
+}
+
+// refuses to compile once `secp256k1_consuls` has no bit left for the last consul
+const _: [(); 0] = [(); (GravityContract::MAX_CONSULS > 8) as usize];
+
+// refuses to compile once the largest contract no longer fits `LEN`
+const _: [(); 0] =
+    [(); (DISCRIMINATOR_LEN + GravityContract::MAX_ENCODED_LEN + GravityContract::RESERVED_LEN
+        > <GravityContract as Pack>::LEN) as usize];
+
+impl GravityContract {
+    /// Leads the packed layout, telling Gravity accounts apart from other state the program owns.
+    pub const DISCRIMINATOR: Discriminator = GRAVITY_CONTRACT;
+
+    /// Version of the layout `pack` writes. Version 1 is the one before the discriminator,
+    /// see `PRE_DISCRIMINATOR_LEN`; 2 added the discriminator, every field following
+    /// `multisig_account` and `RESERVED_LEN` reserved bytes.
+    pub const LAYOUT_VERSION: u8 = 2;
+
+    /// Zeroed bytes closing the layout, kept for fields added by later versions.
+    pub const RESERVED_LEN: usize = 16;
+
+    /// Size of the accounts contracts were kept in before the layout gained its discriminator:
+    /// the bare Borsh encoding of the fields up to `multisig_account`, zero padded. `Migrate`
+    /// moves such a contract over to an account of the current layout.
+    pub const PRE_DISCRIMINATOR_LEN: usize = 299;
+
+    /// Fewest consuls a contract can be set up with, below which nothing can ever be verified.
+    pub const MIN_CONSULS: usize = 1;
+
+    /// Most consuls the packed layout has room for.
+    pub const MAX_CONSULS: usize = 6;
+
+    /// Borsh encoding of a contract holding `MAX_CONSULS`.
+    pub const MAX_ENCODED_LEN: usize = Self::encoded_len(Self::MAX_CONSULS);
+
+    /// Consul count of the three-consul setups most contracts run, see `pack_to_array`.
+    pub const LEGACY_CONSULS: usize = 3;
+
+    /// Packed size of a contract holding at most `LEGACY_CONSULS`, without `LEN`'s room to grow.
+    pub const LEGACY_LEN: usize =
+        DISCRIMINATOR_LEN + Self::encoded_len(Self::LEGACY_CONSULS) + Self::RESERVED_LEN;
+
+    /// Borsh encoding of a contract holding `consul_count` consuls, field by field.
+    pub const fn encoded_len(consul_count: usize) -> usize {
+        32 + 1 + (4 + 32 * consul_count) + 8 + 32 + 8 + 8 + 8 + 1 + 1
+    }
+
+    /// Bytes the encoded contract gains (or loses, when negative) by switching to
+    /// `new_consul_count` consuls. Accounts keep their `LEN` either way, so growth only
+    /// takes up padding and has to stay within `MAX_ENCODED_LEN`.
+    pub fn size_delta(&self, new_consul_count: u8) -> i64 {
+        Self::encoded_len(new_consul_count as usize) as i64
+            - Self::encoded_len(self.consuls.len()) as i64
+    }
+
+    /// Packed size of a contract of `consul_count` consuls under layout `version`, for an
+    /// account sized to its contents, see `LAYOUT_VERSION` for what each one holds. `None`
+    /// for a version that never existed.
+    fn versioned_len(version: u8, consul_count: usize) -> Option<usize> {
+        let encoded_len = Self::encoded_len(consul_count);
+        let (encoded_len, reserved_len) = match version {
+            1 => return Some(encoded_len - 26),
+            Self::LAYOUT_VERSION => (encoded_len, Self::RESERVED_LEN),
+            _ => return None,
+        };
+
+        Some(DISCRIMINATOR_LEN + encoded_len + reserved_len)
+    }
+
+    /// Bytes a contract of `consul_count` consuls packs to more (or fewer, when negative)
+    /// under layout version `to` than under `from`, for sizing the realloc of an in-place
+    /// upgrade. `None` unless both versions are known layouts.
+    pub fn version_size_delta(from: u8, to: u8, consul_count: u8) -> Option<i64> {
+        let from_len = Self::versioned_len(from, consul_count as usize)?;
+        let to_len = Self::versioned_len(to, consul_count as usize)?;
+
+        Some(to_len as i64 - from_len as i64)
+    }
+
+    /// Smallest account the processors take a contract of `consul_count` consuls in. They
+    /// read and write a full `LEN` whatever the consul count, so fewer consuls never shrink it.
+    fn required_account_len(consul_count: usize) -> usize {
+        Self::LEN.max(DISCRIMINATOR_LEN + Self::encoded_len(consul_count) + Self::RESERVED_LEN)
+    }
+
+    /// Lamports the account needs on top of its current balance to stay rent exempt with
+    /// `new_consul_count` consuls. Zero for any set that fits `LEN`, and never negative: the
+    /// account can't be shrunk below `LEN`, so a smaller set frees nothing.
+    pub fn rotation_rent_delta(&self, new_consul_count: u8, rent: &Rent) -> i64 {
+        let current_len = Self::required_account_len(self.consuls.len());
+        let new_len = Self::required_account_len(new_consul_count as usize);
+
+        rent.minimum_balance(new_len) as i64 - rent.minimum_balance(current_len) as i64
+    }
+
+    /// Bytes of an `account_len` account past what a contract of `consul_count` consuls
+    /// requires, the only ones the account could give up. Padding within `LEN` left by a
+    /// smaller set isn't counted, the processors need it all.
+    pub fn wasted_bytes(account_len: usize, consul_count: u8) -> usize {
+        account_len.saturating_sub(Self::required_account_len(consul_count as usize))
+    }
+
+    /// Lamports an account of `account_len` bytes holding `account_lamports` lacks to be rent
+    /// exempt, zero once it is.
+    pub fn rent_shortfall(&self, account_lamports: u64, account_len: usize, rent: &Rent) -> u64 {
+        rent.minimum_balance(account_len).saturating_sub(account_lamports)
+    }
+
+    /// Layout of an account holding `MAX_CONSULS`. Every consul short of that moves the
+    /// fields following `consuls` 32 bytes up and leaves as many bytes more of `padding`.
+    pub fn layout_schema() -> Vec<FieldSchema> {
+        let fields = [
+            ("discriminator", DISCRIMINATOR_LEN, FieldType::Discriminator),
+            ("initializer_pubkey", 32, FieldType::Pubkey),
+            ("bft", 1, FieldType::U8),
+            ("consuls", 4 + 32 * Self::MAX_CONSULS, FieldType::PubkeyVec),
+            ("last_round", 8, FieldType::U64),
+            ("multisig_account", 32, FieldType::Pubkey),
+            ("consul_epoch", 8, FieldType::U64),
+            ("chain_id", 8, FieldType::U64),
+            ("last_update_slot", 8, FieldType::U64),
+            ("secp256k1_consuls", 1, FieldType::U8),
+            ("consul_shards", 1, FieldType::U8),
+            (
+                "padding",
+                Self::LEN - DISCRIMINATOR_LEN - Self::MAX_ENCODED_LEN - Self::RESERVED_LEN,
+                FieldType::Zeroed,
+            ),
+            ("reserved", Self::RESERVED_LEN, FieldType::Zeroed),
+        ];
+
+        let mut offset = 0;
+        fields
+            .iter()
+            .map(|(name, len, field_type)| {
+                let field = FieldSchema {
+                    name,
+                    offset,
+                    len: *len,
+                    field_type: *field_type,
+                };
+                offset += len;
+                field
+            })
+            .collect()
+    }
+
+    /// Splits a buffer the way both pack and unpack see it: the contract takes the first
+    /// `LEN` bytes, or all of a shorter buffer, and closes with the reserved region; the
+    /// bytes past `LEN` carry nothing. `None` when the buffer can't even hold the
+    /// discriminator and the reserved region.
+    fn split_layout(len: usize) -> Option<(usize, usize)> {
+        let contract_len = len.min(Self::LEN);
+
+        Some((contract_len.checked_sub(Self::RESERVED_LEN)?, contract_len))
+            .filter(|(fields_end, _)| *fields_end >= DISCRIMINATOR_LEN)
+    }
+
+    /// Writes the contract over `dst` as laid out by `split_layout`, zeroing the padding, the
+    /// reserved region and whatever lies past `LEN`, so no stale bytes stay behind. Fails
+    /// without writing anything when `dst` is too short for the encoded contract.
+    pub fn try_pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let data = self
+            .encode_fields(Self::LAYOUT_VERSION)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let (fields_end, _) =
+            Self::split_layout(dst.len()).ok_or(ProgramError::AccountDataTooSmall)?;
+        if DISCRIMINATOR_LEN + data.len() > fields_end {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let (fields, rest) = dst.split_at_mut(fields_end);
+        let fields = write_discriminator(fields, &Self::DISCRIMINATOR);
+        fields[..data.len()].copy_from_slice(&data);
+        for byte in fields[data.len()..].iter_mut().chain(rest.iter_mut()) {
+            *byte = 0;
+        }
+
+        Ok(())
+    }
+
+    fn unpack_contract(src: &[u8]) -> Result<Self, ProgramError> {
+        let (fields_end, contract_len) =
+            Self::split_layout(src.len()).ok_or(ProgramError::InvalidAccountData)?;
+        let (fields, rest) = src.split_at(fields_end);
+        let (reserved, tail) = rest.split_at(contract_len - fields_end);
+        let mut mut_src = strip_discriminator(fields, &Self::DISCRIMINATOR)?;
+
+        let mut gravity_contract_info = Self::default();
+        gravity_contract_info
+            .decode_fields(&mut mut_src, Self::LAYOUT_VERSION)
+            .map_err(|err| {
+                msg!(
+                    "Error: failed to deserialize GravityContract instruction: {}",
+                    err
+                );
+                ProgramError::InvalidInstructionData
+            })?;
+        gravity_contract_info.assert_min_consuls()?;
+
+        // whatever follows the encoded contract is padding and must stay zeroed, as pack
+        // leaves it
+        if !is_contract_empty(mut_src) || !is_contract_empty(reserved) || !is_contract_empty(tail) {
+            return Err(GravityError::NonZeroReserved.into());
+        }
+
+        Ok(gravity_contract_info)
+    }
+
+    /// Decodes an account of the layout before the discriminator, see `PRE_DISCRIMINATOR_LEN`.
+    /// The fields that layout lacks come out at their defaults. The bytes past the encoded
+    /// fields aren't checked, as that layout never cleared them.
+    pub fn unpack_pre_discriminator(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut src = src
+            .get(..Self::PRE_DISCRIMINATOR_LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        let mut gravity_contract_info = Self::default();
+        gravity_contract_info
+            .decode_fields(&mut src, 1)
+            .map_err(|err| {
+                msg!(
+                    "Error: failed to deserialize pre-discriminator GravityContract: {}",
+                    err
+                );
+                ProgramError::InvalidAccountData
+            })?;
+        gravity_contract_info.assert_min_consuls()?;
+
+        Ok(gravity_contract_info)
+    }
+
+    /// The layout version an account of `src` was packed under, told by the discriminator.
+    fn layout_version_of(src: &[u8]) -> u8 {
+        if src.starts_with(&Self::DISCRIMINATOR) {
+            Self::LAYOUT_VERSION
+        } else {
+            1
+        }
+    }
+
+    /// Decodes a contract of any layout version, see `LAYOUT_VERSION`, leaving the fields
+    /// its version lacks at their defaults.
+    pub fn unpack_versioned(src: &[u8]) -> Result<Self, ProgramError> {
+        match Self::layout_version_of(src) {
+            1 => Self::unpack_pre_discriminator(src),
+            _ => Self::unpack_contract(src),
+        }
+    }
+
+    /// Packs the fields the layout before the discriminator has into a `PRE_DISCRIMINATOR_LEN`
+    /// account, the way contracts were packed until then; any later fields are left out.
+    pub fn pack_pre_discriminator(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut packed = self
+            .encode_fields(1)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if packed.len() > Self::PRE_DISCRIMINATOR_LEN {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        packed.resize(Self::PRE_DISCRIMINATOR_LEN, 0);
+        Ok(packed)
+    }
+
+    /// Best-effort decode of an account a pack may have been interrupted on. Fields are decoded
+    /// in layout order up to the first one that can't be, leaving the rest at their defaults;
+    /// the flag tells whether the result is a complete, valid contract.
+    pub fn try_recover(src: &[u8]) -> Result<(Self, bool), ProgramError> {
+        if let Ok(gravity_contract_info) = Self::unpack_contract(src) {
+            let is_valid = gravity_contract_info.check_invariants().is_ok()
+                && !gravity_contract_info.has_zeroed_keys();
+            return Ok((gravity_contract_info, is_valid));
+        }
+
+        let mut src = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        let mut gravity_contract_info = Self::default();
+        // stopping early is the point here, so the error itself carries nothing
+        let _ = gravity_contract_info.decode_fields(&mut src, Self::LAYOUT_VERSION);
+
+        Ok((gravity_contract_info, false))
+    }
+
+    /// Decodes, in layout order, the fields layout `version` has, see `LAYOUT_VERSION`; those
+    /// version 1 lacks are left as they are. The current version starts with the fields of
+    /// version 1.
+    fn decode_fields(&mut self, src: &mut &[u8], version: u8) -> std::io::Result<()> {
+        self.initializer_pubkey = Pubkey::deserialize(src)?;
+        self.bft = u8::deserialize(src)?;
+        self.consuls = Vec::deserialize(src)?;
+        self.last_round = u64::deserialize(src)?;
+        self.multisig_account = Pubkey::deserialize(src)?;
+        if version >= 2 {
+            self.consul_epoch = u64::deserialize(src)?;
+            self.chain_id = u64::deserialize(src)?;
+            self.last_update_slot = u64::deserialize(src)?;
+            self.secp256k1_consuls = u8::deserialize(src)?;
+            self.consul_shards = u8::deserialize(src)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes the fields layout `version` has the way `decode_fields` reads them; for the
+    /// current version that is the Borsh encoding of the whole contract.
+    fn encode_fields(&self, version: u8) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(Self::MAX_ENCODED_LEN);
+        self.initializer_pubkey.serialize(&mut data)?;
+        self.bft.serialize(&mut data)?;
+        self.consuls.serialize(&mut data)?;
+        self.last_round.serialize(&mut data)?;
+        self.multisig_account.serialize(&mut data)?;
+        if version >= 2 {
+            self.consul_epoch.serialize(&mut data)?;
+            self.chain_id.serialize(&mut data)?;
+            self.last_update_slot.serialize(&mut data)?;
+            self.secp256k1_consuls.serialize(&mut data)?;
+            self.consul_shards.serialize(&mut data)?;
+        }
+        Ok(data)
+    }
+
+    /// Zeroed keys are what an interrupted write leaves behind, no real contract holds them.
+    fn has_zeroed_keys(&self) -> bool {
+        let zeroed = Pubkey::default();
+
+        self.initializer_pubkey == zeroed
+            || self.multisig_account == zeroed
+            || self.consuls.contains(&zeroed)
+    }
+
+    pub fn is_owner(&self, key: &Pubkey) -> bool {
+        self.initializer_pubkey == *key
+    }
+
+    pub fn initializer_in(&self, allowlist: &[Pubkey]) -> bool {
+        allowlist.contains(&self.initializer_pubkey)
+    }
+
+    pub fn initializer_is_consul(&self) -> bool {
+        self.consuls.contains(&self.initializer_pubkey)
+    }
+
+    /// Deployments differ on whether the initializer may sit among the consuls; with
+    /// `allow_overlap` unset, one that does is refused.
+    pub fn assert_initializer_policy(&self, allow_overlap: bool) -> Result<(), GravityError> {
+        if !allow_overlap && self.initializer_is_consul() {
+            return Err(GravityError::InitializerConsulOverlap);
+        }
+
+        Ok(())
+    }
+
+    pub fn assert_owner(&self, key: &Pubkey) -> Result<(), ProgramError> {
+        if !self.is_owner(key) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(())
+    }
+
+    /// Hands the contract over to `new_owner`, on behalf of the current initializer only.
+    pub fn transfer_ownership(
+        &mut self,
+        current_signer: &Pubkey,
+        new_owner: Pubkey,
+    ) -> Result<(), ProgramError> {
+        self.assert_owner(current_signer)?;
+        if new_owner == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.initializer_pubkey = new_owner;
+        Ok(())
+    }
+
+    fn assert_min_consuls(&self) -> Result<(), GravityError> {
+        if self.consuls.len() < Self::MIN_CONSULS {
+            return Err(GravityError::TooFewConsuls);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the invariants every initialized contract holds.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        Ok(self.check_invariants()?)
+    }
+
+    fn check_invariants(&self) -> Result<(), GravityError> {
+        self.assert_min_consuls()?;
+        if self.bft == 0 || self.bft as usize > self.consuls.len() {
+            return Err(GravityError::InvalidBFTCount);
+        }
+
+        let mut consuls = self.consuls.clone();
+        consuls.sort();
+        consuls.dedup();
+        if consuls.len() != self.consuls.len() {
+            return Err(GravityError::DuplicateConsul);
+        }
+        if self
+            .secp256k1_consuls
+            .checked_shr(self.consuls.len() as u32)
+            .unwrap_or(0)
+            != 0
+        {
+            return Err(GravityError::KeyTypeMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Every invariant `validate` would stop at the first of, plus the checks the packed layout
+    /// and zeroed keys call for, in one pass for diagnostics.
+    pub fn validate_all(&self) -> Vec<GravityError> {
+        let mut violations = vec![];
+
+        if self.consuls.len() < Self::MIN_CONSULS {
+            violations.push(GravityError::TooFewConsuls);
+        }
+        if self.consuls.len() > Self::MAX_CONSULS {
+            violations.push(GravityError::TooManyConsuls);
+        }
+        if self.bft == 0 || self.bft as usize > self.consuls.len() {
+            violations.push(GravityError::InvalidBFTCount);
+        }
+
+        let mut consuls = self.consuls.clone();
+        consuls.sort();
+        consuls.dedup();
+        if consuls.len() != self.consuls.len() {
+            violations.push(GravityError::DuplicateConsul);
+        }
+        if self
+            .secp256k1_consuls
+            .checked_shr(self.consuls.len() as u32)
+            .unwrap_or(0)
+            != 0
+        {
+            violations.push(GravityError::KeyTypeMismatch);
+        }
+        if self.consuls.contains(&Pubkey::default()) {
+            violations.push(GravityError::InvalidConsulKey);
+        }
+
+        violations
+    }
+
+    /// Unpacks and validates each account on its own, so one broken account doesn't hide the rest.
+    pub fn validate_batch(
+        accounts: &[(Pubkey, Vec<u8>)],
+    ) -> Vec<(Pubkey, Result<(), ProgramError>)> {
+        accounts
+            .iter()
+            .map(|(address, data)| {
+                let result = data
+                    .get(0..Self::LEN)
+                    .ok_or(ProgramError::InvalidAccountData)
+                    .and_then(Self::unpack)
+                    .and_then(|gravity_contract_info| gravity_contract_info.validate());
+
+                (*address, result)
+            })
+            .collect()
+    }
+
+    /// With `check_consul_keys`, refuses consuls that can't produce ed25519 signatures.
+    /// Secp256k1 consuls are keccak digests and are not expected to be curve points.
+    pub fn new(
+        initializer_pubkey: Pubkey,
+        bft: u8,
+        consuls: Vec<Pubkey>,
+        multisig_account: Pubkey,
+        check_consul_keys: bool,
+    ) -> Result<Self, ProgramError> {
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey,
+            bft,
+            consuls,
+            multisig_account,
+            ..GravityContract::default()
+        };
+        gravity_contract_info.assert_min_consuls()?;
+
+        if check_consul_keys {
+            gravity_contract_info.validate_consul_keys()?;
+        }
+
+        Ok(gravity_contract_info)
+    }
+
+    /// Reads the signers of an SPL Token multisig, for deployments keeping their consuls there.
+    pub fn from_multisig_account(account: &AccountInfo) -> Result<Vec<Pubkey>, ProgramError> {
+        let multisig = Multisig::unpack(&account.try_borrow_data()?)?;
+
+        Ok(multisig.signers[..multisig.n as usize].to_vec())
+    }
+
+    pub fn validate_consul_keys(&self) -> Result<(), ProgramError> {
+        for (i, consul) in self.consuls.iter().enumerate() {
+            if !is_ed25519_point(consul) {
+                msg!("consul #{} is not a valid ed25519 point: {}", i, consul);
+                return Err(GravityError::InvalidConsulKey.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Packs into `buf` in place of its contents, reusing its allocation across calls.
+    pub fn pack_into_vec(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.reserve_exact(Self::LEN);
+
+        buf.extend_from_slice(&Self::DISCRIMINATOR);
+        self.serialize(buf).unwrap();
+        assert!(buf.len() <= Self::LEN - Self::RESERVED_LEN);
+
+        buf.resize(Self::LEN, 0);
+    }
+
+    /// Packs the contract on the stack instead of the heap, for sets of up to `LEGACY_CONSULS`.
+    /// The array decodes with `unpack_from_slice` like a full `LEN` account would.
+    pub fn pack_to_array(&self) -> Result<[u8; Self::LEGACY_LEN], ProgramError> {
+        if self.consuls.len() > Self::LEGACY_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        let mut packed = [0; Self::LEGACY_LEN];
+        self.try_pack_into_slice(&mut packed)?;
+
+        Ok(packed)
+    }
+
+    /// Fails unless `golden`, a contract packed under some layout version, decodes to `self`
+    /// and `self` packs back to the very same bytes under that version, so that accounts
+    /// written by older versions keep reading the same under the current one. The version
+    /// is told from `golden` as `unpack_versioned` does.
+    pub fn assert_layout_compatible(&self, golden: &[u8]) -> Result<(), ProgramError> {
+        let decoded = Self::unpack_versioned(golden)?;
+        if decoded != *self {
             msg!(
-                "Error: failed to deserialize GravityContract instruction: {}",
-                err
+                "Error: golden bytes decode to {}, expected {}",
+                decoded,
+                self
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let packed = match Self::layout_version_of(golden) {
+            1 => self.pack_pre_discriminator()?,
+            _ => {
+                let mut packed = vec![0; Self::LEN];
+                self.try_pack_into_slice(&mut packed)?;
+                packed
+            }
+        };
+        if packed != golden {
+            msg!("Error: contract no longer packs to its golden bytes");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(())
+    }
+
+    /// Re-packs the contract in `src`, of the current layout, under layout version 1, the
+    /// only older one, for rolling an upgrade back. The fields version 1 lacks are dropped,
+    /// but only while they're bookkeeping the older program doesn't read, `consul_epoch` and
+    /// `last_update_slot`; a set `chain_id`, secp256k1 consul or consul shard count can't be
+    /// dropped without the contract verifying differently.
+    pub fn downgrade_to(src: &[u8], target_version: u8) -> Result<Vec<u8>, ProgramError> {
+        if target_version != 1 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let gravity_contract_info = Self::unpack_contract(src)?;
+        if gravity_contract_info.chain_id != 0 {
+            msg!("Error: layout version 1 has no chain id");
+            return Err(GravityError::DowngradeLosesData.into());
+        }
+        if gravity_contract_info.secp256k1_consuls != 0 {
+            msg!("Error: layout version 1 has no secp256k1 consuls");
+            return Err(GravityError::DowngradeLosesData.into());
+        }
+        if gravity_contract_info.consul_shards != 0 {
+            msg!("Error: layout version 1 has no consul shards");
+            return Err(GravityError::DowngradeLosesData.into());
+        }
+
+        gravity_contract_info.pack_pre_discriminator()
+    }
+
+    /// Fixed contracts with the `LEN` bytes they pack to, as golden vectors for other
+    /// implementations of the layout. Keys are filled with a repeated byte so the vectors
+    /// come out the same on every run; they are not valid ed25519 points.
+    pub fn conformance_vectors() -> Vec<(GravityContract, Vec<u8>)> {
+        let key = |byte: u8| Pubkey::new(&[byte; 32]);
+
+        let contracts = vec![
+            GravityContract {
+                initializer_pubkey: key(1),
+                bft: 1,
+                consuls: vec![key(2)],
+                multisig_account: key(3),
+                ..GravityContract::default()
+            },
+            GravityContract {
+                initializer_pubkey: key(1),
+                bft: 2,
+                consuls: vec![key(2), key(4), key(5)],
+                last_round: 7,
+                multisig_account: key(3),
+                consul_epoch: 1,
+                chain_id: 1,
+                last_update_slot: 120,
+                secp256k1_consuls: 0,
+                consul_shards: 0,
+            },
+            GravityContract {
+                initializer_pubkey: key(0xff),
+                bft: Self::MAX_CONSULS as u8,
+                consuls: (0..Self::MAX_CONSULS as u8)
+                    .map(|i| key(0x10 + i))
+                    .collect(),
+                last_round: u64::MAX,
+                multisig_account: key(0xfe),
+                consul_epoch: u64::MAX,
+                chain_id: u64::MAX,
+                last_update_slot: u64::MAX,
+                secp256k1_consuls: 0b0011_1111,
+                consul_shards: 1,
+            },
+        ];
+
+        contracts
+            .into_iter()
+            .map(|contract| {
+                let mut packed = vec![];
+                contract.pack_into_vec(&mut packed);
+                (contract, packed)
+            })
+            .collect()
+    }
+
+    /// The sha256 of the packed contract, for CI on every target to compare against one golden
+    /// value, so that a platform packing differently, e.g. on byte order, is caught.
+    pub fn platform_fingerprint(&self) -> [u8; 32] {
+        let mut packed = vec![];
+        self.pack_into_vec(&mut packed);
+
+        hash::hash(&packed).to_bytes()
+    }
+
+    /// The hash of the contract as `try_pack_into_slice` lays it out in a `LEN` account, the
+    /// one event records commit to. Fails on a contract the layout can't hold.
+    pub fn state_hash(&self) -> Result<Hash, ProgramError> {
+        let mut packed = vec![0; Self::LEN];
+        self.try_pack_into_slice(&mut packed)?;
+
+        Ok(hash::hash(&packed))
+    }
+
+    /// Compares the consul sets ignoring their order.
+    pub fn consuls_eq(&self, other: &GravityContract) -> bool {
+        let mut consuls = self.consuls.clone();
+        let mut other_consuls = other.consuls.clone();
+        consuls.sort();
+        other_consuls.sort();
+
+        consuls == other_consuls
+    }
+
+    /// For schema upgrades that aren't meant to rotate: fails unless `new` holds the consuls
+    /// of `old` in the same order, the positions signature bundles address them by.
+    pub fn assert_upgrade_preserves_consuls(old: &Self, new: &Self) -> Result<(), ProgramError> {
+        if old.consuls != new.consuls {
+            msg!(
+                "Error: upgrade changed the consuls from {:?} to {:?}",
+                old.consuls,
+                new.consuls
             );
+            return Err(GravityError::UnexpectedConsulChange.into());
+        }
+
+        Ok(())
+    }
+
+    /// Packs and unpacks the contract, failing unless what comes back means the same thing:
+    /// equal fields and `consuls_eq` consuls.
+    pub fn assert_roundtrip(&self) -> Result<(), ProgramError> {
+        if self.consuls.len() > Self::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        let mut buf = vec![];
+        self.pack_into_vec(&mut buf);
+        let unpacked = Self::unpack_from_slice(&buf)?;
+
+        let same_fields = GravityContract {
+            consuls: vec![],
+            ..unpacked.clone()
+        } == GravityContract {
+            consuls: vec![],
+            ..self.clone()
+        };
+        if !same_fields || !unpacked.consuls_eq(self) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(())
+    }
+
+    /// Discriminator and borsh encoding without the account padding, for passing state over CPI.
+    pub fn to_cpi_bytes(&self) -> Vec<u8> {
+        let mut buf = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    /// Reverses `to_cpi_bytes`, taking nothing past the encoding and only a valid contract.
+    pub fn from_cpi_bytes(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        let gravity_contract_info = Self::try_from_slice(src).map_err(|err| {
+            msg!("Error: failed to deserialize GravityContract: {}", err);
             ProgramError::InvalidInstructionData
-        })
+        })?;
+        gravity_contract_info.validate()?;
+
+        Ok(gravity_contract_info)
     }
 
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+    /// Appends each contract to `out` as its borsh encoding behind a little-endian `u32` length,
+    /// so that a snapshot holding contracts of different consul counts reads back in one pass.
+    pub fn pack_sequence(contracts: &[GravityContract], out: &mut Vec<u8>) {
+        for gravity_contract_info in contracts.iter() {
+            let data = gravity_contract_info.try_to_vec().unwrap();
+
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&data);
+        }
+    }
+
+    pub fn unpack_sequence(mut src: &[u8]) -> Result<Vec<GravityContract>, ProgramError> {
+        let mut contracts = Vec::new();
+
+        while !src.is_empty() {
+            if src.len() < 4 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let (len, rest) = src.split_at(4);
+            let len = u32::from_le_bytes(*array_ref![len, 0, 4]) as usize;
+            if rest.len() < len {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let (data, rest) = rest.split_at(len);
+
+            let gravity_contract_info = Self::try_from_slice(data).map_err(|err| {
+                msg!(
+                    "Error: failed to deserialize GravityContract #{}: {}",
+                    contracts.len(),
+                    err
+                );
+                ProgramError::InvalidInstructionData
+            })?;
+            gravity_contract_info.assert_min_consuls()?;
+
+            contracts.push(gravity_contract_info);
+            src = rest;
+        }
+
+        Ok(contracts)
+    }
+
+    /// Orders the consul set, so that equal sets always pack into the same bytes.
+    pub fn canonicalize(&mut self) {
+        self.consuls.sort();
+    }
+
+    /// Checks that `src` is exactly what a canonicalized contract packs into.
+    pub fn is_canonical(src: &[u8]) -> bool {
+        let mut gravity_contract_info = match Self::unpack_from_slice(src) {
+            Ok(contract) => contract,
+            Err(_) => return false,
+        };
+        gravity_contract_info.canonicalize();
+
+        let mut repacked = vec![0; src.len()];
+        gravity_contract_info
+            .try_pack_into_slice(&mut repacked)
+            .is_ok()
+            && repacked == src
+    }
+
+    /// Decodes the account data of a contract fetched off-chain, refusing it unless `owner`,
+    /// the program the account belongs to, is `program_id`. Trailing account bytes are
+    /// ignored, as with `Pack::unpack` in the processor. An account of the layout before the
+    /// discriminator is decoded as such unless `strict_mode` is on.
+    pub fn unpack_checked(
+        data: &[u8],
+        owner: &Pubkey,
+        program_id: &Pubkey,
+        strict_mode: StrictMode,
+    ) -> Result<Self, ProgramError> {
+        if owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if Self::layout_version_of(data) == 1 {
+            if strict_mode == StrictMode::On {
+                msg!("Error: account holds a contract of the layout before the discriminator");
+                return Err(GravityError::LegacyLayoutRejected.into());
+            }
+
+            return Self::unpack_pre_discriminator(data);
+        }
+
+        let data = data
+            .get(0..Self::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Self::unpack(data)
+    }
+
+    /// Decodes an account update streamed by a Geyser plugin, which hands over the raw data
+    /// and owner of the account. Validated as `unpack_checked` does for RPC fetches; the
+    /// stream covers every account, those not migrated yet included.
+    pub fn from_geyser_update(
+        data: &[u8],
+        owner: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        Self::unpack_checked(data, owner, program_id, StrictMode::Off)
+    }
+
+    /// Initializer of a packed contract, read without decoding the consuls, for filtering many
+    /// accounts by owner.
+    pub fn read_initializer(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        strip_discriminator(src, &Self::DISCRIMINATOR)?;
+
+        PackedContract(src).initializer()
+    }
+
+    /// Walks the consuls of a packed contract without unpacking it. The discriminator and the
+    /// consul count are checked upfront, so a short or foreign slice fails before iterating.
+    pub fn iter_consuls_from_slice(
+        src: &[u8],
+    ) -> Result<impl Iterator<Item = Pubkey> + '_, ProgramError> {
+        strip_discriminator(src, &Self::DISCRIMINATOR)?;
+
+        let packed_contract = PackedContract(src);
+        let consuls = packed_contract.bytes(
+            PackedContract::CONSULS_OFFSET + 4,
+            32 * packed_contract.consul_count()?,
+        )?;
+
+        Ok(consuls.chunks_exact(32).map(Pubkey::new))
+    }
+
+    /// Rewrites a packed contract in canonical form, telling whether any byte had to change.
+    pub fn canonicalize_buffer(buf: &mut [u8]) -> Result<bool, ProgramError> {
+        let mut gravity_contract_info = Self::unpack(buf)?;
+        gravity_contract_info.canonicalize();
+
+        let mut repacked = vec![0; buf.len()];
+        gravity_contract_info.pack_into_slice(&mut repacked);
+        if repacked[..] == buf[..] {
+            return Ok(false);
+        }
+
+        buf.copy_from_slice(&repacked);
+        Ok(true)
+    }
+
+    /// Replaces the consul set, moving to the next epoch only when the set actually changes.
+    /// Leaves the contract untouched once the epoch can't be moved any further.
+    pub fn set_consuls(&mut self, new_consuls: Vec<Pubkey>) -> Result<(), GravityError> {
+        if self.consuls != new_consuls {
+            self.consul_epoch = self
+                .consul_epoch
+                .checked_add(1)
+                .ok_or(GravityError::EpochExhausted)?;
+            self.secp256k1_consuls = 0;
+        }
+        self.consuls = new_consuls;
+
+        Ok(())
+    }
+
+    /// Scheme the consul at `position` signs with.
+    pub fn key_type(&self, position: usize) -> ConsulKeyType {
+        if self.secp256k1_consuls & (1 << position) != 0 {
+            ConsulKeyType::Secp256k1
+        } else {
+            ConsulKeyType::Ed25519
+        }
+    }
+
+    /// Records the scheme of each consul, `key_types` listing them by position.
+    pub fn set_key_types(&mut self, key_types: &[ConsulKeyType]) -> Result<(), GravityError> {
+        if key_types.len() != self.consuls.len() {
+            return Err(GravityError::KeyTypeMismatch);
+        }
+
+        self.secp256k1_consuls = key_types
+            .iter()
+            .enumerate()
+            .filter(|(_, key_type)| **key_type == ConsulKeyType::Secp256k1)
+            .fold(0, |mask, (position, _)| mask | 1 << position);
+
+        Ok(())
+    }
+
+    /// Records the consul set as kept across `shard_count` shards, on writing shard `index`
+    /// of `consuls`. Every shard holds at least one consul, so there can't be more shards
+    /// than `MAX_CONSULS`.
+    pub fn set_consul_shard(
+        &mut self,
+        index: u8,
+        shard_count: u8,
+        consuls: &[Pubkey],
+    ) -> Result<(), GravityError> {
+        if index >= shard_count || shard_count as usize > Self::MAX_CONSULS {
+            return Err(GravityError::InvalidShardSequence);
+        }
+        if consuls.is_empty() {
+            return Err(GravityError::TooFewConsuls);
+        }
+        if consuls.len() > ConsulShard::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls);
+        }
+
+        self.consul_shards = shard_count;
+        Ok(())
+    }
+
+    /// Identifies the configuration of the contract apart from its progress: the initializer,
+    /// `bft` and the consuls in sorted order, but neither the round nor the epoch.
+    pub fn config_id(&self) -> [u8; 32] {
+        let mut consuls = self.consuls.clone();
+        consuls.sort();
+
+        let bft = [self.bft];
+        let mut chunks: Vec<&[u8]> = vec![self.initializer_pubkey.as_ref(), &bft];
+        chunks.extend(consuls.iter().map(|consul| consul.as_ref()));
+
+        hashv(&chunks).to_bytes()
+    }
+
+    /// Binds the consul set to its epoch, so that equal sets from different epochs never collide.
+    pub fn consul_commitment(&self) -> Hash {
+        let epoch = self.consul_epoch.to_le_bytes();
+
+        let mut chunks: Vec<&[u8]> = vec![&epoch];
+        chunks.extend(self.consuls.iter().map(|consul| consul.as_ref()));
+
+        hashv(&chunks)
+    }
+
+    /// Also binds the threshold, so that a signed commitment can't be replayed with another bft.
+    /// Consuls are hashed in sorted order, the commitment doesn't depend on how they are stored.
+    pub fn full_commitment(&self) -> Hash {
+        let epoch = self.consul_epoch.to_le_bytes();
+        let bft = [self.bft];
+
+        let mut consuls = self.consuls.clone();
+        consuls.sort();
+
+        let mut chunks: Vec<&[u8]> = vec![&epoch, &bft];
+        chunks.extend(consuls.iter().map(|consul| consul.as_ref()));
+
+        hashv(&chunks)
+    }
+
+    fn consul_leaves(&self) -> Vec<MerkleHash> {
+        self.consuls
+            .iter()
+            .enumerate()
+            .map(|(index, consul)| hash_leaf(&consul_leaf(index, consul)))
+            .collect()
+    }
+
+    /// Root of a Merkle tree over the consuls, each leaf binding a consul to its index.
+    pub fn consuls_merkle_root(&self) -> MerkleHash {
+        merkle_root(&self.consul_leaves())
+    }
+
+    /// Index of `key` with the path proving it against `consuls_merkle_root`.
+    pub fn consul_membership_proof(&self, key: &Pubkey) -> Option<(usize, Vec<MerkleHash>)> {
+        let index = self.consuls.iter().position(|consul| consul == key)?;
+
+        Some((index, merkle_proof(&self.consul_leaves(), index)))
+    }
+
+    /// The consul keys back to back in canonical order, as the consuls region of a canonical
+    /// contract holds them, without the length prefix.
+    pub fn consuls_bytes(&self) -> Vec<u8> {
+        let mut consuls = self.consuls.clone();
+        consuls.sort();
+
+        consuls
+            .iter()
+            .flat_map(|consul| consul.to_bytes().to_vec())
+            .collect()
+    }
+
+    /// Lays the consuls out as a standalone ABI-encoded `bytes32[]`: offset, length, then one
+    /// word per consul. Not an `address[]`, whose 20-byte elements can't hold a 32-byte consul
+    /// key; the EVM side has to declare the array as `bytes32[]` to decode it.
+    pub fn consuls_abi_bytes32(&self) -> Vec<u8> {
+        abi_encode_bytes32_array(&self.consuls)
+    }
+
+    /// Whether `evm_hash` is the `algo` digest of `consuls_abi_bytes32`, which is how
+    /// the EVM side commits to its consul set.
+    pub fn matches_evm_commitment(&self, evm_hash: [u8; 32], algo: HashAlgo) -> bool {
+        algo.digest(&self.consuls_abi_bytes32()) == evm_hash
+    }
+
+    pub fn consul_index(&self) -> Result<ConsulIndex<'_>, GravityError> {
+        ConsulIndex::new(&self.consuls)
+    }
+
+    /// Seed prefix of the accounts consul rewards accumulate in.
+    pub const REWARD_SEED: &'static [u8] = b"reward";
+
+    /// Claim account of the consul at `consul_index`. It is derived from the consul's key
+    /// rather than its position, so a rotation that reorders the set moves no rewards
+    /// between consuls. `None` when there is no consul at `consul_index`.
+    pub fn reward_destination(
+        &self,
+        consul_index: usize,
+        program_id: &Pubkey,
+    ) -> Option<(Pubkey, u8)> {
+        let consul = self.consuls.get(consul_index)?;
+
+        Some(Pubkey::find_program_address(
+            &[
+                Self::REWARD_SEED,
+                self.multisig_account.as_ref(),
+                consul.as_ref(),
+            ],
+            program_id,
+        ))
+    }
+
+    /// Counts the current consuls that are kept by the proposed set.
+    pub fn rotation_overlap(&self, new: &[Pubkey]) -> usize {
+        self.consuls
+            .iter()
+            .filter(|consul| new.contains(consul))
+            .count()
+    }
+
+    /// Consuls whose index is absent from `signed_indices`, in consul order.
+    pub fn non_signers(&self, signed_indices: &[u8]) -> Vec<Pubkey> {
+        self.consuls
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !signed_indices.iter().any(|signed| *signed as usize == *i))
+            .map(|(_, consul)| *consul)
+            .collect()
+    }
+
+    pub fn can_rotate_safely(&self, new: &[Pubkey], min_overlap: usize) -> bool {
+        self.rotation_overlap(new) >= min_overlap
+    }
+
+    /// Whether the consuls kept by `new_consuls` still make a quorum of the current set on
+    /// their own, so that a rotation can't hand the contract to an entirely new party.
+    pub fn rotation_preserves_quorum(&self, new_consuls: &[Pubkey]) -> bool {
+        self.can_rotate_safely(new_consuls, self.bft as usize)
+    }
+
+    /// Signers an `EmergencyReplace` needs: all but one of the `multisig_signers` the multisig
+    /// holds, yet always more than `bft` while the set allows it.
+    pub fn emergency_threshold(&self, multisig_signers: u8) -> u8 {
+        let consuls_count = multisig_signers as usize;
+        let threshold = (self.bft as usize + 1)
+            .max(consuls_count.saturating_sub(1))
+            .min(consuls_count);
+
+        threshold as u8
+    }
+
+    /// Rounds left before `update_round` runs out below which `audit_report` raises a warning.
+    pub const ROUND_HEADROOM_WARNING: u64 = 1 << 32;
+
+    /// Multi-line summary of the contract for CLI output, closing with a line per warning.
+    pub fn audit_report(&self) -> String {
+        let security_level = self.security_assessment();
+
+        let mut warnings = vec![];
+        if let Err(err) = self.check_invariants() {
+            warnings.push(format!("invalid contract: {}", err));
+        }
+        if security_level == SecurityLevel::Weak {
+            warnings.push(format!(
+                "weak quorum: {} of {} consuls can push an update through",
+                self.bft,
+                self.consuls.len()
+            ));
+        }
+        if u64::MAX - self.last_round < Self::ROUND_HEADROOM_WARNING {
+            warnings.push(format!(
+                "round {} is close to the last one possible",
+                self.last_round
+            ));
+        }
+
+        let mut report = format!(
+            "initializer: {}\nconsuls: {}\nbft: {}\nsecurity level: {:?}\nround: {}\nconsul epoch: {}\n",
+            self.initializer_pubkey,
+            self.consuls.len(),
+            self.bft,
+            security_level,
+            self.last_round,
+            self.consul_epoch
+        );
+        if warnings.is_empty() {
+            report.push_str("warnings: none\n");
+        }
+        for warning in warnings.iter() {
+            report.push_str(&format!("warning: {}\n", warning));
+        }
+
+        report
+    }
+
+    /// Smallest quorum `security_assessment` rates at `level` for `consul_count` consuls.
+    pub fn recommended_bft(consul_count: usize, level: SecurityLevel) -> u8 {
+        let bft = match level {
+            SecurityLevel::Weak => consul_count / 2,
+            SecurityLevel::Adequate => consul_count / 2 + 1,
+            SecurityLevel::Strong => consul_count * 2 / 3 + 1,
+        };
+
+        bft.clamp(1, consul_count.clamp(1, u8::MAX as usize)) as u8
+    }
+
+    /// Weak while half of the consuls or less make a quorum, strong above two thirds.
+    pub fn security_assessment(&self) -> SecurityLevel {
+        let bft = self.bft as usize;
+        let consuls_count = self.consuls.len();
+
+        if bft <= consuls_count / 2 {
+            SecurityLevel::Weak
+        } else if bft * 3 > consuls_count * 2 {
+            SecurityLevel::Strong
+        } else {
+            SecurityLevel::Adequate
+        }
+    }
+
+    /// Most faulty consuls the set withstands, the largest `f` with `n >= 3f + 1`.
+    pub fn tolerable_faults(&self) -> usize {
+        self.consuls.len().saturating_sub(1) / 3
+    }
+
+    /// Whether `bft` reaches `floor(2n / 3) + 1`, the smallest quorum that two sets of
+    /// `tolerable_faults` can't both be part of, without exceeding the consul count.
+    pub fn is_bft_sound(&self) -> bool {
+        let bft = self.bft as usize;
+        let consuls_count = self.consuls.len();
+
+        bft > consuls_count * 2 / 3 && bft <= consuls_count
+    }
+
+    /// Moves the contract forward to `new_round`. Once `last_round` reaches `u64::MAX`
+    /// there is no round left to move to, and the contract can't be updated anymore.
+    pub fn update_round(&mut self, new_round: u64) -> Result<(), GravityError> {
+        self.check_round(new_round)?;
+
+        self.last_round = new_round;
+        Ok(())
+    }
+
+    /// The contract as `update_round` would leave it, with `self` untouched.
+    pub fn simulate_update_round(&self, new_round: u64) -> Result<GravityContract, ProgramError> {
+        let mut simulated = self.clone();
+        simulated.update_round(new_round)?;
+
+        Ok(simulated)
+    }
+
+    /// The contract `InitContract` sets up. Returns it with the events it logs, as the
+    /// other transitions below do, each recorded with the contract as it stood then.
+    pub fn initialize(
+        initializer: Pubkey,
+        bft: u8,
+        consuls: Vec<Pubkey>,
+        multisig_account: Pubkey,
+        chain_id: u64,
+    ) -> Result<(Self, Vec<EventRecord>), ProgramError> {
+        if consuls.len() > Self::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        let gravity_contract_info = GravityContract {
+            initializer_pubkey: initializer,
+            bft,
+            consuls: consuls.clone(),
+            multisig_account,
+            chain_id,
+            ..GravityContract::default()
+        };
+        let event = ContractEvent::Initialized {
+            initializer,
+            bft,
+            consuls,
+            multisig_account,
+            chain_id,
+        };
+        let records = vec![EventRecord::new(event, &gravity_contract_info)?];
+
+        Ok((gravity_contract_info, records))
+    }
+
+    /// What a round update does once its signatures are checked: moves the contract to
+    /// `new_round`, recording `slot` as that of the update.
+    pub fn advance_round(
+        &mut self,
+        new_round: u64,
+        slot: Slot,
+    ) -> Result<Vec<EventRecord>, ProgramError> {
+        self.update_round(new_round)?;
+        self.last_update_slot = slot;
+
+        Ok(vec![EventRecord::new(
+            ContractEvent::RoundUpdated { round: new_round },
+            self,
+        )?])
+    }
+
+    /// What `UpdateConsuls` and `EmergencyReplace` do once their signers are checked:
+    /// `advance_round`, then the switch to `new_consuls`, logged only when the set actually
+    /// changes. The set has to fit the layout and make for a valid contract on its own.
+    pub fn rotate_consuls(
+        &mut self,
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
+        slot: Slot,
+    ) -> Result<Vec<EventRecord>, ProgramError> {
+        if new_consuls.len() > Self::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        let mut records = self.advance_round(current_round, slot)?;
+
+        let old_epoch = self.consul_epoch;
+        self.set_consuls(new_consuls.clone())?;
+        self.validate()?;
+        if self.consul_epoch != old_epoch {
+            records.push(EventRecord::new(
+                ContractEvent::ConsulsRotated {
+                    consuls: new_consuls,
+                },
+                self,
+            )?);
+        }
+
+        Ok(records)
+    }
+
+    /// Applies what each instruction does to the contract, in order, without accounts or
+    /// a runtime, for reproducing a captured instruction trace. `initial` supplies the keys
+    /// an `InitContract` would take from its accounts. Signatures aren't checked, the trace
+    /// is taken as authorized, and the slot of every update is the one the contract last
+    /// recorded. The steps run the same transitions the processor does.
+    pub fn replay(
+        initial: GravityContract,
+        instructions: &[GravityContractInstruction],
+    ) -> Result<GravityContract, ProgramError> {
+        let mut state = initial;
+
+        for (step, instruction) in instructions.iter().enumerate() {
+            state.replay_step(instruction).map_err(|err| {
+                msg!("Error: replay failed at instruction {}: {}", step, err);
+                err
+            })?;
+        }
+
+        Ok(state)
+    }
+
+    fn replay_step(
+        &mut self,
+        instruction: &GravityContractInstruction,
+    ) -> Result<(), ProgramError> {
+        match instruction {
+            GravityContractInstruction::InitContract {
+                new_consuls,
+                bft,
+                chain_id,
+                ..
+            } => {
+                // no initialized contract is without consuls
+                if !self.consuls.is_empty() {
+                    return Err(GravityError::AccountAlreadyUsed.into());
+                }
+
+                let (gravity_contract_info, _) = Self::initialize(
+                    self.initializer_pubkey,
+                    *bft,
+                    new_consuls.clone(),
+                    self.multisig_account,
+                    *chain_id,
+                )?;
+                *self = gravity_contract_info;
+            }
+            GravityContractInstruction::UpdateConsuls {
+                new_consuls,
+                current_round,
+            } => {
+                let slot = self.last_update_slot;
+                self.rotate_consuls(*current_round, new_consuls.clone(), slot)?;
+            }
+            GravityContractInstruction::EmergencyReplace {
+                new_consuls,
+                current_round,
+            } => {
+                let slot = self.last_update_slot;
+                self.rotate_consuls(*current_round, new_consuls.clone(), slot)?;
+            }
+            GravityContractInstruction::TransferOwnership { new_owner } => {
+                let owner = self.initializer_pubkey;
+                self.transfer_ownership(&owner, *new_owner)?;
+            }
+            GravityContractInstruction::BatchUpdateRounds { updates } => {
+                let slot = self.last_update_slot;
+                for (round, _) in updates.iter() {
+                    self.advance_round(*round, slot)?;
+                }
+            }
+            GravityContractInstruction::SetConsulKeyTypes { key_types } => {
+                self.set_key_types(key_types)?;
+            }
+            GravityContractInstruction::SetConsulShard {
+                index,
+                shard_count,
+                consuls,
+            } => {
+                self.set_consul_shard(*index, *shard_count, consuls)?;
+            }
+            GravityContractInstruction::UpdateRoundMixed { round, .. } => {
+                let slot = self.last_update_slot;
+                self.advance_round(*round, slot)?;
+            }
+            GravityContractInstruction::ProposeConfig { config } => config.validate()?,
+            // these touch accounts other than the contract, or move it as it is
+            GravityContractInstruction::ResetPendingUpdate
+            | GravityContractInstruction::Migrate
+            | GravityContractInstruction::RegisterNebula { .. }
+            | GravityContractInstruction::SignPendingUpdate { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn check_round(&self, new_round: u64) -> Result<(), GravityError> {
+        if self.last_round == u64::MAX {
+            return Err(GravityError::RoundExhausted);
+        }
+        if new_round <= self.last_round {
+            return Err(GravityError::InputRoundMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the last round update is more than `max_age` slots behind `current_slot`.
+    pub fn is_stale(&self, current_slot: Slot, max_age: u64) -> bool {
+        current_slot.saturating_sub(self.last_update_slot) > max_age
+    }
+
+    /// Slot the round following `last_round` closes at, for a round opened at `round_start_slot`
+    /// and lasting `slots_per_round`: consuls have to sign before it, in a slot below the value
+    /// returned. The contract tracks neither, each deployment sets its own round length.
+    /// Saturates rather than wrapping.
+    pub fn signing_deadline(&self, round_start_slot: Slot, slots_per_round: u64) -> Slot {
+        round_start_slot.saturating_add(slots_per_round)
+    }
+
+    /// How many rounds `last_round` trails `reference_round` by, zero when caught up or ahead.
+    pub fn round_lag(&self, reference_round: u64) -> u64 {
+        reference_round.saturating_sub(self.last_round)
+    }
+
+    /// Encodes `target` as the fields it changes relative to `base`: two bytes flagging each
+    /// changed field, the first eight fields in the first byte and the rest in the second,
+    /// then the new value of every flagged one. Consuls go as the base positions dropped and
+    /// the keys appended after the rest, or as the whole set when that is shorter.
+    pub fn diff_encode(base: &GravityContract, target: &GravityContract) -> Vec<u8> {
+        let mut changed = 0u8;
+        let mut changed_ext = 0u8;
+        let mut fields = vec![];
+
+        if target.initializer_pubkey != base.initializer_pubkey {
+            changed |= DIFF_INITIALIZER;
+            fields.extend_from_slice(target.initializer_pubkey.as_ref());
+        }
+        if target.bft != base.bft {
+            changed |= DIFF_BFT;
+            fields.push(target.bft);
+        }
+        if target.consuls != base.consuls {
+            changed |= DIFF_CONSULS;
+            fields.extend(encode_consul_diff(&base.consuls, &target.consuls));
+        }
+        for (flag, base_value, target_value) in [
+            (DIFF_LAST_ROUND, base.last_round, target.last_round),
+            (DIFF_CONSUL_EPOCH, base.consul_epoch, target.consul_epoch),
+            (DIFF_CHAIN_ID, base.chain_id, target.chain_id),
+            (
+                DIFF_LAST_UPDATE_SLOT,
+                base.last_update_slot,
+                target.last_update_slot,
+            ),
+        ] {
+            if target_value != base_value {
+                changed |= flag;
+                fields.extend_from_slice(&target_value.to_le_bytes());
+            }
+        }
+        if target.multisig_account != base.multisig_account {
+            changed |= DIFF_MULTISIG;
+            fields.extend_from_slice(target.multisig_account.as_ref());
+        }
+        if target.secp256k1_consuls != base.secp256k1_consuls {
+            changed_ext |= DIFF_SECP256K1_CONSULS;
+            fields.push(target.secp256k1_consuls);
+        }
+        if target.consul_shards != base.consul_shards {
+            changed_ext |= DIFF_CONSUL_SHARDS;
+            fields.push(target.consul_shards);
+        }
+
+        let mut patch = vec![changed, changed_ext];
+        patch.extend(fields);
+        patch
+    }
+
+    /// Rebuilds the contract `diff_encode` was given as `target` from the same `base`.
+    /// Truncated or trailing bytes, flags standing for no field and dropped positions out
+    /// of order or past the base consuls are rejected.
+    pub fn diff_apply(
+        base: &GravityContract,
+        patch: &[u8],
+    ) -> Result<GravityContract, ProgramError> {
+        let mut src = patch;
+        let flags = take_patch(&mut src, 2)?;
+        let (changed, changed_ext) = (flags[0], flags[1]);
+        if changed_ext & !DIFF_EXT_FIELDS != 0 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut target = base.clone();
+
+        if changed & DIFF_INITIALIZER != 0 {
+            target.initializer_pubkey = Pubkey::new(take_patch(&mut src, 32)?);
+        }
+        if changed & DIFF_BFT != 0 {
+            target.bft = take_patch(&mut src, 1)?[0];
+        }
+        if changed & DIFF_CONSULS != 0 {
+            target.consuls = decode_consul_diff(&base.consuls, &mut src)?;
+        }
+        for (flag, value) in [
+            (DIFF_LAST_ROUND, &mut target.last_round),
+            (DIFF_CONSUL_EPOCH, &mut target.consul_epoch),
+            (DIFF_CHAIN_ID, &mut target.chain_id),
+            (DIFF_LAST_UPDATE_SLOT, &mut target.last_update_slot),
+        ] {
+            if changed & flag != 0 {
+                *value = u64::from_le_bytes(*array_ref![take_patch(&mut src, 8)?, 0, 8]);
+            }
+        }
+        if changed & DIFF_MULTISIG != 0 {
+            target.multisig_account = Pubkey::new(take_patch(&mut src, 32)?);
+        }
+        if changed_ext & DIFF_SECP256K1_CONSULS != 0 {
+            target.secp256k1_consuls = take_patch(&mut src, 1)?[0];
+        }
+        if changed_ext & DIFF_CONSUL_SHARDS != 0 {
+            target.consul_shards = take_patch(&mut src, 1)?[0];
+        }
+
+        if !src.is_empty() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(target)
+    }
+}
+
+const DIFF_INITIALIZER: u8 = 1 << 0;
+const DIFF_BFT: u8 = 1 << 1;
+const DIFF_CONSULS: u8 = 1 << 2;
+const DIFF_LAST_ROUND: u8 = 1 << 3;
+const DIFF_MULTISIG: u8 = 1 << 4;
+const DIFF_CONSUL_EPOCH: u8 = 1 << 5;
+const DIFF_CHAIN_ID: u8 = 1 << 6;
+const DIFF_LAST_UPDATE_SLOT: u8 = 1 << 7;
+
+// flags of the second byte
+const DIFF_SECP256K1_CONSULS: u8 = 1 << 0;
+const DIFF_CONSUL_SHARDS: u8 = 1 << 1;
+const DIFF_EXT_FIELDS: u8 = DIFF_SECP256K1_CONSULS | DIFF_CONSUL_SHARDS;
+
+/// Consuls as a delta (tag 0, the dropped base positions and the appended keys, each
+/// behind a one byte count) or in full (tag 1, behind a four byte count).
+fn encode_consul_diff(base: &[Pubkey], target: &[Pubkey]) -> Vec<u8> {
+    // keep the longest run of target consuls that the base already holds in order
+    let mut kept = 0;
+    let mut dropped = vec![];
+    for (position, consul) in base.iter().enumerate() {
+        if target.get(kept) == Some(consul) {
+            kept += 1;
+        } else {
+            dropped.push(position as u8);
+        }
+    }
+    let appended = &target[kept..];
+
+    let mut full = vec![1];
+    full.extend_from_slice(&(target.len() as u32).to_le_bytes());
+    for consul in target.iter() {
+        full.extend_from_slice(consul.as_ref());
+    }
+    if base.len() > u8::MAX as usize || appended.len() > u8::MAX as usize {
+        return full;
+    }
+
+    let mut delta = vec![0, dropped.len() as u8];
+    delta.extend(dropped);
+    delta.push(appended.len() as u8);
+    for consul in appended.iter() {
+        delta.extend_from_slice(consul.as_ref());
+    }
+
+    if delta.len() < full.len() {
+        delta
+    } else {
+        full
+    }
+}
+
+fn decode_consul_diff(base: &[Pubkey], src: &mut &[u8]) -> Result<Vec<Pubkey>, ProgramError> {
+    let take_consuls = |src: &mut &[u8], count: usize| {
+        (0..count)
+            .map(|_| Ok(Pubkey::new(take_patch(src, 32)?)))
+            .collect::<Result<Vec<Pubkey>, ProgramError>>()
+    };
+
+    match take_patch(src, 1)?[0] {
+        0 => {
+            let count = take_patch(src, 1)?[0] as usize;
+            let dropped = take_patch(src, count)?;
+            if !dropped.windows(2).all(|pair| pair[0] < pair[1])
+                || dropped
+                    .iter()
+                    .any(|position| *position as usize >= base.len())
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let mut consuls: Vec<Pubkey> = base
+                .iter()
+                .enumerate()
+                .filter(|(position, _)| !dropped.contains(&(*position as u8)))
+                .map(|(_, consul)| *consul)
+                .collect();
+            let count = take_patch(src, 1)?[0] as usize;
+            consuls.extend(take_consuls(src, count)?);
+            Ok(consuls)
+        }
+        1 => {
+            let count = u32::from_le_bytes(*array_ref![take_patch(src, 4)?, 0, 4]) as usize;
+            take_consuls(src, count)
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn take_patch<'a>(src: &mut &'a [u8], len: usize) -> Result<&'a [u8], ProgramError> {
+    if src.len() < len {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let (head, tail) = src.split_at(len);
+    *src = tail;
+    Ok(head)
+}
+
+fn abi_encode_bytes32_array(consuls: &[Pubkey]) -> Vec<u8> {
+    const WORD: usize = 32;
+
+    let mut encoded = Vec::with_capacity(WORD * (2 + consuls.len()));
+
+    let mut offset = [0; WORD];
+    offset[WORD - 8..].copy_from_slice(&(WORD as u64).to_be_bytes());
+    encoded.extend_from_slice(&offset);
+
+    let mut length = [0; WORD];
+    length[WORD - 8..].copy_from_slice(&(consuls.len() as u64).to_be_bytes());
+    encoded.extend_from_slice(&length);
+
+    for consul in consuls.iter() {
+        encoded.extend_from_slice(consul.as_ref());
+    }
+
+    encoded
+}
+
+impl HashAlgo {
+    pub fn digest(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgo::Sha256 => hash::hash(data).to_bytes(),
+            HashAlgo::Keccak256 => keccak::hash(data).to_bytes(),
+        }
+    }
+}
+
+fn consul_leaf(index: usize, consul: &Pubkey) -> Vec<u8> {
+    let mut leaf = (index as u64).to_le_bytes().to_vec();
+    leaf.extend_from_slice(consul.as_ref());
+    leaf
+}
+
+/// Checks a proof from `consul_membership_proof` without the consul set at hand.
+pub fn verify_consul_membership(
+    root: MerkleHash,
+    key: &Pubkey,
+    index: usize,
+    proof: &[MerkleHash],
+) -> bool {
+    verify_merkle_inclusion(root, &consul_leaf(index, key), proof)
+}
+
+/// Whether `new` secures the contract worse than `old` did, going by `security_assessment`
+/// so that a quorum scaled along with the consul set doesn't count as weaker.
+pub fn bft_regressed(old: &GravityContract, new: &GravityContract) -> bool {
+    new.security_assessment() < old.security_assessment()
+}
+
+/// Consuls shared by all of `contracts`, sorted so the result doesn't depend on either order.
+pub fn common_consuls(contracts: &[&GravityContract]) -> Vec<Pubkey> {
+    let (first, rest) = match contracts.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+
+    let mut common: Vec<Pubkey> = first
+        .consuls
+        .iter()
+        .filter(|consul| {
+            rest.iter()
+                .all(|gravity_contract_info| gravity_contract_info.consuls.contains(consul))
+        })
+        .copied()
+        .collect();
+    common.sort();
+    common.dedup();
+
+    common
+}
+
+/// Assembles a contract field by field, checking the invariants of `validate` on `build`.
+#[derive(Default, Debug, Clone)]
+pub struct GravityContractBuilder {
+    gravity_contract_info: GravityContract,
+}
+
+impl GravityContractBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn initializer(mut self, initializer_pubkey: Pubkey) -> Self {
+        self.gravity_contract_info.initializer_pubkey = initializer_pubkey;
+        self
+    }
+
+    pub fn bft(mut self, bft: u8) -> Self {
+        self.gravity_contract_info.bft = bft;
+        self
+    }
+
+    pub fn add_consul(mut self, consul: Pubkey) -> Self {
+        self.gravity_contract_info.consuls.push(consul);
+        self
+    }
+
+    pub fn multisig_account(mut self, multisig_account: Pubkey) -> Self {
+        self.gravity_contract_info.multisig_account = multisig_account;
+        self
+    }
+
+    pub fn build(self) -> Result<GravityContract, GravityError> {
+        self.gravity_contract_info.check_invariants()?;
+
+        Ok(self.gravity_contract_info)
+    }
+}
+
+/// Lookup between consul keys and their positions, built once per verification.
+pub struct ConsulIndex<'a> {
+    consuls: &'a [Pubkey],
+    positions: HashMap<Pubkey, u8>,
+}
+
+impl<'a> ConsulIndex<'a> {
+    /// Fails for a set with consuls past the last position a `u8` index can address.
+    pub fn new(consuls: &'a [Pubkey]) -> Result<Self, GravityError> {
+        let positions = consuls
+            .iter()
+            .enumerate()
+            .map(|(i, consul)| {
+                u8::try_from(i)
+                    .map(|i| (*consul, i))
+                    .map_err(|_| GravityError::TooManyConsuls)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ConsulIndex { consuls, positions })
+    }
+
+    pub fn by_key(&self, consul: &Pubkey) -> Option<u8> {
+        self.positions.get(consul).copied()
+    }
+
+    pub fn by_index(&self, index: u8) -> Option<&'a Pubkey> {
+        self.consuls.get(index as usize)
+    }
+}
+
+/// What an RPC lookup reports about the account behind a key.
+#[derive(PartialEq, Eq, Default, Debug, Clone, Copy)]
+pub struct AccountProfile {
+    pub owner: Pubkey,
+    pub executable: bool,
+    pub lamports: u64,
+    pub data_len: usize,
+}
+
+/// Why a consul doesn't look like a funded keypair able to sign.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ConsulAccountIssue {
+    /// Program derived addresses have no private key to sign with.
+    OffCurve,
+    Executable,
+    NotSystemOwned,
+    NotRentExempt,
+}
+
+/// A proposed consul set, checked off-chain before it gets deployed.
+pub struct ConsulSet<'a>(pub &'a [Pubkey]);
+
+impl<'a> ConsulSet<'a> {
+    /// The `(threshold, total)` pair a threshold signature scheme is configured with to accept
+    /// exactly what a `bft`-of-n quorum of these consuls does.
+    pub fn threshold_params(&self, bft: u8) -> Result<(u8, u8), GravityError> {
+        let total = u8::try_from(self.0.len()).map_err(|_| GravityError::TooManyConsuls)?;
+        if bft == 0 || bft > total {
+            return Err(GravityError::InvalidBFTCount);
+        }
+
+        Ok((bft, total))
+    }
+
+    /// Whether `other_commitment` is the `algo` digest of the consuls in sorted order, ABI-encoded
+    /// as `bytes32[]`, so that parties that gathered the same keys in a different order agree.
+    pub fn commitment_matches(&self, other_commitment: [u8; 32], algo: HashAlgo) -> bool {
+        let mut consuls = self.0.to_vec();
+        consuls.sort();
+
+        algo.digest(&abi_encode_bytes32_array(&consuls)) == other_commitment
+    }
+
+    /// How evenly the consuls spread over the operators in `clusters`, as the entropy of
+    /// the cluster sizes over its maximum: 1 when every consul runs independently, 0 when
+    /// they all share one operator. Consuls missing from `clusters` count as independent;
+    /// a set of one or no consuls scores 0.
+    pub fn decentralization_score(&self, clusters: &HashMap<Pubkey, u32>) -> f32 {
+        let total = self.0.len();
+        if total < 2 {
+            return 0.0;
+        }
+
+        let mut sizes: HashMap<u32, usize> = HashMap::new();
+        let mut independent = 0;
+        for consul in self.0.iter() {
+            match clusters.get(consul) {
+                Some(cluster) => *sizes.entry(*cluster).or_default() += 1,
+                None => independent += 1,
+            }
+        }
+
+        let share = |size: usize| {
+            let p = size as f32 / total as f32;
+            -p * p.ln()
+        };
+        let entropy: f32 =
+            sizes.values().map(|size| share(*size)).sum::<f32>() + independent as f32 * share(1);
+
+        entropy / (total as f32).ln()
+    }
+
+    /// Checks every consul against an external registry of approved validators, failing with
+    /// those `is_approved` turns down, in set order.
+    pub fn all_approved<F: Fn(&Pubkey) -> bool>(&self, is_approved: F) -> Result<(), Vec<Pubkey>> {
+        let unapproved: Vec<Pubkey> = self
+            .0
+            .iter()
+            .filter(|consul| !is_approved(consul))
+            .copied()
+            .collect();
+
+        if unapproved.is_empty() {
+            Ok(())
+        } else {
+            Err(unapproved)
+        }
+    }
+
+    /// Asks `is_online` about every consul and fails unless at least `bft` of them answer.
+    pub fn validate_liveness<F: Fn(&Pubkey) -> bool>(
+        &self,
+        bft: u8,
+        is_online: F,
+    ) -> Result<(), GravityError> {
+        let online = self.0.iter().filter(|consul| is_online(consul)).count();
+        if online < bft as usize {
+            return Err(GravityError::QuorumUnreachable);
+        }
+
+        Ok(())
+    }
+
+    /// Flags every consul whose account, as described by `probe`, isn't a rent-exempt system
+    /// account, with the most serious issue found. Consuls that look fine are left out.
+    pub fn check_accounts<F: Fn(&Pubkey) -> AccountProfile>(
+        &self,
+        probe: F,
+    ) -> Vec<(Pubkey, ConsulAccountIssue)> {
+        let rent = Rent::default();
+
+        self.0
+            .iter()
+            .filter_map(|consul| {
+                let profile = probe(consul);
+
+                let issue = if !is_ed25519_point(consul) {
+                    ConsulAccountIssue::OffCurve
+                } else if profile.executable {
+                    ConsulAccountIssue::Executable
+                } else if profile.owner != system_program::id() {
+                    ConsulAccountIssue::NotSystemOwned
+                } else if !rent.is_exempt(profile.lamports, profile.data_len) {
+                    ConsulAccountIssue::NotRentExempt
+                } else {
+                    return None;
+                };
+
+                Some((*consul, issue))
+            })
+            .collect()
+    }
+}
+
+/// Borrowed view over a packed `GravityContract`, reading fields straight from the bytes.
+#[derive(Clone, Copy)]
+pub struct PackedContract<'a>(pub &'a [u8]);
+
+impl<'a> PackedContract<'a> {
+    const INITIALIZER_OFFSET: usize = DISCRIMINATOR_LEN;
+    const BFT_OFFSET: usize = DISCRIMINATOR_LEN + 32;
+    const CONSULS_OFFSET: usize = DISCRIMINATOR_LEN + 33;
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], ProgramError> {
+        self.0
+            .get(offset..offset + len)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    pub fn initializer(&self) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::new(self.bytes(Self::INITIALIZER_OFFSET, 32)?))
+    }
+
+    pub fn bft(&self) -> Result<u8, ProgramError> {
+        Ok(self.bytes(Self::BFT_OFFSET, 1)?[0])
+    }
+
+    pub fn consul_count(&self) -> Result<usize, ProgramError> {
+        let bytes = self.bytes(Self::CONSULS_OFFSET, 4)?;
+        Ok(u32::from_le_bytes(*array_ref![bytes, 0, 4]) as usize)
+    }
+
+    pub fn last_round(&self) -> Result<u64, ProgramError> {
+        let offset = Self::CONSULS_OFFSET + 4 + 32 * self.consul_count()?;
+        let bytes = self.bytes(offset, 8)?;
+        Ok(u64::from_le_bytes(*array_ref![bytes, 0, 8]))
+    }
+
+    pub fn unpack(&self) -> Result<GravityContract, ProgramError> {
+        GravityContract::unpack_from_slice(self.0)
+    }
+}
+
+/// Nebulas the Gravity contract signs for.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct NebulaRegistry {
+    pub gravity_contract: Pubkey,
+    pub nebulas: Vec<Pubkey>,
+}
+
+impl Sealed for NebulaRegistry {}
+
+impl IsInitialized for NebulaRegistry {
+    fn is_initialized(&self) -> bool {
+        self.gravity_contract != Pubkey::default()
+    }
+}
+
+impl Pack for NebulaRegistry {
+    const LEN: usize = DISCRIMINATOR_LEN + 32 + 4 + 32 * Self::MAX_NEBULAS;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize NebulaRegistry instruction: {}",
+                err
+            );
+            ProgramError::InvalidInstructionData
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        let dst = write_discriminator(dst, &Self::DISCRIMINATOR);
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl NebulaRegistry {
+    pub const DISCRIMINATOR: Discriminator = NEBULA_REGISTRY;
+    pub const MAX_NEBULAS: usize = 16;
+
+    pub fn register(&mut self, nebula: Pubkey) -> Result<(), GravityError> {
+        if self.is_registered(&nebula) {
+            return Err(GravityError::NebulaAlreadyRegistered);
+        }
+        if self.nebulas.len() >= Self::MAX_NEBULAS {
+            return Err(GravityError::NebulaRegistryFull);
+        }
+
+        self.nebulas.push(nebula);
+        Ok(())
+    }
+
+    pub fn is_registered(&self, nebula: &Pubkey) -> bool {
+        self.nebulas.contains(nebula)
+    }
+}
+
+pub type ConsulSignature = [u8; 64];
+
+/// Collects consul signatures over an `UpdateConsuls` payload, so that each consul
+/// can sign in its own transaction until the quorum is reached.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct PendingUpdate {
+    pub gravity_contract: Pubkey,
+    pub current_round: u64,
+    pub new_consuls: Vec<Pubkey>,
+    pub signatures: Vec<(u8, ConsulSignature)>,
+    pub is_finalized: bool,
+    pub started_at: Slot,
+}
+
+impl Sealed for PendingUpdate {}
+
+impl IsInitialized for PendingUpdate {
+    fn is_initialized(&self) -> bool {
+        self.gravity_contract != Pubkey::default()
+    }
+}
+
+impl Pack for PendingUpdate {
+    // fits 11 new consuls and 11 signatures, the multisig signers limit
+    const LEN: usize = DISCRIMINATOR_LEN + 1124;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize PendingUpdate instruction: {}",
+                err
+            );
+            ProgramError::InvalidInstructionData
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        let dst = write_discriminator(dst, &Self::DISCRIMINATOR);
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl PendingUpdate {
+    pub const DISCRIMINATOR: Discriminator = PENDING_UPDATE;
+
+    /// Roughly ten minutes of slots.
+    pub const TTL: Slot = 1500;
+
+    /// What the consuls sign for this update: the `ConsulRotation` preimage moving
+    /// `gravity_contract` to `new_consuls` at `current_round`.
+    pub fn signing_message(&self, gravity_contract: &GravityContract) -> Vec<u8> {
+        gravity_contract.signing_preimage(
+            Action::ConsulRotation,
+            &ActionParams {
+                new_round: self.current_round,
+                new_consuls: self.new_consuls.clone(),
+                ..ActionParams::default()
+            },
+        )
+    }
+
+    /// Records the signature of `signer` over `message`, which has to check out against
+    /// `verifier` and come from one of `consuls`, each signing only once.
+    pub fn add_signature(
+        &mut self,
+        verifier: &dyn SignatureVerifier,
+        consuls: &[Pubkey],
+        signer: &Pubkey,
+        message: &[u8],
+        signature: ConsulSignature,
+    ) -> Result<(), GravityError> {
+        if self.is_finalized {
+            return Err(GravityError::PendingUpdateFinalized);
+        }
+
+        let consul_index = consuls
+            .iter()
+            .position(|consul| consul == signer)
+            .ok_or(GravityError::UnknownConsul)?;
+        let consul_index = u8::try_from(consul_index).map_err(|_| GravityError::TooManyConsuls)?;
+
+        if self.signatures.iter().any(|(index, _)| *index == consul_index) {
+            return Err(GravityError::DuplicateSignature);
+        }
+        if !is_canonical_scalar(&signature) {
+            return Err(GravityError::MalleableSignature);
+        }
+        if !verifier.verify_ed25519(signer, message, &signature) {
+            return Err(GravityError::InvalidSignature);
+        }
+
+        self.signatures.push((consul_index, signature));
+        Ok(())
+    }
+
+    pub fn is_ready(&self, bft: u8) -> bool {
+        self.signatures.len() >= bft as usize
+    }
+
+    pub fn finalize(&mut self, bft: u8) -> Result<(), GravityError> {
+        if self.is_finalized {
+            return Err(GravityError::PendingUpdateFinalized);
+        }
+        if !self.is_ready(bft) {
+            return Err(GravityError::InvalidBFTCount);
+        }
+
+        self.is_finalized = true;
+        Ok(())
+    }
+
+    pub fn is_expired(&self, time_source: &dyn TimeSource, ttl: Slot) -> bool {
+        time_source.current_slot() > self.started_at.saturating_add(ttl)
+    }
+}
+
+/// A single consul rotation, linking the commitments before and after it.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct RotationEntry {
+    pub epoch: u64,
+    pub old_commitment: Hash,
+    pub new_commitment: Hash,
+    pub initiator: Pubkey,
+    pub slot: Slot,
+}
+
+/// Append-only trail of the latest consul rotations of a Gravity contract, kept at the
+/// contract's `RotationLog::address`.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct RotationLog {
+    pub gravity_contract: Pubkey,
+    pub entries: Vec<RotationEntry>,
+}
+
+impl Sealed for RotationLog {}
+
+impl IsInitialized for RotationLog {
+    fn is_initialized(&self) -> bool {
+        self.gravity_contract != Pubkey::default()
+    }
+}
+
+impl Pack for RotationLog {
+    const LEN: usize = DISCRIMINATOR_LEN + 32 + 4 + Self::ENTRY_LEN * Self::MAX_ENTRIES;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize RotationLog instruction: {}",
+                err
+            );
+            ProgramError::InvalidInstructionData
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        let dst = write_discriminator(dst, &Self::DISCRIMINATOR);
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl RotationLog {
+    pub const DISCRIMINATOR: Discriminator = ROTATION_LOG;
+    pub const MAX_ENTRIES: usize = 16;
+    const ENTRY_LEN: usize = 8 + 32 + 32 + 32 + 8;
+
+    /// Seed prefix of the account a contract's rotations are logged in.
+    pub const SEED: &'static [u8] = b"rotation_log";
+
+    /// The one account the rotations of `gravity_contract` can be logged in, so that no
+    /// rotation goes unrecorded by being logged elsewhere.
+    pub fn address(gravity_contract: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, gravity_contract.as_ref()], program_id)
+    }
+
+    /// Appends `entry`, dropping the oldest one once the log holds `MAX_ENTRIES`: a full log
+    /// must never hold up a rotation. What is kept still chains from entry to entry.
+    pub fn append(&mut self, entry: RotationEntry) {
+        if self.entries.len() >= Self::MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// Every entry has to start from the commitment the previous one ended with.
+    pub fn verify_chain(&self) -> bool {
+        self.entries
+            .windows(2)
+            .all(|pair| pair[1].old_commitment == pair[0].new_commitment)
+    }
+}
+
+/// The consul set a contract had at some round, by its `consul_commitment`.
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub struct RoundHistoryEntry {
+    pub round: u64,
+    pub consul_hash: Hash,
+}
+
+/// Consul sets by round, as tracked off chain. Writers only append, so lookups should run
+/// on a `compact` history.
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct ConsulRoundHistory {
+    pub entries: Vec<RoundHistoryEntry>,
+}
+
+impl ConsulRoundHistory {
+    /// Sorts the entries by round and keeps one per round, the one appended last.
+    pub fn compact(&mut self) {
+        // stable, so entries of the same round stay in the order they were appended
+        self.entries.sort_by_key(|entry| entry.round);
+
+        let mut compacted: Vec<RoundHistoryEntry> = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.drain(..) {
+            match compacted.last_mut() {
+                Some(last) if last.round == entry.round => *last = entry,
+                _ => compacted.push(entry),
+            }
+        }
+        self.entries = compacted;
+    }
+
+    /// First round missing between the earliest and the latest entry of a compact history.
+    pub fn first_gap(&self) -> Option<u64> {
+        self.entries
+            .windows(2)
+            .find(|pair| pair[1].round > pair[0].round + 1)
+            .map(|pair| pair[0].round + 1)
+    }
+}
+
+/// A mutation of a Gravity contract, carrying what it takes to redo it.
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Clone)]
+pub enum ContractEvent {
+    Initialized {
+        initializer: Pubkey,
+        bft: u8,
+        consuls: Vec<Pubkey>,
+        multisig_account: Pubkey,
+        chain_id: u64,
+    },
+    RoundUpdated {
+        round: u64,
+    },
+    ConsulsRotated {
+        consuls: Vec<Pubkey>,
+    },
+    BftChanged {
+        bft: u8,
+    },
+    OwnershipTransferred {
+        new_owner: Pubkey,
+    },
+}
+
+/// An event together with the contract it left: the slot the contract recorded,
+/// `last_update_slot`, and the hash of its packed state.
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Clone)]
+pub struct EventRecord {
+    pub event: ContractEvent,
+    pub slot: Slot,
+    pub state_hash: Hash,
+}
+
+impl EventRecord {
+    /// Fails when `contract` can't be packed, its state having no hash to record then.
+    pub fn new(event: ContractEvent, contract: &GravityContract) -> Result<Self, ProgramError> {
+        Ok(EventRecord {
+            event,
+            slot: contract.last_update_slot,
+            state_hash: contract.state_hash()?,
+        })
+    }
+
+    /// Leads the program log lines `to_log` writes.
+    pub const LOG_PREFIX: &'static str = "Event: ";
+
+    /// Leads every line a program logs, in the log messages of a transaction.
+    pub const PROGRAM_LOG_PREFIX: &'static str = "Program log: ";
+
+    /// The line the processor logs the record as: `LOG_PREFIX` followed by the base64 of its
+    /// Borsh encoding, which, unlike `Debug` output, is a format decoders can rely on.
+    pub fn to_log(&self) -> String {
+        format!(
+            "{}{}",
+            Self::LOG_PREFIX,
+            base64::encode(self.try_to_vec().unwrap())
+        )
+    }
+
+    /// Decodes a line written by `to_log`, with or without the `PROGRAM_LOG_PREFIX` the
+    /// runtime puts in front of it. `None` for any other line; an event line that doesn't
+    /// decode fails.
+    pub fn from_log(line: &str) -> Result<Option<Self>, ProgramError> {
+        let line = line.strip_prefix(Self::PROGRAM_LOG_PREFIX).unwrap_or(line);
+        let encoded = match line.strip_prefix(Self::LOG_PREFIX) {
+            Some(encoded) => encoded,
+            None => return Ok(None),
+        };
+
+        let data = base64::decode(encoded).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let record = Self::try_from_slice(&data).map_err(|err| {
+            msg!("Error: failed to deserialize EventRecord: {}", err);
+            ProgramError::InvalidInstructionData
+        })?;
+
+        Ok(Some(record))
+    }
+}
+
+/// The events of a contract's lifetime, in the order the processor logged them.
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct EventLog {
+    pub records: Vec<EventRecord>,
+}
+
+impl EventLog {
+    pub fn record(
+        &mut self,
+        event: ContractEvent,
+        contract: &GravityContract,
+    ) -> Result<(), ProgramError> {
+        self.records.push(EventRecord::new(event, contract)?);
+        Ok(())
+    }
+
+    /// Collects the records out of the log messages of a contract's transactions, in the
+    /// order they were logged, passing over every line that isn't one.
+    pub fn from_logs<S: AsRef<str>>(lines: &[S]) -> Result<Self, ProgramError> {
+        let mut records = vec![];
+        for line in lines.iter() {
+            records.extend(EventRecord::from_log(line.as_ref())?);
+        }
+
+        Ok(EventLog { records })
+    }
+
+    /// Replays the events from a blank contract, through the transitions the processor
+    /// logged them from; every one of them has to reproduce the state hash it was recorded
+    /// with, and the last one that of `final_state`. A dropped or reordered event leaves the
+    /// replay on another state than the one recorded next.
+    pub fn verify_against(&self, final_state: &GravityContract) -> bool {
+        let mut state = GravityContract::default();
+
+        for record in self.records.iter() {
+            match &record.event {
+                ContractEvent::Initialized {
+                    initializer,
+                    bft,
+                    consuls,
+                    multisig_account,
+                    chain_id,
+                } => {
+                    match GravityContract::initialize(
+                        *initializer,
+                        *bft,
+                        consuls.clone(),
+                        *multisig_account,
+                        *chain_id,
+                    ) {
+                        Ok((initialized, _)) => state = initialized,
+                        Err(_) => return false,
+                    }
+                }
+                ContractEvent::RoundUpdated { round } => {
+                    if state.advance_round(*round, record.slot).is_err() {
+                        return false;
+                    }
+                }
+                ContractEvent::ConsulsRotated { consuls } => {
+                    if state.set_consuls(consuls.clone()).is_err() {
+                        return false;
+                    }
+                }
+                ContractEvent::BftChanged { bft } => state.bft = *bft,
+                ContractEvent::OwnershipTransferred { new_owner } => {
+                    state.initializer_pubkey = *new_owner
+                }
+            }
+
+            if state.state_hash() != Ok(record.state_hash) {
+                return false;
+            }
+        }
+
+        state.state_hash().is_ok() && state.state_hash() == final_state.state_hash()
+    }
+}
+
+/// One slice of a consul set kept across several accounts, `index` out of `shard_count`.
+#[derive(BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, Default, Debug, Clone)]
+pub struct ConsulShard {
+    pub gravity_contract: Pubkey,
+    pub index: u8,
+    pub shard_count: u8,
+    pub consuls: Vec<Pubkey>,
+}
+
+impl Sealed for ConsulShard {}
+
+impl IsInitialized for ConsulShard {
+    fn is_initialized(&self) -> bool {
+        self.gravity_contract != Pubkey::default()
+    }
+}
+
+impl Pack for ConsulShard {
+    const LEN: usize = DISCRIMINATOR_LEN + 32 + 1 + 1 + 4 + 32 * Self::MAX_CONSULS;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = strip_discriminator(src, &Self::DISCRIMINATOR)?;
+        let shard = Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize ConsulShard instruction: {}",
+                err
+            );
+            ProgramError::InvalidInstructionData
+        })?;
+        if shard.consuls.len() > Self::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+
+        Ok(shard)
+    }
+
+    /// `try_pack_into_slice`, panicking when the shard doesn't fit `dst`.
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        self.try_pack_into_slice(dst).unwrap();
+    }
+}
+
+impl ConsulShard {
+    pub const DISCRIMINATOR: Discriminator = CONSUL_SHARD;
+
+    /// Most consuls a shard holds, as many as the whole set a contract can verify with.
+    pub const MAX_CONSULS: usize = GravityContract::MAX_CONSULS;
+
+    /// Seed prefix of the accounts a contract's consul set is sharded across.
+    pub const SEED: &'static [u8] = b"consul_shard";
+
+    /// The one account shard `index` of `gravity_contract` can be kept in, so that no shard
+    /// of another set can be passed in its place.
+    pub fn address(gravity_contract: &Pubkey, index: u8, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[Self::SEED, gravity_contract.as_ref(), &[index]],
+            program_id,
+        )
+    }
+
+    /// Writes the shard over `dst`, zeroing whatever follows it. Fails without writing
+    /// anything when it holds more than `MAX_CONSULS` or `dst` is too short for it.
+    pub fn try_pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if self.consuls.len() > Self::MAX_CONSULS {
+            return Err(GravityError::TooManyConsuls.into());
+        }
+        let data = self
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if DISCRIMINATOR_LEN + data.len() > dst.len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let dst = write_discriminator(dst, &Self::DISCRIMINATOR);
+        let (fields, rest) = dst.split_at_mut(data.len());
+        fields.copy_from_slice(&data);
+        for byte in rest.iter_mut() {
+            *byte = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl GravityContract {
+    /// Reassembles the consul set kept across the contract's `consul_shards` shards, which
+    /// have to be passed in index order. Each one has to be the program's account at its
+    /// `ConsulShard::address` for `gravity_contract`, this contract's account, and be a
+    /// shard of it; the set can't grow past `MAX_CONSULS` either.
+    pub fn load_consuls_from_shards(
+        &self,
+        gravity_contract: &Pubkey,
+        shards: &[&AccountInfo],
+        program_id: &Pubkey,
+    ) -> Result<Vec<Pubkey>, ProgramError> {
+        if shards.len() != self.consul_shards as usize {
+            return Err(GravityError::InvalidShardSequence.into());
+        }
+
+        let mut consuls = vec![];
+        for (i, shard_account) in shards.iter().enumerate() {
+            if shard_account.owner != program_id {
+                msg!("Error: consul shard {} is owned by another program", i);
+                return Err(GravityError::SpoofedAccount.into());
+            }
+            if *shard_account.key != ConsulShard::address(gravity_contract, i as u8, program_id).0 {
+                return Err(GravityError::InvalidShardSequence.into());
+            }
+
+            let shard: ConsulShard = unpack_prefix(&shard_account.try_borrow_data()?)?;
+            if shard.gravity_contract != *gravity_contract
+                || shard.index as usize != i
+                || shard.shard_count as usize != shards.len()
+            {
+                return Err(GravityError::InvalidShardSequence.into());
+            }
+
+            consuls.extend(shard.consuls);
+            if consuls.len() > Self::MAX_CONSULS {
+                return Err(GravityError::TooManyConsuls.into());
+            }
+        }
+
+        if consuls.is_empty() {
+            return Err(GravityError::TooFewConsuls.into());
+        }
+
+        Ok(consuls)
+    }
+}
+
+/// Any state type the Gravity program keeps in its accounts.
+#[derive(PartialEq, Debug, Clone)]
+pub enum DecodedAccount {
+    Gravity(GravityContract),
+    PendingUpdate(PendingUpdate),
+    NebulaRegistry(NebulaRegistry),
+    RotationLog(RotationLog),
+    ConsulShard(ConsulShard),
+}
+
+fn unpack_prefix<T: Pack>(data: &[u8]) -> Result<T, ProgramError> {
+    let data = data
+        .get(0..T::LEN)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    T::unpack_from_slice(data)
+}
+
+/// Decodes an account of the Gravity program, picking the state type by the discriminator.
+pub fn decode_account(data: &[u8]) -> Result<DecodedAccount, ProgramError> {
+    Ok(match AccountKind::from_discriminator(data) {
+        Some(AccountKind::Gravity) => DecodedAccount::Gravity(unpack_prefix(data)?),
+        Some(AccountKind::PendingUpdate) => DecodedAccount::PendingUpdate(unpack_prefix(data)?),
+        Some(AccountKind::NebulaRegistry) => DecodedAccount::NebulaRegistry(unpack_prefix(data)?),
+        Some(AccountKind::RotationLog) => DecodedAccount::RotationLog(unpack_prefix(data)?),
+        Some(AccountKind::ConsulShard) => DecodedAccount::ConsulShard(unpack_prefix(data)?),
+        _ => return Err(GravityError::WrongAccountType.into()),
+    })
+}
+
+/// For indexers sifting scanned accounts: passes those that lead with the discriminator of
+/// a Gravity state type and are owned by `program_id`. The discriminator alone proves
+/// nothing, anyone can write it into an account of their own program.
+pub fn verify_scan_result(account: &AccountInfo, program_id: &Pubkey) -> Result<(), ProgramError> {
+    match AccountKind::from_discriminator(&account.try_borrow_data()?) {
+        Some(AccountKind::Gravity)
+        | Some(AccountKind::PendingUpdate)
+        | Some(AccountKind::NebulaRegistry)
+        | Some(AccountKind::RotationLog)
+        | Some(AccountKind::ConsulShard) => {}
+        _ => return Err(GravityError::WrongAccountType.into()),
+    }
+
+    if account.owner != program_id {
+        msg!(
+            "Error: {} is owned by {}, not the Gravity program",
+            account.key,
+            account.owner
+        );
+        return Err(GravityError::SpoofedAccount.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gravity_misc::discriminator::NEBULA_CONTRACT;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::gravity::{time::MockClock, verifier::SoftwareVerifier};
+
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    enum Endian {
+        Little,
+        Big,
+    }
+
+    impl GravityContract {
+        /// `pack_into_slice` with `bft` and `last_round` in the given byte order, for
+        /// exercising decoders against the documented little-endian layout.
+        fn pack_into_slice_with_endian(&self, dst: &mut [u8], endian: Endian) {
+            self.pack_into_slice(dst);
+            if endian == Endian::Little {
+                return;
+            }
+
+            // a single byte reads the same either way
+            dst[PackedContract::BFT_OFFSET] = self.bft;
+
+            let last_round_offset = PackedContract::CONSULS_OFFSET + 4 + 32 * self.consuls.len();
+            dst[last_round_offset..last_round_offset + 8]
+                .copy_from_slice(&self.last_round.to_be_bytes());
+        }
+    }
+
+    fn build_contract(consuls: Vec<Pubkey>) -> GravityContract {
+        GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls,
+            last_round: 7,
+            multisig_account: Pubkey::new_unique(),
+            consul_epoch: 0,
+            chain_id: 0,
+            last_update_slot: 0,
+            secp256k1_consuls: 0,
+            consul_shards: 0,
+        }
+    }
+
+    fn sorted_consuls(count: usize) -> Vec<Pubkey> {
+        let mut consuls: Vec<Pubkey> = (0..count).map(|_| Pubkey::new_unique()).collect();
+        consuls.sort();
+        consuls
+    }
+
+    #[test]
+    fn test_is_canonical() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        assert!(GravityContract::is_canonical(&packed));
+    }
+
+    #[test]
+    fn test_is_canonical_shuffled_consuls() {
+        let mut consuls = sorted_consuls(3);
+        consuls.reverse();
+        let gravity_contract_info = build_contract(consuls);
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        assert!(!GravityContract::is_canonical(&packed));
+        assert!(!GravityContract::is_canonical(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_canonicalize_buffer() {
+        let mut consuls = sorted_consuls(3);
+        consuls.reverse();
+        let mut gravity_contract_info = build_contract(consuls);
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        assert_eq!(GravityContract::canonicalize_buffer(&mut packed), Ok(true));
+        assert!(GravityContract::is_canonical(&packed));
+
+        gravity_contract_info.canonicalize();
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed).unwrap(),
+            gravity_contract_info
+        );
+        assert_eq!(GravityContract::canonicalize_buffer(&mut packed), Ok(false));
+
+        assert_eq!(
+            GravityContract::canonicalize_buffer(&mut [1, 2, 3]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_packed_contract() {
+        let gravity_contract_info = build_contract(sorted_consuls(5));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        let packed_contract = PackedContract(&packed);
+        let unpacked = packed_contract.unpack().unwrap();
+        assert_eq!(packed_contract.bft().unwrap(), unpacked.bft);
+        assert_eq!(
+            packed_contract.consul_count().unwrap(),
+            unpacked.consuls.len()
+        );
+        assert_eq!(packed_contract.last_round().unwrap(), unpacked.last_round);
+
+        assert!(PackedContract(&packed[..40]).last_round().is_err());
+    }
+
+    #[test]
+    fn test_pack_with_endian() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let mut little_endian = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice_with_endian(&mut little_endian, Endian::Little);
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(little_endian, packed);
+
+        // the layout is little-endian, so a big-endian round decodes to another value
+        let mut big_endian = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice_with_endian(&mut big_endian, Endian::Big);
+        let unpacked = GravityContract::unpack_from_slice(&big_endian).unwrap();
+        assert_eq!(unpacked.bft, gravity_contract_info.bft);
+        assert_ne!(unpacked.last_round, gravity_contract_info.last_round);
+        assert_eq!(
+            unpacked.last_round,
+            gravity_contract_info.last_round.swap_bytes()
+        );
+    }
+
+    #[test]
+    fn test_from_geyser_update() {
+        let program_id = Pubkey::new_unique();
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        // plugins pass the whole account, rent padding included
+        let mut data = vec![0; GravityContract::LEN + 16];
+        gravity_contract_info.pack_into_slice(&mut data[..GravityContract::LEN]);
+
+        assert_eq!(
+            GravityContract::from_geyser_update(&data, &program_id, &program_id),
+            Ok(gravity_contract_info)
+        );
+        assert_eq!(
+            GravityContract::from_geyser_update(&data, &Pubkey::new_unique(), &program_id),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            GravityContract::from_geyser_update(
+                &data[..GravityContract::LEN - 1],
+                &program_id,
+                &program_id
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_unpack_checked_strict_mode() {
+        let program_id = Pubkey::new_unique();
+        let (contract, packed) = GravityContract::conformance_vectors().swap_remove(1);
+        let legacy_contract = GravityContract {
+            consul_epoch: 0,
+            chain_id: 0,
+            last_update_slot: 0,
+            ..contract.clone()
+        };
+
+        assert_eq!(
+            GravityContract::unpack_checked(
+                &golden_v1(),
+                &program_id,
+                &program_id,
+                StrictMode::Off
+            ),
+            Ok(legacy_contract)
+        );
+        assert_eq!(
+            GravityContract::unpack_checked(&golden_v1(), &program_id, &program_id, StrictMode::On),
+            Err(GravityError::LegacyLayoutRejected.into())
+        );
+        // migrated accounts read the same either way
+        for strict_mode in [StrictMode::Off, StrictMode::On].iter() {
+            assert_eq!(
+                GravityContract::unpack_checked(&packed, &program_id, &program_id, *strict_mode),
+                Ok(contract.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_initializer() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        assert_eq!(
+            GravityContract::read_initializer(&packed),
+            Ok(GravityContract::unpack_from_slice(&packed)
+                .unwrap()
+                .initializer_pubkey)
+        );
+        // the consuls aren't needed, so a slice cut right past the initializer still reads
+        assert_eq!(
+            GravityContract::read_initializer(&packed[..DISCRIMINATOR_LEN + 32]),
+            Ok(gravity_contract_info.initializer_pubkey)
+        );
+        assert_eq!(
+            GravityContract::read_initializer(&packed[..DISCRIMINATOR_LEN + 31]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        packed[..DISCRIMINATOR_LEN].copy_from_slice(&NEBULA_CONTRACT);
+        assert_eq!(
+            GravityContract::read_initializer(&packed),
+            Err(GravityError::WrongAccountType.into())
+        );
+    }
+
+    #[test]
+    fn test_iter_consuls_from_slice() {
+        let gravity_contract_info = build_contract(sorted_consuls(5));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        let consuls: Vec<Pubkey> = GravityContract::iter_consuls_from_slice(&packed)
+            .unwrap()
+            .collect();
+        assert_eq!(
+            consuls,
+            GravityContract::unpack_from_slice(&packed).unwrap().consuls
+        );
+
+        assert!(GravityContract::iter_consuls_from_slice(&packed[..100]).is_err());
+        assert!(GravityContract::iter_consuls_from_slice(&packed[..4]).is_err());
+    }
+
+    #[test]
+    fn test_try_recover() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        assert_eq!(
+            GravityContract::try_recover(&packed).unwrap(),
+            (gravity_contract_info.clone(), true)
+        );
+
+        // the write stopped halfway through the second consul
+        let mut interrupted = vec![0; GravityContract::LEN];
+        let cut = PackedContract::CONSULS_OFFSET + 4 + 32 + 16;
+        interrupted[..cut].copy_from_slice(&packed[..cut]);
+        let (recovered, is_valid) = GravityContract::try_recover(&interrupted).unwrap();
+        assert!(!is_valid);
+        assert_eq!(
+            recovered.initializer_pubkey,
+            gravity_contract_info.initializer_pubkey
+        );
+        assert_eq!(recovered.consuls[0], gravity_contract_info.consuls[0]);
+        assert_eq!(recovered.multisig_account, Pubkey::default());
+
+        // the write stopped before the consul count
+        let mut interrupted = vec![0; GravityContract::LEN];
+        let cut = PackedContract::CONSULS_OFFSET;
+        interrupted[..cut].copy_from_slice(&packed[..cut]);
+        let (recovered, is_valid) = GravityContract::try_recover(&interrupted).unwrap();
+        assert!(!is_valid);
+        assert_eq!(recovered.bft, gravity_contract_info.bft);
+        assert!(recovered.consuls.is_empty());
+
+        // truncated without padding, only the leading fields are there to recover
+        let (recovered, is_valid) = GravityContract::try_recover(&packed[..cut]).unwrap();
+        assert!(!is_valid);
+        assert_eq!(recovered.bft, gravity_contract_info.bft);
+        assert_eq!(recovered.last_round, 0);
+
+        assert!(GravityContract::try_recover(&[0; GravityContract::LEN]).is_err());
+    }
+
+    #[test]
+    fn test_config_id() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let config_id = gravity_contract_info.config_id();
+
+        gravity_contract_info.update_round(8).unwrap();
+        gravity_contract_info.consul_epoch += 1;
+        assert_eq!(gravity_contract_info.config_id(), config_id);
+
+        gravity_contract_info.consuls.reverse();
+        assert_eq!(gravity_contract_info.config_id(), config_id);
+
+        let mut rotated = gravity_contract_info.clone();
+        rotated.consuls[0] = Pubkey::new_unique();
+        assert_ne!(rotated.config_id(), config_id);
+
+        let mut rotated = gravity_contract_info.clone();
+        rotated.consuls.pop();
+        assert_ne!(rotated.config_id(), config_id);
+
+        gravity_contract_info.bft = 3;
+        assert_ne!(gravity_contract_info.config_id(), config_id);
+    }
+
+    #[test]
+    fn test_consul_epoch() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let commitment = gravity_contract_info.consul_commitment();
+
+        // a plain round update resubmits the same consuls
+        let same_consuls = gravity_contract_info.consuls.clone();
+        gravity_contract_info.set_consuls(same_consuls).unwrap();
+        assert_eq!(gravity_contract_info.consul_epoch, 0);
+        assert_eq!(gravity_contract_info.consul_commitment(), commitment);
+
+        gravity_contract_info
+            .set_consuls(sorted_consuls(3))
+            .unwrap();
+        assert_eq!(gravity_contract_info.consul_epoch, 1);
+        assert_ne!(gravity_contract_info.consul_commitment(), commitment);
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed)
+                .unwrap()
+                .consul_epoch,
+            1
+        );
+
+        gravity_contract_info.consul_epoch = u64::MAX;
+        let consuls = gravity_contract_info.consuls.clone();
+        assert!(matches!(
+            gravity_contract_info.set_consuls(sorted_consuls(3)),
+            Err(GravityError::EpochExhausted)
+        ));
+        assert_eq!(gravity_contract_info.consuls, consuls);
+    }
+
+    #[test]
+    fn test_full_commitment() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let commitment = gravity_contract_info.full_commitment();
+        let consul_commitment = gravity_contract_info.consul_commitment();
+
+        gravity_contract_info.consuls.reverse();
+        assert_eq!(gravity_contract_info.full_commitment(), commitment);
+        gravity_contract_info.consuls.reverse();
+
+        // the threshold alone is enough to tell the commitments apart
+        gravity_contract_info.bft = 3;
+        assert_ne!(gravity_contract_info.full_commitment(), commitment);
+        assert_eq!(gravity_contract_info.consul_commitment(), consul_commitment);
+    }
+
+    #[test]
+    fn test_validate_batch() {
+        let pack = |gravity_contract_info: GravityContract| {
+            let mut packed = vec![0; GravityContract::LEN];
+            gravity_contract_info.pack_into_slice(&mut packed);
+            packed
+        };
+
+        let valid = build_contract(sorted_consuls(3));
+        let mut over_threshold = build_contract(sorted_consuls(3));
+        over_threshold.bft = 4;
+        let consul = Pubkey::new_unique();
+        let duplicated = build_contract(vec![consul, consul]);
+
+        let accounts = vec![
+            (Pubkey::new_unique(), pack(valid)),
+            (Pubkey::new_unique(), vec![1, 2, 3]),
+            (Pubkey::new_unique(), pack(over_threshold)),
+            (Pubkey::new_unique(), pack(duplicated)),
+            (Pubkey::new_unique(), vec![0xff; GravityContract::LEN]),
+        ];
+
+        let results: Vec<Result<(), ProgramError>> = GravityContract::validate_batch(&accounts)
+            .into_iter()
+            .zip(accounts.iter())
+            .map(|((address, result), (expected_address, _))| {
+                assert_eq!(address, *expected_address);
+                result
+            })
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err(ProgramError::InvalidAccountData),
+                Err(GravityError::InvalidBFTCount.into()),
+                Err(GravityError::DuplicateConsul.into()),
+                Err(GravityError::WrongAccountType.into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let codes = |gravity_contract_info: &GravityContract| -> Vec<u32> {
+            gravity_contract_info
+                .validate_all()
+                .iter()
+                .map(|violation| *violation as u32)
+                .collect()
+        };
+
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        assert!(gravity_contract_info.validate_all().is_empty());
+
+        let duplicate = gravity_contract_info.consuls[0];
+        gravity_contract_info.consuls.push(duplicate);
+        gravity_contract_info.consuls.push(Pubkey::default());
+        gravity_contract_info.bft = 0;
+        assert_eq!(
+            codes(&gravity_contract_info),
+            vec![
+                GravityError::InvalidBFTCount as u32,
+                GravityError::DuplicateConsul as u32,
+                GravityError::InvalidConsulKey as u32,
+            ]
+        );
+
+        gravity_contract_info.consuls = sorted_consuls(GravityContract::MAX_CONSULS + 1);
+        gravity_contract_info.bft = 8;
+        assert_eq!(
+            codes(&gravity_contract_info),
+            vec![
+                GravityError::TooManyConsuls as u32,
+                GravityError::InvalidBFTCount as u32,
+            ]
+        );
+
+        gravity_contract_info.consuls.clear();
+        assert_eq!(
+            codes(&gravity_contract_info),
+            vec![
+                GravityError::TooFewConsuls as u32,
+                GravityError::InvalidBFTCount as u32,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_consul_keys() {
+        let on_curve: Vec<Pubkey> = (0..2).map(|_| Keypair::new().pubkey()).collect();
+        // program addresses are off the curve by construction
+        let (off_curve, _) = Pubkey::find_program_address(&[b"consul"], &Pubkey::new_unique());
+
+        let initializer = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+
+        let gravity_contract_info =
+            GravityContract::new(initializer, 2, on_curve.clone(), multisig, true).unwrap();
+        assert_eq!(gravity_contract_info.validate_consul_keys(), Ok(()));
+
+        let mut consuls = on_curve;
+        consuls.push(off_curve);
+        assert_eq!(
+            GravityContract::new(initializer, 2, consuls.clone(), multisig, true),
+            Err(GravityError::InvalidConsulKey.into())
+        );
+        assert!(GravityContract::new(initializer, 2, consuls, multisig, false).is_ok());
+    }
+
+    #[test]
+    fn test_pack_into_vec() {
+        let mut buf = Vec::new();
+
+        let gravity_contract_info = build_contract(sorted_consuls(5));
+        gravity_contract_info.pack_into_vec(&mut buf);
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(buf, packed);
+
+        let (capacity, ptr) = (buf.capacity(), buf.as_ptr());
+
+        // a smaller set leaves no stale bytes behind
+        let gravity_contract_info = build_contract(sorted_consuls(1));
+        gravity_contract_info.pack_into_vec(&mut buf);
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(buf, packed);
+
+        assert_eq!((buf.capacity(), buf.as_ptr()), (capacity, ptr));
+    }
+
+    #[test]
+    fn test_pack_to_array() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let packed = gravity_contract_info.pack_to_array().unwrap();
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed).unwrap(),
+            gravity_contract_info
+        );
+
+        let mut full = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut full);
+        assert_eq!(
+            &full[..GravityContract::LEGACY_LEN - GravityContract::RESERVED_LEN],
+            &packed[..GravityContract::LEGACY_LEN - GravityContract::RESERVED_LEN]
+        );
+
+        assert_eq!(
+            build_contract(sorted_consuls(5)).pack_to_array(),
+            Err(GravityError::TooManyConsuls.into())
+        );
+    }
+
+    #[test]
+    fn test_from_multisig_account() {
+        let consuls = sorted_consuls(3);
+        let mut multisig = Multisig {
+            m: 2,
+            n: 3,
+            is_initialized: true,
+            ..Multisig::default()
+        };
+        multisig.signers[..3].copy_from_slice(&consuls);
+
+        let key = Pubkey::new_unique();
+        let owner = spl_token::id();
+        let mut lamports = 0;
+        let mut data = vec![0; Multisig::LEN];
+        Multisig::pack(multisig, &mut data).unwrap();
+
+        let account = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(
+            GravityContract::from_multisig_account(&account).unwrap(),
+            consuls
+        );
+
+        account.try_borrow_mut_data().unwrap()[2] = 0;
+        assert_eq!(
+            GravityContract::from_multisig_account(&account),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn test_too_few_consuls() {
+        let gravity_contract_info = GravityContract {
+            bft: 0,
+            ..build_contract(vec![])
+        };
+        assert_eq!(
+            gravity_contract_info.validate(),
+            Err(GravityError::TooFewConsuls.into())
+        );
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed),
+            Err(GravityError::TooFewConsuls.into())
+        );
+
+        assert_eq!(
+            GravityContract::new(Pubkey::new_unique(), 0, vec![], Pubkey::new_unique(), false),
+            Err(GravityError::TooFewConsuls.into())
+        );
+    }
+
+    #[test]
+    fn test_assert_upgrade_preserves_consuls() {
+        let old = build_contract(sorted_consuls(3));
+        let upgraded = GravityContract {
+            last_update_slot: 40,
+            ..old.clone()
+        };
+        assert_eq!(
+            GravityContract::assert_upgrade_preserves_consuls(&old, &upgraded),
+            Ok(())
+        );
+
+        let mut dropped = upgraded.clone();
+        dropped.consuls[2] = Pubkey::new_unique();
+        assert_eq!(
+            GravityContract::assert_upgrade_preserves_consuls(&old, &dropped),
+            Err(GravityError::UnexpectedConsulChange.into())
+        );
+
+        let mut reordered = upgraded;
+        reordered.consuls.swap(0, 1);
+        assert_eq!(
+            GravityContract::assert_upgrade_preserves_consuls(&old, &reordered),
+            Err(GravityError::UnexpectedConsulChange.into())
+        );
+    }
+
+    #[test]
+    fn test_assert_roundtrip() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(4));
+        gravity_contract_info.consuls.reverse();
+
+        assert_eq!(gravity_contract_info.assert_roundtrip(), Ok(()));
+
+        let mut sorted = gravity_contract_info.clone();
+        sorted.canonicalize();
+        assert!(sorted.consuls_eq(&gravity_contract_info));
+        assert_ne!(sorted, gravity_contract_info);
+        assert!(!sorted.consuls_eq(&build_contract(sorted_consuls(4))));
+
+        // too few consuls to unpack again
+        gravity_contract_info.consuls.clear();
+        assert_eq!(
+            gravity_contract_info.assert_roundtrip(),
+            Err(GravityError::TooFewConsuls.into())
+        );
+        gravity_contract_info.consuls = sorted_consuls(GravityContract::MAX_CONSULS + 1);
+        assert_eq!(
+            gravity_contract_info.assert_roundtrip(),
+            Err(GravityError::TooManyConsuls.into())
+        );
+    }
+
+    #[test]
+    fn test_cpi_bytes() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let cpi_bytes = gravity_contract_info.to_cpi_bytes();
+        assert!(cpi_bytes.len() < GravityContract::LEN);
+        assert_eq!(
+            GravityContract::from_cpi_bytes(&cpi_bytes),
+            Ok(gravity_contract_info.clone())
+        );
+
+        let mut wrong_discriminator = cpi_bytes.clone();
+        wrong_discriminator[..DISCRIMINATOR_LEN].copy_from_slice(&NEBULA_CONTRACT);
+        assert_eq!(
+            GravityContract::from_cpi_bytes(&wrong_discriminator),
+            Err(GravityError::WrongAccountType.into())
+        );
+
+        let mut trailing = cpi_bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            GravityContract::from_cpi_bytes(&trailing),
+            Err(ProgramError::InvalidInstructionData)
+        );
+
+        let invalid = GravityContract {
+            bft: 4,
+            ..gravity_contract_info
+        };
+        assert_eq!(
+            GravityContract::from_cpi_bytes(&invalid.to_cpi_bytes()),
+            Err(GravityError::InvalidBFTCount.into())
+        );
+    }
+
+    #[test]
+    fn test_pack_sequence() {
+        let contracts: Vec<GravityContract> = [1, 6, 3]
+            .iter()
+            .map(|count| build_contract(sorted_consuls(*count)))
+            .collect();
+
+        let mut out = vec![];
+        GravityContract::pack_sequence(&contracts, &mut out);
+        assert_eq!(GravityContract::unpack_sequence(&out).unwrap(), contracts);
+
+        assert_eq!(GravityContract::unpack_sequence(&[]).unwrap(), vec![]);
+        assert_eq!(
+            GravityContract::unpack_sequence(&out[..out.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let consuls = sorted_consuls(3);
+        let initializer = Pubkey::new_unique();
+
+        let gravity_contract_info = consuls
+            .iter()
+            .fold(GravityContractBuilder::new(), |builder, consul| {
+                builder.add_consul(*consul)
+            })
+            .initializer(initializer)
+            .bft(2)
+            .build()
+            .unwrap();
+        assert_eq!(gravity_contract_info.initializer_pubkey, initializer);
+        assert_eq!(gravity_contract_info.consuls, consuls);
+        assert_eq!(gravity_contract_info.bft, 2);
+
+        assert!(matches!(
+            GravityContractBuilder::new().bft(1).build(),
+            Err(GravityError::TooFewConsuls)
+        ));
+        assert!(matches!(
+            GravityContractBuilder::new()
+                .add_consul(consuls[0])
+                .bft(2)
+                .build(),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(matches!(
+            GravityContractBuilder::new()
+                .add_consul(consuls[0])
+                .add_consul(consuls[0])
+                .bft(1)
+                .build(),
+            Err(GravityError::DuplicateConsul)
+        ));
+    }
+
+    #[test]
+    fn test_is_owner() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        assert!(gravity_contract_info.is_owner(&gravity_contract_info.initializer_pubkey));
+        assert!(!gravity_contract_info.is_owner(&gravity_contract_info.consuls[0]));
+        assert!(!gravity_contract_info.is_owner(&Pubkey::default()));
+    }
+
+    #[test]
+    fn test_initializer_in() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let initializer = gravity_contract_info.initializer_pubkey;
+
+        assert!(gravity_contract_info.initializer_in(&[Pubkey::new_unique(), initializer]));
+        assert!(!gravity_contract_info.initializer_in(&[Pubkey::new_unique()]));
+        assert!(!gravity_contract_info.initializer_in(&[]));
+    }
+
+    #[test]
+    fn test_conformance_vectors() {
+        let vectors = GravityContract::conformance_vectors();
+        assert_eq!(vectors, GravityContract::conformance_vectors());
+
+        for (contract, packed) in vectors.iter() {
+            assert_eq!(packed.len(), GravityContract::LEN);
+            assert_eq!(&GravityContract::unpack(packed).unwrap(), contract);
+        }
+
+        // pins the bytes themselves, any layout change shows up here
+        let digest = hashv(
+            &vectors
+                .iter()
+                .map(|(_, packed)| &packed[..])
+                .collect::<Vec<&[u8]>>(),
+        );
+        assert_eq!(
+            digest.to_string(),
+            "6A6fXaoYHivnkmD3Dqn4Bvy172L9ZWqUbXZGh3GP8zND"
+        );
+    }
+
+    #[test]
+    fn test_platform_fingerprint() {
+        let (contract, packed) = GravityContract::conformance_vectors().swap_remove(1);
+
+        assert_eq!(
+            contract.platform_fingerprint(),
+            hash::hash(&packed).to_bytes()
+        );
+        assert_eq!(
+            hex::encode(contract.platform_fingerprint()),
+            "b5f5e12876e048104d77e03d638c3c75745211705b0d68148511d7ddcc516dc3"
+        );
+    }
+
+    /// Conformance vector 1 as the layout before the discriminator packed it into a zeroed
+    /// account, without the fields that came later.
+    fn golden_v1() -> Vec<u8> {
+        hex::decode(concat!(
+            "0101010101010101010101010101010101010101010101010101010101010101",
+            "0203000000020202020202020202020202020202020202020202020202020202",
+            "0202020202040404040404040404040404040404040404040404040404040404",
+            "0404040404050505050505050505050505050505050505050505050505050505",
+            "0505050505070000000000000003030303030303030303030303030303030303",
+            "0303030303030303030303030300000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000",
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let (contract, packed) = GravityContract::conformance_vectors().swap_remove(1);
+        assert_eq!(contract.state_hash(), Ok(hash::hash(&packed)));
+
+        // a contract the layout can't hold has no state to record, and fails instead of
+        // aborting the instruction
+        let oversized = GravityContract {
+            consuls: sorted_consuls(GravityContract::MAX_CONSULS + 1),
+            ..contract
+        };
+        assert_eq!(
+            oversized.state_hash(),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+        assert_eq!(
+            EventRecord::new(ContractEvent::BftChanged { bft: 1 }, &oversized),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_layout_compatible() {
+        let golden_v1 = golden_v1();
+        // and in the current layout, once a round update recorded slot 120
+        let golden_v2 = hex::decode(concat!(
+            "2dc4b9d6167a8f31010101010101010101010101010101010101010101010101",
+            "0101010101010101020300000002020202020202020202020202020202020202",
+            "0202020202020202020202020204040404040404040404040404040404040404",
+            "0404040404040404040404040405050505050505050505050505050505050505",
+            "0505050505050505050505050507000000000000000303030303030303030303",
+            "0303030303030303030303030303030303030303030100000000000000010000",
+            "0000000000780000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "00000000000000000000000000000000000000000000000000000000000000",
+        ))
+        .unwrap();
+
+        let (contract, packed) = GravityContract::conformance_vectors().swap_remove(1);
+        assert_eq!(packed, golden_v2);
+        assert_eq!(contract.assert_layout_compatible(&golden_v2), Ok(()));
+
+        let v1_contract = GravityContract {
+            consul_epoch: 0,
+            chain_id: 0,
+            last_update_slot: 0,
+            ..contract.clone()
+        };
+        assert_eq!(v1_contract.assert_layout_compatible(&golden_v1), Ok(()));
+        assert_eq!(
+            contract.assert_layout_compatible(&golden_v1),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            GravityContract::unpack_from_slice(&golden_v1),
+            Err(GravityError::WrongAccountType.into())
+        );
+
+        // a later layout putting a field in the reserved region isn't read as this one
+        let mut dirty = golden_v2;
+        dirty[GravityContract::LEN - 1] = 1;
+        assert_eq!(
+            contract.assert_layout_compatible(&dirty),
+            Err(GravityError::NonZeroReserved.into())
+        );
+    }
+
+    #[test]
+    fn test_initializer_policy() {
+        let separate = build_contract(sorted_consuls(3));
+        assert!(!separate.initializer_is_consul());
+        assert!(separate.assert_initializer_policy(false).is_ok());
+        assert!(separate.assert_initializer_policy(true).is_ok());
+
+        let overlapping = GravityContract {
+            initializer_pubkey: separate.consuls[1],
+            ..separate
+        };
+        assert!(overlapping.initializer_is_consul());
+        assert!(matches!(
+            overlapping.assert_initializer_policy(false),
+            Err(GravityError::InitializerConsulOverlap)
+        ));
+        assert!(overlapping.assert_initializer_policy(true).is_ok());
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let initializer = gravity_contract_info.initializer_pubkey;
+        let new_owner = Pubkey::new_unique();
+
+        assert_eq!(
+            gravity_contract_info.transfer_ownership(&new_owner, new_owner),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            gravity_contract_info.transfer_ownership(&initializer, Pubkey::default()),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(gravity_contract_info.initializer_pubkey, initializer);
+
+        gravity_contract_info
+            .transfer_ownership(&initializer, new_owner)
+            .unwrap();
+        assert_eq!(gravity_contract_info.initializer_pubkey, new_owner);
+        assert!(gravity_contract_info
+            .transfer_ownership(&initializer, initializer)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rotation_overlap() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let replacement = sorted_consuls(3);
+        assert_eq!(gravity_contract_info.rotation_overlap(&replacement), 0);
+        assert!(!gravity_contract_info.can_rotate_safely(&replacement, 1));
+
+        let mut partial = gravity_contract_info.consuls[..2].to_vec();
+        partial.push(Pubkey::new_unique());
+        assert_eq!(gravity_contract_info.rotation_overlap(&partial), 2);
+        assert!(gravity_contract_info.can_rotate_safely(&partial, 2));
+        assert!(!gravity_contract_info.can_rotate_safely(&partial, 3));
+    }
+
+    #[test]
+    fn test_rotation_preserves_quorum() {
+        let gravity_contract_info = GravityContract {
+            bft: 3,
+            ..build_contract(sorted_consuls(5))
+        };
+
+        let mut keeps_three = gravity_contract_info.consuls[..3].to_vec();
+        keeps_three.extend(sorted_consuls(2));
+        assert!(gravity_contract_info.rotation_preserves_quorum(&keeps_three));
+
+        let mut keeps_two = gravity_contract_info.consuls[..2].to_vec();
+        keeps_two.extend(sorted_consuls(3));
+        assert!(!gravity_contract_info.rotation_preserves_quorum(&keeps_two));
+    }
+
+    #[test]
+    fn test_non_signers() {
+        let consuls = sorted_consuls(4);
+        let gravity_contract_info = build_contract(consuls.clone());
+
+        assert_eq!(
+            gravity_contract_info.non_signers(&[2, 0]),
+            vec![consuls[1], consuls[3]]
+        );
+        assert_eq!(gravity_contract_info.non_signers(&[]), consuls);
+        assert!(gravity_contract_info.non_signers(&[3, 2, 1, 0]).is_empty());
+    }
+
+    #[test]
+    fn test_common_consuls() {
+        let consuls = sorted_consuls(4);
+
+        let first = build_contract(consuls.clone());
+        let mut reversed = consuls.clone();
+        reversed.reverse();
+        let second = build_contract(reversed);
+        assert_eq!(common_consuls(&[&first, &second]), consuls);
+
+        let third = build_contract(vec![consuls[3], Pubkey::new_unique(), consuls[1]]);
+        assert_eq!(
+            common_consuls(&[&first, &second, &third]),
+            vec![consuls[1], consuls[3]]
+        );
+
+        assert!(common_consuls(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_consul_membership_proof() {
+        let consuls = sorted_consuls(5);
+        let gravity_contract_info = build_contract(consuls.clone());
+        let root = gravity_contract_info.consuls_merkle_root();
+
+        for (i, consul) in consuls.iter().enumerate() {
+            let (index, proof) = gravity_contract_info
+                .consul_membership_proof(consul)
+                .unwrap();
+            assert_eq!(index, i);
+            assert!(verify_consul_membership(root, consul, index, &proof));
+            assert!(!verify_consul_membership(root, consul, index + 1, &proof));
+        }
+
+        let stranger = Pubkey::new_unique();
+        assert_eq!(
+            gravity_contract_info.consul_membership_proof(&stranger),
+            None
+        );
+        let (index, proof) = gravity_contract_info
+            .consul_membership_proof(&consuls[0])
+            .unwrap();
+        assert!(!verify_consul_membership(root, &stranger, index, &proof));
+    }
+
+    #[test]
+    fn test_consuls_bytes() {
+        let mut consuls = sorted_consuls(4);
+        let mut gravity_contract_info = build_contract(consuls.clone());
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        let start = PackedContract::CONSULS_OFFSET + 4;
+        let region = &packed[start..start + 32 * consuls.len()];
+        assert_eq!(gravity_contract_info.consuls_bytes(), region);
+
+        consuls.reverse();
+        gravity_contract_info.consuls = consuls;
+        assert_eq!(gravity_contract_info.consuls_bytes(), region);
+    }
+
+    #[test]
+    fn test_consuls_abi_bytes32() {
+        let gravity_contract_info = build_contract(vec![
+            Pubkey::new(&[0x11; 32]),
+            Pubkey::new(&[0x22; 32]),
+            Pubkey::new(&[0x33; 32]),
+        ]);
+
+        let expected = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "1111111111111111111111111111111111111111111111111111111111111111",
+            "2222222222222222222222222222222222222222222222222222222222222222",
+            "3333333333333333333333333333333333333333333333333333333333333333",
+        ))
+        .unwrap();
+
+        assert_eq!(gravity_contract_info.consuls_abi_bytes32(), expected);
+    }
+
+    #[test]
+    fn test_matches_evm_commitment() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let encoded = gravity_contract_info.consuls_abi_bytes32();
+
+        let keccak_hash = keccak::hash(&encoded).to_bytes();
+        assert!(gravity_contract_info.matches_evm_commitment(keccak_hash, HashAlgo::Keccak256));
+        assert!(!gravity_contract_info.matches_evm_commitment(keccak_hash, HashAlgo::Sha256));
+
+        let sha256_hash = hash::hash(&encoded).to_bytes();
+        assert!(gravity_contract_info.matches_evm_commitment(sha256_hash, HashAlgo::Sha256));
+
+        let other = build_contract(sorted_consuls(3));
+        assert!(!other.matches_evm_commitment(keccak_hash, HashAlgo::Keccak256));
+    }
+
+    #[test]
+    fn test_commitment_matches() {
+        let consuls = sorted_consuls(4);
+        let mut shuffled = consuls.clone();
+        shuffled.swap(0, 3);
+        shuffled.swap(1, 2);
+
+        let commitment =
+            keccak::hash(&build_contract(consuls.clone()).consuls_abi_bytes32()).to_bytes();
+
+        assert!(ConsulSet(&consuls).commitment_matches(commitment, HashAlgo::Keccak256));
+        assert!(ConsulSet(&shuffled).commitment_matches(commitment, HashAlgo::Keccak256));
+        assert!(!ConsulSet(&shuffled).commitment_matches(commitment, HashAlgo::Sha256));
+
+        let mut other = consuls.clone();
+        other[2] = Pubkey::new_unique();
+        assert!(!ConsulSet(&other).commitment_matches(commitment, HashAlgo::Keccak256));
+        assert!(!ConsulSet(&consuls[..3]).commitment_matches(commitment, HashAlgo::Keccak256));
+    }
+
+    #[test]
+    fn test_threshold_params() {
+        let consuls = sorted_consuls(5);
+        assert_eq!(ConsulSet(&consuls).threshold_params(3).unwrap(), (3, 5));
+        assert_eq!(ConsulSet(&consuls).threshold_params(5).unwrap(), (5, 5));
+
+        assert!(matches!(
+            ConsulSet(&consuls).threshold_params(6),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(matches!(
+            ConsulSet(&consuls).threshold_params(0),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(matches!(
+            ConsulSet(&[]).threshold_params(1),
+            Err(GravityError::InvalidBFTCount)
+        ));
+        assert!(matches!(
+            ConsulSet(&sorted_consuls(256)).threshold_params(3),
+            Err(GravityError::TooManyConsuls)
+        ));
+    }
+
+    #[test]
+    fn test_decentralization_score() {
+        let consuls = sorted_consuls(4);
+        let consul_set = ConsulSet(&consuls);
+        let clustered = |groups: &[u32]| -> HashMap<Pubkey, u32> {
+            consuls
+                .iter()
+                .copied()
+                .zip(groups.iter().copied())
+                .collect()
+        };
+
+        let distributed = consul_set.decentralization_score(&clustered(&[0, 1, 2, 3]));
+        assert!((distributed - 1.0).abs() < 1e-6);
+        assert_eq!(
+            consul_set.decentralization_score(&HashMap::new()),
+            distributed
+        );
+
+        assert_eq!(
+            consul_set.decentralization_score(&clustered(&[7, 7, 7, 7])),
+            0.0
+        );
+
+        let paired = consul_set.decentralization_score(&clustered(&[0, 0, 1, 1]));
+        assert!((paired - 0.5).abs() < 1e-6);
+        let skewed = consul_set.decentralization_score(&clustered(&[0, 0, 0, 1]));
+        assert!(0.0 < skewed && skewed < paired);
+
+        assert_eq!(
+            ConsulSet(&consuls[..1]).decentralization_score(&HashMap::new()),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_all_approved() {
+        let consuls = sorted_consuls(4);
+        let consul_set = ConsulSet(&consuls);
+        let registry = &consuls[..];
+
+        assert_eq!(
+            consul_set.all_approved(|consul| registry.contains(consul)),
+            Ok(())
+        );
+
+        let registry = [consuls[0], consuls[1], consuls[3], Pubkey::new_unique()];
+        assert_eq!(
+            consul_set.all_approved(|consul| registry.contains(consul)),
+            Err(vec![consuls[2]])
+        );
+    }
+
+    #[test]
+    fn test_validate_liveness() {
+        let consuls = sorted_consuls(5);
+        let consul_set = ConsulSet(&consuls);
+        let online = &consuls[..3];
+
+        assert!(consul_set
+            .validate_liveness(3, |consul| online.contains(consul))
+            .is_ok());
+        assert!(matches!(
+            consul_set.validate_liveness(4, |consul| online.contains(consul)),
+            Err(GravityError::QuorumUnreachable)
+        ));
+        assert!(matches!(
+            consul_set.validate_liveness(1, |_| false),
+            Err(GravityError::QuorumUnreachable)
+        ));
+
+        // every consul is asked, even once the quorum is already online
+        let asked = std::cell::Cell::new(0);
+        consul_set
+            .validate_liveness(1, |_| {
+                asked.set(asked.get() + 1);
+                true
+            })
+            .unwrap();
+        assert_eq!(asked.get(), consuls.len());
+    }
+
+    #[test]
+    fn test_check_accounts() {
+        let funded = Keypair::new().pubkey();
+        let unfunded = Keypair::new().pubkey();
+        let program = Keypair::new().pubkey();
+        let (pda, _) = Pubkey::find_program_address(&[b"consul"], &Pubkey::new_unique());
+        let consuls = [funded, unfunded, program, pda];
+
+        let probe = |consul: &Pubkey| {
+            let funded_account = AccountProfile {
+                owner: system_program::id(),
+                lamports: Rent::default().minimum_balance(0),
+                ..AccountProfile::default()
+            };
+
+            if *consul == unfunded {
+                AccountProfile {
+                    lamports: 0,
+                    ..funded_account
+                }
+            } else if *consul == program {
+                AccountProfile {
+                    owner: Pubkey::new_unique(),
+                    executable: true,
+                    ..funded_account
+                }
+            } else {
+                funded_account
+            }
+        };
+
+        assert_eq!(
+            ConsulSet(&consuls).check_accounts(probe),
+            vec![
+                (unfunded, ConsulAccountIssue::NotRentExempt),
+                (program, ConsulAccountIssue::Executable),
+                (pda, ConsulAccountIssue::OffCurve),
+            ]
+        );
+        assert!(ConsulSet(&consuls[..1]).check_accounts(probe).is_empty());
+    }
+
+    #[test]
+    fn test_consul_index() {
+        let gravity_contract_info = build_contract(sorted_consuls(4));
+        let consul_index = gravity_contract_info.consul_index().unwrap();
+
+        for (i, consul) in gravity_contract_info.consuls.iter().enumerate() {
+            assert_eq!(consul_index.by_key(consul), Some(i as u8));
+            assert_eq!(consul_index.by_index(i as u8), Some(consul));
+        }
+
+        assert_eq!(consul_index.by_key(&Pubkey::new_unique()), None);
+        assert_eq!(consul_index.by_index(4), None);
+
+        // positions past u8::MAX can't be addressed by a bundle
+        let consuls: Vec<Pubkey> = (0..=u8::MAX as usize + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        assert!(matches!(
+            ConsulIndex::new(&consuls),
+            Err(GravityError::TooManyConsuls)
+        ));
+        assert!(ConsulIndex::new(&consuls[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_reward_destination() {
+        let program_id = Pubkey::new_unique();
+        let gravity_contract_info = build_contract(sorted_consuls(4));
+
+        let destinations: Vec<(Pubkey, u8)> = (0..4)
+            .map(|i| {
+                gravity_contract_info
+                    .reward_destination(i, &program_id)
+                    .unwrap()
+            })
+            .collect();
+        for (i, destination) in destinations.iter().enumerate() {
+            assert_eq!(
+                gravity_contract_info.reward_destination(i, &program_id),
+                Some(*destination)
+            );
+            assert!(destinations[i + 1..]
+                .iter()
+                .all(|other| other.0 != destination.0));
+        }
+
+        // follows the consul, not its slot in the set
+        let mut reordered = gravity_contract_info.clone();
+        reordered.consuls.reverse();
+        assert_eq!(
+            reordered.reward_destination(3, &program_id),
+            Some(destinations[0])
+        );
+
+        assert_ne!(
+            gravity_contract_info.reward_destination(0, &Pubkey::new_unique()),
+            Some(destinations[0])
+        );
+        assert_eq!(
+            gravity_contract_info.reward_destination(4, &program_id),
+            None
+        );
+    }
+
+    #[test]
+    fn test_non_zero_padding() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed).unwrap(),
+            gravity_contract_info
+        );
+
+        packed[GravityContract::LEN - 1] = 1;
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed),
+            Err(GravityError::NonZeroReserved.into())
+        );
+
+        // repacking a smaller consul set clears the stale tail
+        build_contract(sorted_consuls(1)).pack_into_slice(&mut packed);
+        assert!(GravityContract::unpack_from_slice(&packed).is_ok());
+    }
+
+    #[test]
+    fn test_discriminator() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(packed[..DISCRIMINATOR_LEN], GravityContract::DISCRIMINATOR);
+
+        // the same bytes behind a Nebula prefix
+        packed[..DISCRIMINATOR_LEN].copy_from_slice(&NEBULA_CONTRACT);
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed),
+            Err(GravityError::WrongAccountType.into())
+        );
+        assert!(!GravityContract::is_canonical(&packed));
+    }
+
+    #[test]
+    fn test_unpack_pre_discriminator() {
+        // the fields that layout lacks are at their defaults already
+        let gravity_contract_info = build_contract(sorted_consuls(GravityContract::MAX_CONSULS));
+
+        let packed = gravity_contract_info.pack_pre_discriminator().unwrap();
+        assert_eq!(
+            GravityContract::unpack_pre_discriminator(&packed),
+            Ok(gravity_contract_info.clone())
+        );
+        // that layout left whatever followed the fields, and accounts only ever grew
+        let mut grown = packed.clone();
+        grown.push(0xff);
+        assert_eq!(
+            GravityContract::unpack_pre_discriminator(&grown),
+            Ok(gravity_contract_info)
+        );
+
+        assert_eq!(
+            GravityContract::unpack_pre_discriminator(&packed[..packed.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        // the current layout refuses it rather than misreading it
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed),
+            Err(GravityError::WrongAccountType.into())
+        );
+        assert_eq!(
+            GravityContract::unpack_pre_discriminator(
+                &build_contract(vec![]).pack_pre_discriminator().unwrap()
+            ),
+            Err(GravityError::TooFewConsuls.into())
+        );
+    }
+
+    #[test]
+    fn test_pack_into_oversized_slice() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        let mut oversized = vec![0xff; GravityContract::LEN + 16];
+        gravity_contract_info.pack_into_slice(&mut oversized);
+        assert_eq!(&oversized[..GravityContract::LEN], &packed[..]);
+        assert!(is_contract_empty(&oversized[GravityContract::LEN..]));
+        assert_eq!(
+            GravityContract::unpack_from_slice(&oversized),
+            Ok(gravity_contract_info.clone())
+        );
+        // unpack reads the bytes past `LEN` the way pack leaves them
+        oversized[GravityContract::LEN] = 1;
+        assert_eq!(
+            GravityContract::unpack_from_slice(&oversized),
+            Err(GravityError::NonZeroReserved.into())
+        );
+
+        // too short a slice is refused rather than written partially
+        let mut undersized = vec![0xff; GravityContract::LEGACY_LEN - 1];
+        assert_eq!(
+            gravity_contract_info.try_pack_into_slice(&mut undersized),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+        assert!(undersized.iter().all(|byte| *byte == 0xff));
+        // `Pack`, with no error to hand back, fails loudly instead of leaving it unwritten
+        let pack_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            gravity_contract_info.pack_into_slice(&mut undersized.clone())
+        }));
+        assert!(pack_result.is_err());
+        assert!(!GravityContract::is_canonical(&undersized));
+        assert!(!GravityContract::is_canonical(&[]));
+
+        // while a shorter one the contract still fits packs and decodes alike
+        let mut short = vec![0xff; GravityContract::LEGACY_LEN];
+        gravity_contract_info
+            .try_pack_into_slice(&mut short)
+            .unwrap();
+        assert_eq!(
+            short[..],
+            gravity_contract_info.pack_to_array().unwrap()[..]
+        );
+        assert_eq!(
+            GravityContract::unpack_from_slice(&short),
+            Ok(gravity_contract_info)
+        );
+        assert!(GravityContract::is_canonical(&short));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_unpack_metrics() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        use metrics::{GaugeValue, Key, Recorder, Unit};
+
+        static UNPACKS: AtomicU64 = AtomicU64::new(0);
+        static ERRORS: AtomicU64 = AtomicU64::new(0);
+
+        struct CountingRecorder;
+
+        impl Recorder for CountingRecorder {
+            fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+            fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+            fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+            fn increment_counter(&self, key: &Key, value: u64) {
+                match key.name() {
+                    "gravity_unpack_total" => UNPACKS.fetch_add(value, Ordering::SeqCst),
+                    "gravity_unpack_errors_total" => ERRORS.fetch_add(value, Ordering::SeqCst),
+                    _ => 0,
+                };
+            }
+            fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+            fn record_histogram(&self, _: &Key, _: f64) {}
+        }
+
+        metrics::set_boxed_recorder(Box::new(CountingRecorder)).unwrap();
+
+        let mut packed = vec![0; GravityContract::LEN];
+        build_contract(sorted_consuls(3)).pack_into_slice(&mut packed);
+
+        // other tests unpack concurrently, so the counters are only checked to move
+        let unpacks = UNPACKS.load(Ordering::SeqCst);
+        GravityContract::unpack_from_slice(&packed).unwrap();
+        assert!(UNPACKS.load(Ordering::SeqCst) > unpacks);
+
+        let (unpacks, errors) = (
+            UNPACKS.load(Ordering::SeqCst),
+            ERRORS.load(Ordering::SeqCst),
+        );
+        GravityContract::unpack_from_slice(&packed[1..]).unwrap_err();
+        assert!(UNPACKS.load(Ordering::SeqCst) > unpacks);
+        assert!(ERRORS.load(Ordering::SeqCst) > errors);
+    }
+
+    #[test]
+    fn test_layout_schema() {
+        let schema = GravityContract::layout_schema();
+
+        let mut offset = 0;
+        for field in schema.iter() {
+            assert_eq!(field.offset, offset);
+            offset += field.len;
+        }
+        assert_eq!(offset, GravityContract::LEN);
+
+        let mut gravity_contract_info =
+            build_contract(sorted_consuls(GravityContract::MAX_CONSULS));
+        gravity_contract_info.chain_id = 42;
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+
+        let chain_id = schema
+            .iter()
+            .find(|field| field.name == "chain_id")
+            .unwrap();
+        assert_eq!(chain_id.field_type, FieldType::U64);
+        assert_eq!(
+            packed[chain_id.offset..chain_id.offset + chain_id.len],
+            42u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_max_encoded_len() {
+        let gravity_contract_info = build_contract(sorted_consuls(GravityContract::MAX_CONSULS));
+
+        assert_eq!(
+            gravity_contract_info.try_to_vec().unwrap().len(),
+            GravityContract::MAX_ENCODED_LEN
+        );
+    }
+
+    #[test]
+    fn test_downgrade_to() {
+        let (contract, packed) = GravityContract::conformance_vectors().swap_remove(1);
+        let unchained_contract = GravityContract {
+            chain_id: 0,
+            ..contract.clone()
+        };
+        let mut unchained_packed = vec![];
+        unchained_contract.pack_into_vec(&mut unchained_packed);
+
+        // the epoch and the slot are dropped, leaving the very bytes the baseline packed
+        let v1_packed = GravityContract::downgrade_to(&unchained_packed, 1).unwrap();
+        assert_eq!(v1_packed, golden_v1());
+        assert_eq!(
+            GravityContract::unpack_pre_discriminator(&v1_packed),
+            Ok(GravityContract {
+                consul_epoch: 0,
+                last_update_slot: 0,
+                ..unchained_contract.clone()
+            })
+        );
+
+        // the chain id the signatures bind to can't be dropped, nor can the key types or the
+        // shard count
+        assert_eq!(
+            GravityContract::downgrade_to(&packed, 1),
+            Err(GravityError::DowngradeLosesData.into())
+        );
+        for lossy in [
+            GravityContract {
+                secp256k1_consuls: 0b1,
+                ..unchained_contract.clone()
+            },
+            GravityContract {
+                consul_shards: 1,
+                ..unchained_contract
+            },
+        ]
+        .iter()
+        {
+            let mut lossy_packed = vec![];
+            lossy.pack_into_vec(&mut lossy_packed);
+            assert_eq!(
+                GravityContract::downgrade_to(&lossy_packed, 1),
+                Err(GravityError::DowngradeLosesData.into())
+            );
+        }
+
+        // only the current layout goes down, and only to version 1
+        assert_eq!(
+            GravityContract::downgrade_to(&v1_packed, 1),
+            Err(GravityError::WrongAccountType.into())
+        );
+        for version in [0, GravityContract::LAYOUT_VERSION].iter() {
+            assert_eq!(
+                GravityContract::downgrade_to(&unchained_packed, *version),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_size_delta() {
+        // the discriminator, every field after `multisig_account` and the reserved region
+        // all came in at once
+        assert_eq!(GravityContract::version_size_delta(1, 2, 3), Some(50));
+        assert_eq!(GravityContract::version_size_delta(1, 2, 6), Some(50));
+        assert_eq!(GravityContract::version_size_delta(2, 1, 3), Some(-50));
+        assert_eq!(GravityContract::version_size_delta(2, 2, 3), Some(0));
+        assert_eq!(GravityContract::version_size_delta(2, 3, 3), None);
+        assert_eq!(GravityContract::version_size_delta(0, 2, 3), None);
+
+        // version 1 packed the bare fields, in accounts of a fixed size
+        assert_eq!(
+            GravityContract::versioned_len(1, GravityContract::MAX_CONSULS),
+            Some(GravityContract::PRE_DISCRIMINATOR_LEN - 30)
+        );
+        // the current layout sized to its contents is `LEN` short of the unused consul slots
+        assert_eq!(
+            GravityContract::versioned_len(GravityContract::LAYOUT_VERSION, 3),
+            Some(GravityContract::LEN - (GravityContract::MAX_CONSULS - 3) * 32)
+        );
+    }
+
+    #[test]
+    fn test_size_delta() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        assert_eq!(gravity_contract_info.size_delta(3), 0);
+        assert_eq!(gravity_contract_info.size_delta(5), 64);
+        assert_eq!(gravity_contract_info.size_delta(1), -64);
+
+        let encoded_len = gravity_contract_info.try_to_vec().unwrap().len() as i64;
+        let grown = build_contract(sorted_consuls(5));
+        assert_eq!(
+            grown.try_to_vec().unwrap().len() as i64,
+            encoded_len + gravity_contract_info.size_delta(5)
+        );
+    }
+
+    #[test]
+    fn test_rotation_rent_delta() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let rent = Rent::default();
+
+        // the account keeps its `LEN` for any set within the limits
+        assert_eq!(gravity_contract_info.rotation_rent_delta(3, &rent), 0);
+        assert_eq!(gravity_contract_info.rotation_rent_delta(1, &rent), 0);
+        assert_eq!(
+            gravity_contract_info.rotation_rent_delta(GravityContract::MAX_CONSULS as u8, &rent),
+            0
+        );
+
+        // `LEN` has no room past `MAX_CONSULS`, two more consuls take two more keys
+        assert_eq!(
+            gravity_contract_info
+                .rotation_rent_delta(GravityContract::MAX_CONSULS as u8 + 2, &rent),
+            rent.minimum_balance(GravityContract::LEN + 64) as i64
+                - rent.minimum_balance(GravityContract::LEN) as i64
+        );
+    }
+
+    #[test]
+    fn test_wasted_bytes() {
+        let len = GravityContract::LEN;
+
+        assert_eq!(GravityContract::wasted_bytes(len, 3), 0);
+        assert_eq!(GravityContract::wasted_bytes(len + 100, 3), 100);
+        assert_eq!(GravityContract::wasted_bytes(10, 1), 0);
+
+        // fewer consuls leave padding the processors still read
+        assert_eq!(
+            GravityContract::wasted_bytes(len, 1),
+            GravityContract::wasted_bytes(len, GravityContract::MAX_CONSULS as u8)
+        );
+    }
+
+    #[test]
+    fn test_rent_shortfall() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let rent = Rent::default();
+        let exempt = rent.minimum_balance(GravityContract::LEN);
+
+        assert_eq!(
+            gravity_contract_info.rent_shortfall(exempt, GravityContract::LEN, &rent),
+            0
+        );
+        assert_eq!(
+            gravity_contract_info.rent_shortfall(exempt + 1, GravityContract::LEN, &rent),
+            0
+        );
+        assert_eq!(
+            gravity_contract_info.rent_shortfall(exempt - 10, GravityContract::LEN, &rent),
+            10
+        );
+        assert_eq!(
+            gravity_contract_info.rent_shortfall(0, GravityContract::LEN, &rent),
+            exempt
+        );
+    }
+
+    #[test]
+    fn test_reserved_region() {
+        // six consuls is the most the layout holds next to the reserved region
+        let gravity_contract_info = build_contract(sorted_consuls(6));
+        let encoded_len = gravity_contract_info.try_to_vec().unwrap().len();
+        assert!(
+            DISCRIMINATOR_LEN + encoded_len + GravityContract::RESERVED_LEN <= GravityContract::LEN
+        );
+
+        let mut packed = vec![0xff; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert!(is_contract_empty(
+            &packed[GravityContract::LEN - GravityContract::RESERVED_LEN..]
+        ));
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed).unwrap(),
+            gravity_contract_info
+        );
+
+        packed[GravityContract::LEN - GravityContract::RESERVED_LEN] = 1;
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed),
+            Err(GravityError::NonZeroReserved.into())
+        );
+    }
+
+    #[test]
+    fn test_security_assessment() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(5));
+
+        gravity_contract_info.bft = 2;
+        assert_eq!(gravity_contract_info.security_assessment(), SecurityLevel::Weak);
+
+        gravity_contract_info.bft = 3;
+        assert_eq!(gravity_contract_info.security_assessment(), SecurityLevel::Adequate);
+
+        gravity_contract_info.bft = 4;
+        assert_eq!(gravity_contract_info.security_assessment(), SecurityLevel::Strong);
+    }
+
+    #[test]
+    fn test_is_bft_sound() {
+        for (consuls_count, faults, sound_bft) in [(4, 1, 3), (7, 2, 5), (10, 3, 7)] {
+            let mut gravity_contract_info = build_contract(sorted_consuls(consuls_count));
+            assert_eq!(gravity_contract_info.tolerable_faults(), faults);
+
+            gravity_contract_info.bft = sound_bft;
+            assert!(gravity_contract_info.is_bft_sound());
+            assert_eq!(
+                GravityContract::recommended_bft(consuls_count, SecurityLevel::Strong),
+                sound_bft
+            );
+
+            gravity_contract_info.bft = sound_bft - 1;
+            assert!(!gravity_contract_info.is_bft_sound());
+
+            gravity_contract_info.bft = consuls_count as u8 + 1;
+            assert!(!gravity_contract_info.is_bft_sound());
+        }
+
+        assert_eq!(build_contract(vec![]).tolerable_faults(), 0);
+    }
+
+    #[test]
+    fn test_emergency_threshold() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(5));
+
+        gravity_contract_info.bft = 3;
+        assert_eq!(gravity_contract_info.emergency_threshold(5), 4);
+        gravity_contract_info.bft = 4;
+        assert_eq!(gravity_contract_info.emergency_threshold(5), 5);
+        // a unanimous quorum has nothing stricter to fall back on
+        gravity_contract_info.bft = 5;
+        assert_eq!(gravity_contract_info.emergency_threshold(5), 5);
+
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        gravity_contract_info.bft = 2;
+        assert_eq!(gravity_contract_info.emergency_threshold(3), 3);
+        // the multisig, not the consul list, says how many signers there are to count
+        assert_eq!(gravity_contract_info.emergency_threshold(5), 4);
+    }
+
+    #[test]
+    fn test_audit_report() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(5));
+
+        gravity_contract_info.bft = 2;
+        let report = gravity_contract_info.audit_report();
+        assert!(report.contains("consuls: 5\n"));
+        assert!(report.contains("security level: Weak\n"));
+        assert!(report.contains("warning: weak quorum: 2 of 5 consuls"));
+
+        gravity_contract_info.bft = 4;
+        assert!(gravity_contract_info
+            .audit_report()
+            .contains("warnings: none\n"));
+
+        gravity_contract_info.last_round = u64::MAX - 1;
+        assert!(gravity_contract_info
+            .audit_report()
+            .contains("is close to the last one possible"));
+
+        gravity_contract_info.bft = 6;
+        assert!(gravity_contract_info
+            .audit_report()
+            .contains("warning: invalid contract:"));
+    }
+
+    #[test]
+    fn test_bft_regressed() {
+        let with_bft = |consul_count, bft| GravityContract {
+            bft,
+            ..build_contract(sorted_consuls(consul_count))
+        };
+
+        assert!(bft_regressed(&with_bft(5, 4), &with_bft(5, 2)));
+        assert!(!bft_regressed(&with_bft(5, 3), &with_bft(7, 4)));
+        assert!(!bft_regressed(&with_bft(5, 2), &with_bft(5, 4)));
+        assert!(!bft_regressed(&with_bft(5, 4), &with_bft(5, 4)));
+    }
+
+    #[test]
+    fn test_recommended_bft() {
+        for (consul_count, expected) in [(5, [2, 3, 4]), (7, [3, 4, 5])].iter() {
+            let mut gravity_contract_info = build_contract(sorted_consuls(*consul_count));
+
+            for (level, bft) in [
+                SecurityLevel::Weak,
+                SecurityLevel::Adequate,
+                SecurityLevel::Strong,
+            ]
+            .iter()
+            .zip(expected.iter())
+            {
+                gravity_contract_info.bft = GravityContract::recommended_bft(*consul_count, *level);
+                assert_eq!(gravity_contract_info.bft, *bft);
+                assert_eq!(gravity_contract_info.security_assessment(), *level);
+            }
+        }
+
+        assert_eq!(GravityContract::recommended_bft(1, SecurityLevel::Weak), 1);
+        assert_eq!(
+            GravityContract::recommended_bft(2, SecurityLevel::Strong),
+            2
+        );
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let gravity_contract_info = GravityContract {
+            last_update_slot: 1_000,
+            ..build_contract(sorted_consuls(3))
+        };
+
+        assert!(!gravity_contract_info.is_stale(1_000, 100));
+        assert!(!gravity_contract_info.is_stale(1_100, 100));
+        assert!(gravity_contract_info.is_stale(1_101, 100));
+        // a clock behind the last update is no sign of staleness
+        assert!(!gravity_contract_info.is_stale(900, 0));
+
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(
+            GravityContract::unpack_from_slice(&packed)
+                .unwrap()
+                .last_update_slot,
+            1_000
+        );
+    }
+
+    #[test]
+    fn test_signing_deadline() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        // the round spans slots 1_000 to 1_149
+        assert_eq!(gravity_contract_info.signing_deadline(1_000, 150), 1_150);
+        assert_eq!(gravity_contract_info.signing_deadline(1_000, 0), 1_000);
+        assert_eq!(
+            gravity_contract_info.signing_deadline(u64::MAX - 10, 150),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_round_lag() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        assert_eq!(gravity_contract_info.round_lag(7), 0);
+        assert_eq!(gravity_contract_info.round_lag(10), 3);
+        assert_eq!(gravity_contract_info.round_lag(2), 0);
+    }
+
+    #[test]
+    fn test_update_round() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+
+        gravity_contract_info.update_round(10).unwrap();
+        assert_eq!(gravity_contract_info.last_round, 10);
+        assert!(matches!(
+            gravity_contract_info.update_round(10),
+            Err(GravityError::InputRoundMismatch)
+        ));
+
+        gravity_contract_info.update_round(u64::MAX).unwrap();
+        assert!(matches!(
+            gravity_contract_info.update_round(u64::MAX),
+            Err(GravityError::RoundExhausted)
+        ));
+        assert_eq!(gravity_contract_info.last_round, u64::MAX);
+        assert_eq!(gravity_contract_info.round_lag(u64::MAX), 0);
+        assert!(gravity_contract_info
+            .to_string()
+            .contains(&format!("last_round: {}", u64::MAX)));
+    }
+
+    #[test]
+    fn test_diff_encode() {
+        let base = GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls: sorted_consuls(5),
+            last_round: 7,
+            multisig_account: Pubkey::new_unique(),
+            ..GravityContract::default()
+        };
+
+        let advanced = GravityContract {
+            last_round: 8,
+            last_update_slot: 120,
+            ..base.clone()
+        };
+        let patch = GravityContract::diff_encode(&base, &advanced);
+        assert_eq!(patch.len(), 2 + 8 + 8);
+        assert!(patch.len() * 10 < GravityContract::LEN);
+        assert_eq!(GravityContract::diff_apply(&base, &patch), Ok(advanced));
+
+        assert_eq!(GravityContract::diff_encode(&base, &base), vec![0, 0]);
+
+        // dropping one consul and appending another only carries the new key
+        let mut consuls = base.consuls.clone();
+        consuls.remove(1);
+        consuls.push(Pubkey::new_unique());
+        let rotated = GravityContract {
+            consuls,
+            consul_epoch: 1,
+            ..base.clone()
+        };
+        let patch = GravityContract::diff_encode(&base, &rotated);
+        assert_eq!(patch.len(), 2 + (1 + 2 + 1 + 32) + 8);
+        assert_eq!(GravityContract::diff_apply(&base, &patch), Ok(rotated));
+
+        let reordered = GravityContract {
+            consuls: base.consuls.iter().rev().copied().collect(),
+            bft: 3,
+            ..base.clone()
+        };
+        let patch = GravityContract::diff_encode(&base, &reordered);
+        assert_eq!(GravityContract::diff_apply(&base, &patch), Ok(reordered));
+
+        assert_eq!(
+            GravityContract::diff_apply(&base, &patch[..patch.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut trailing = patch;
+        trailing.push(0);
+        assert_eq!(
+            GravityContract::diff_apply(&base, &trailing),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            GravityContract::diff_apply(&base, &[DIFF_CONSULS, 0, 0, 1, 5, 0]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            GravityContract::diff_apply(&base, &[0, !DIFF_EXT_FIELDS]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_diff_round_trip() {
+        let (base, _) = GravityContract::conformance_vectors().swap_remove(1);
+        let key_types = GravityContract {
+            secp256k1_consuls: 0b101,
+            ..base.clone()
+        };
+        let patch = GravityContract::diff_encode(&base, &key_types);
+        assert_eq!(patch, vec![0, DIFF_SECP256K1_CONSULS, 0b101]);
+        assert_eq!(GravityContract::diff_apply(&base, &patch), Ok(key_types));
+
+        let sharded = GravityContract {
+            consul_shards: 2,
+            ..base.clone()
+        };
+        let patch = GravityContract::diff_encode(&base, &sharded);
+        assert_eq!(patch, vec![0, DIFF_CONSUL_SHARDS, 2]);
+        assert_eq!(GravityContract::diff_apply(&base, &patch), Ok(sharded));
+
+        // every field changed at once comes back as it went in
+        let target = GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: base.bft + 1,
+            consuls: sorted_consuls(base.consuls.len() + 1),
+            last_round: base.last_round + 1,
+            multisig_account: Pubkey::new_unique(),
+            consul_epoch: base.consul_epoch + 1,
+            chain_id: base.chain_id + 1,
+            last_update_slot: base.last_update_slot + 1,
+            secp256k1_consuls: 0b110,
+            consul_shards: base.consul_shards + 1,
+        };
+        let patch = GravityContract::diff_encode(&base, &target);
+        assert_eq!(
+            GravityContract::diff_apply(&base, &patch),
+            Ok(target.clone())
+        );
+        // and back again, each field's flag naming a change
+        let patch = GravityContract::diff_encode(&target, &base);
+        assert_eq!(patch[..2], [u8::MAX, DIFF_EXT_FIELDS]);
+        assert_eq!(GravityContract::diff_apply(&target, &patch), Ok(base));
+    }
+
+    #[test]
+    fn test_replay() {
+        let initial = GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            multisig_account: Pubkey::new_unique(),
+            ..GravityContract::default()
+        };
+        let consuls = sorted_consuls(3);
+        let rotated = sorted_consuls(3);
+
+        let mut trace = vec![
+            GravityContractInstruction::InitContract {
+                new_consuls: consuls.clone(),
+                current_round: 0,
+                bft: 2,
+                chain_id: 3,
+            },
+            GravityContractInstruction::UpdateConsuls {
+                new_consuls: consuls.clone(),
+                current_round: 5,
+            },
+            GravityContractInstruction::UpdateConsuls {
+                new_consuls: rotated.clone(),
+                current_round: 9,
+            },
+        ];
+
+        let state = GravityContract::replay(initial.clone(), &trace).unwrap();
+        assert_eq!(state.last_round, 9);
+        assert_eq!(state.consuls, rotated);
+        assert_eq!(state.consul_epoch, 1);
+        assert_eq!(state.bft, 2);
+        assert_eq!(state.chain_id, 3);
+        assert_eq!(state.initializer_pubkey, initial.initializer_pubkey);
+        assert_eq!(state.multisig_account, initial.multisig_account);
+
+        assert_eq!(
+            GravityContract::replay(state, &trace[..1]),
+            Err(GravityError::AccountAlreadyUsed.into())
+        );
+
+        // the round regression at the last step fails the whole replay
+        trace.push(GravityContractInstruction::UpdateConsuls {
+            new_consuls: rotated,
+            current_round: 9,
+        });
+        assert_eq!(
+            GravityContract::replay(initial, &trace),
+            Err(GravityError::InputRoundMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_rotate_consuls() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let same_consuls = gravity_contract_info.consuls.clone();
+
+        // an update keeping the set moves the round alone
+        let records = gravity_contract_info
+            .rotate_consuls(8, same_consuls, 40)
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![EventRecord::new(
+                ContractEvent::RoundUpdated { round: 8 },
+                &gravity_contract_info
+            )
+            .unwrap()]
+        );
+        assert_eq!(gravity_contract_info.last_update_slot, 40);
+
+        // each event is recorded with the contract as it stood once it happened
+        let rotated = sorted_consuls(3);
+        let before_rotation = GravityContract {
+            last_round: 9,
+            last_update_slot: 41,
+            ..gravity_contract_info.clone()
+        };
+        let records = gravity_contract_info
+            .rotate_consuls(9, rotated.clone(), 41)
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                EventRecord::new(ContractEvent::RoundUpdated { round: 9 }, &before_rotation)
+                    .unwrap(),
+                EventRecord::new(
+                    ContractEvent::ConsulsRotated { consuls: rotated },
+                    &gravity_contract_info
+                )
+                .unwrap(),
+            ]
+        );
+        assert_eq!(gravity_contract_info.consul_epoch, 1);
+
+        assert_eq!(
+            gravity_contract_info.rotate_consuls(10, sorted_consuls(7), 42),
+            Err(GravityError::TooManyConsuls.into())
+        );
+        assert_eq!(gravity_contract_info.last_round, 9);
+    }
+
+    #[test]
+    fn test_simulate_update_round() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+
+        let simulated = gravity_contract_info.simulate_update_round(10).unwrap();
+        assert_eq!(simulated.last_round, 10);
+        assert_eq!(gravity_contract_info.last_round, 7);
+        assert_eq!(simulated.consuls, gravity_contract_info.consuls);
+
+        assert_eq!(
+            gravity_contract_info.simulate_update_round(7),
+            Err(GravityError::InputRoundMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_pending_update_accumulation() {
+        let keypairs: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_contract_info =
+            build_contract(keypairs.iter().map(|consul| consul.pubkey()).collect());
+        let consuls = &gravity_contract_info.consuls;
+        let bft = gravity_contract_info.bft;
+
+        let mut packed = vec![0; PendingUpdate::LEN];
+        PendingUpdate {
+            gravity_contract: Pubkey::new_unique(),
+            current_round: 8,
+            new_consuls: sorted_consuls(3),
+            ..PendingUpdate::default()
+        }
+        .pack_into_slice(&mut packed);
+
+        let message = PendingUpdate::unpack_from_slice(&packed)
+            .unwrap()
+            .signing_message(&gravity_contract_info);
+        let sign = |consul: &Keypair, message: &[u8]| {
+            let mut signature = [0; 64];
+            signature.copy_from_slice(consul.sign_message(message).as_ref());
+            signature
+        };
+
+        // every consul signs in a separate transaction
+        for (i, consul) in keypairs.iter().take(bft as usize).enumerate() {
+            let mut pending_update = PendingUpdate::unpack_from_slice(&packed).unwrap();
+            assert!(!pending_update.is_ready(bft));
+
+            let stranger = Keypair::new();
+            assert!(matches!(
+                pending_update.add_signature(
+                    &SoftwareVerifier,
+                    consuls,
+                    &stranger.pubkey(),
+                    &message,
+                    sign(&stranger, &message)
+                ),
+                Err(GravityError::UnknownConsul)
+            ));
+            // a signature over anything else, or made up, doesn't count
+            for forged in [sign(consul, b"round 8"), [i as u8; 64]].iter() {
+                assert!(matches!(
+                    pending_update.add_signature(
+                        &SoftwareVerifier,
+                        consuls,
+                        &consul.pubkey(),
+                        &message,
+                        *forged
+                    ),
+                    Err(GravityError::InvalidSignature)
+                ));
+            }
+
+            let signature = sign(consul, &message);
+            pending_update
+                .add_signature(
+                    &SoftwareVerifier,
+                    consuls,
+                    &consul.pubkey(),
+                    &message,
+                    signature,
+                )
+                .unwrap();
+            assert!(matches!(
+                pending_update.add_signature(
+                    &SoftwareVerifier,
+                    consuls,
+                    &consul.pubkey(),
+                    &message,
+                    signature
+                ),
+                Err(GravityError::DuplicateSignature)
+            ));
+
+            pending_update.pack_into_slice(&mut packed);
+        }
+
+        let mut pending_update = PendingUpdate::unpack_from_slice(&packed).unwrap();
+        assert_eq!(pending_update.signatures.len(), bft as usize);
+        assert!(pending_update.is_ready(bft));
+
+        pending_update.finalize(bft).unwrap();
+        assert!(matches!(
+            pending_update.add_signature(
+                &SoftwareVerifier,
+                consuls,
+                &consuls[2],
+                &message,
+                sign(&keypairs[2], &message)
+            ),
+            Err(GravityError::PendingUpdateFinalized)
+        ));
+    }
+
+    #[test]
+    fn test_pending_update_expiration() {
+        let pending_update = PendingUpdate {
+            gravity_contract: Pubkey::new_unique(),
+            started_at: 100,
+            ..PendingUpdate::default()
+        };
+
+        let mut clock = MockClock { slot: 100 };
+        assert!(!pending_update.is_expired(&clock, 10));
+
+        clock.advance(10);
+        assert!(!pending_update.is_expired(&clock, 10));
+
+        clock.advance(1);
+        assert!(pending_update.is_expired(&clock, 10));
+    }
+
+    #[test]
+    fn test_nebula_registry() {
+        let mut nebula_registry = NebulaRegistry {
+            gravity_contract: Pubkey::new_unique(),
+            ..NebulaRegistry::default()
+        };
+
+        let nebula = Pubkey::new_unique();
+        nebula_registry.register(nebula).unwrap();
+        assert!(nebula_registry.is_registered(&nebula));
+        assert!(!nebula_registry.is_registered(&Pubkey::new_unique()));
+        assert!(matches!(
+            nebula_registry.register(nebula),
+            Err(GravityError::NebulaAlreadyRegistered)
+        ));
+
+        while nebula_registry.nebulas.len() < NebulaRegistry::MAX_NEBULAS {
+            nebula_registry.register(Pubkey::new_unique()).unwrap();
+        }
+        assert!(matches!(
+            nebula_registry.register(Pubkey::new_unique()),
+            Err(GravityError::NebulaRegistryFull)
+        ));
+
+        let mut packed = vec![0; NebulaRegistry::LEN];
+        nebula_registry.pack_into_slice(&mut packed);
+        assert_eq!(
+            NebulaRegistry::unpack_from_slice(&packed).unwrap(),
+            nebula_registry
+        );
+    }
+
+    #[test]
+    fn test_compact_round_history() {
+        let entry = |round, hash: u8| RoundHistoryEntry {
+            round,
+            consul_hash: Hash::new_from_array([hash; 32]),
+        };
+        let mut history = ConsulRoundHistory {
+            entries: vec![entry(1, 1), entry(3, 3), entry(2, 2), entry(3, 4)],
+        };
+
+        history.compact();
+        assert_eq!(history.entries, vec![entry(1, 1), entry(2, 2), entry(3, 4)]);
+        assert_eq!(history.first_gap(), None);
+
+        history.entries.push(entry(6, 6));
+        history.compact();
+        assert_eq!(history.first_gap(), Some(4));
+
+        let mut empty = ConsulRoundHistory::default();
+        empty.compact();
+        assert_eq!(empty.first_gap(), None);
+    }
+
+    #[test]
+    fn test_event_log_replay() {
+        let mut contract = GravityContract {
+            initializer_pubkey: Pubkey::new_unique(),
+            bft: 2,
+            consuls: sorted_consuls(3),
+            multisig_account: Pubkey::new_unique(),
+            ..GravityContract::default()
+        };
+        let mut log = EventLog::default();
+        log.record(
+            ContractEvent::Initialized {
+                initializer: contract.initializer_pubkey,
+                bft: contract.bft,
+                consuls: contract.consuls.clone(),
+                multisig_account: contract.multisig_account,
+                chain_id: 0,
+            },
+            &contract,
+        )
+        .unwrap();
+
+        for (round, slot) in [(1, 10), (2, 20)].iter() {
+            contract.update_round(*round).unwrap();
+            contract.last_update_slot = *slot;
+            log.record(ContractEvent::RoundUpdated { round: *round }, &contract)
+                .unwrap();
+        }
+
+        let new_consuls = sorted_consuls(4);
+        contract.set_consuls(new_consuls.clone()).unwrap();
+        log.record(
+            ContractEvent::ConsulsRotated {
+                consuls: new_consuls,
+            },
+            &contract,
+        )
+        .unwrap();
+
+        contract.bft = 3;
+        log.record(ContractEvent::BftChanged { bft: 3 }, &contract)
+            .unwrap();
+
+        let new_owner = Pubkey::new_unique();
+        contract.initializer_pubkey = new_owner;
+        log.record(ContractEvent::OwnershipTransferred { new_owner }, &contract)
+            .unwrap();
+
+        contract.update_round(5).unwrap();
+        contract.last_update_slot = 50;
+        log.record(ContractEvent::RoundUpdated { round: 5 }, &contract)
+            .unwrap();
+
+        assert!(log.verify_against(&contract));
+        assert!(EventLog::default().verify_against(&GravityContract::default()));
+
+        // a missing rotation leaves the old consuls, and the old epoch, in every later state
+        let mut dropped = log.clone();
+        dropped.records.remove(3);
+        assert!(!dropped.verify_against(&contract));
+
+        // a log cut short doesn't reach the final state
+        let mut truncated = log.clone();
+        truncated.records.pop();
+        assert!(!truncated.verify_against(&contract));
+
+        let mut reordered = log.clone();
+        reordered.records.swap(1, 2);
+        assert!(!reordered.verify_against(&contract));
+
+        let tampered = GravityContract {
+            last_round: 6,
+            ..contract.clone()
+        };
+        assert!(!log.verify_against(&tampered));
+    }
+
+    #[test]
+    fn test_rotation_log() {
+        let mut gravity_contract_info = build_contract(sorted_consuls(3));
+        let mut rotation_log = RotationLog {
+            gravity_contract: Pubkey::new_unique(),
+            ..RotationLog::default()
+        };
+
+        for slot in 1..=3 {
+            let old_commitment = gravity_contract_info.consul_commitment();
+            gravity_contract_info
+                .set_consuls(sorted_consuls(3))
+                .unwrap();
+            rotation_log.append(RotationEntry {
+                epoch: gravity_contract_info.consul_epoch,
+                old_commitment,
+                new_commitment: gravity_contract_info.consul_commitment(),
+                initiator: gravity_contract_info.initializer_pubkey,
+                slot,
+            });
+        }
+        assert!(rotation_log.verify_chain());
+        assert!(RotationLog::default().verify_chain());
+
+        let mut packed = vec![0; RotationLog::LEN];
+        rotation_log.pack_into_slice(&mut packed);
+        assert_eq!(
+            RotationLog::unpack_from_slice(&packed).unwrap(),
+            rotation_log
+        );
+
+        let mut tampered = rotation_log.clone();
+        tampered.entries[1].new_commitment = Hash::new_unique();
+        assert!(!tampered.verify_chain());
+
+        let mut tampered = rotation_log.clone();
+        tampered.entries.remove(1);
+        assert!(!tampered.verify_chain());
+
+        while rotation_log.entries.len() < RotationLog::MAX_ENTRIES {
+            let old_commitment = gravity_contract_info.consul_commitment();
+            gravity_contract_info
+                .set_consuls(sorted_consuls(3))
+                .unwrap();
+            rotation_log.append(RotationEntry {
+                epoch: gravity_contract_info.consul_epoch,
+                old_commitment,
+                new_commitment: gravity_contract_info.consul_commitment(),
+                initiator: gravity_contract_info.initializer_pubkey,
+                slot: gravity_contract_info.consul_epoch,
+            });
+        }
+        // a full log makes room by dropping its oldest entry rather than refusing a rotation
+        let oldest_kept = rotation_log.entries[1].clone();
+        let old_commitment = gravity_contract_info.consul_commitment();
+        gravity_contract_info
+            .set_consuls(sorted_consuls(3))
+            .unwrap();
+        let entry = RotationEntry {
+            epoch: gravity_contract_info.consul_epoch,
+            old_commitment,
+            new_commitment: gravity_contract_info.consul_commitment(),
+            ..RotationEntry::default()
+        };
+        rotation_log.append(entry.clone());
+        assert_eq!(rotation_log.entries.len(), RotationLog::MAX_ENTRIES);
+        assert_eq!(rotation_log.entries[0], oldest_kept);
+        assert_eq!(rotation_log.entries.last(), Some(&entry));
+        assert!(rotation_log.verify_chain());
+
+        // a full log still fits its account
+        let mut packed = vec![0; RotationLog::LEN];
+        rotation_log.pack_into_slice(&mut packed);
+        assert_eq!(
+            RotationLog::unpack_from_slice(&packed).unwrap(),
+            rotation_log
+        );
+    }
+
+    #[test]
+    fn test_load_consuls_from_shards() {
+        let program_id = Pubkey::new_unique();
+        let gravity_contract = Pubkey::new_unique();
+        let consuls = sorted_consuls(5);
+        let gravity_contract_info = GravityContract {
+            consul_shards: 2,
+            ..build_contract(consuls.clone())
+        };
+
+        let keys = [
+            ConsulShard::address(&gravity_contract, 0, &program_id).0,
+            ConsulShard::address(&gravity_contract, 1, &program_id).0,
+        ];
+        let mut lamports = [0, 0];
+        let mut data: Vec<Vec<u8>> = [&consuls[..3], &consuls[3..]]
+            .iter()
+            .enumerate()
+            .map(|(index, shard_consuls)| {
+                let mut packed = vec![0; ConsulShard::LEN];
+                ConsulShard {
+                    gravity_contract,
+                    index: index as u8,
+                    shard_count: 2,
+                    consuls: shard_consuls.to_vec(),
+                }
+                .pack_into_slice(&mut packed);
+                packed
+            })
+            .collect();
+
+        let mut accounts = vec![];
+        for ((key, lamports), data) in keys.iter().zip(lamports.iter_mut()).zip(data.iter_mut()) {
+            accounts.push(AccountInfo::new(
+                key,
+                false,
+                false,
+                lamports,
+                data,
+                &program_id,
+                false,
+                0,
+            ));
+        }
+        let (first, second) = (&accounts[0], &accounts[1]);
+        let load = |shards: &[&AccountInfo]| {
+            gravity_contract_info.load_consuls_from_shards(&gravity_contract, shards, &program_id)
+        };
+
+        assert_eq!(load(&[first, second]), Ok(consuls));
+
+        for shards in [vec![first], vec![second], vec![second, first], vec![]].iter() {
+            assert_eq!(
+                load(shards),
+                Err(GravityError::InvalidShardSequence.into())
+            );
+        }
+        assert_eq!(
+            GravityContract {
+                consul_shards: 0,
+                ..gravity_contract_info.clone()
+            }
+            .load_consuls_from_shards(&gravity_contract, &[], &program_id),
+            Err(GravityError::TooFewConsuls.into())
+        );
+
+        // the shards have to be the ones derived for this contract
+        assert_eq!(
+            gravity_contract_info.load_consuls_from_shards(
+                &Pubkey::new_unique(),
+                &[first, second],
+                &program_id
+            ),
+            Err(GravityError::InvalidShardSequence.into())
+        );
+
+        // a forged shard at an address of its own is rejected for it, at the right address
+        // for being owned by another program
+        let forger = Pubkey::new_unique();
+        let mut forged_lamports = 0;
+        let mut forged_data = second.try_borrow_data().unwrap().to_vec();
+        let forged = AccountInfo::new(
+            &keys[1],
+            false,
+            false,
+            &mut forged_lamports,
+            &mut forged_data,
+            &forger,
+            false,
+            0,
+        );
+        assert_eq!(
+            load(&[first, &forged]),
+            Err(GravityError::SpoofedAccount.into())
+        );
+
+        // a shard of another contract doesn't complete the set
+        let mut shard = ConsulShard::unpack(&second.try_borrow_data().unwrap()).unwrap();
+        shard.gravity_contract = Pubkey::new_unique();
+        shard.pack_into_slice(&mut second.try_borrow_mut_data().unwrap());
+        assert_eq!(
+            load(&[first, second]),
+            Err(GravityError::InvalidShardSequence.into())
+        );
+
+        // nor can the shards add up to more consuls than the contract verifies with
+        for (index, shard_account) in [first, second].iter().enumerate() {
+            ConsulShard {
+                gravity_contract,
+                index: index as u8,
+                shard_count: 2,
+                consuls: sorted_consuls(4),
+            }
+            .pack_into_slice(&mut shard_account.try_borrow_mut_data().unwrap());
+        }
+        assert_eq!(
+            load(&[first, second]),
+            Err(GravityError::TooManyConsuls.into())
+        );
+
+        // a short account fails to decode instead of panicking
+        let mut short_lamports = 0;
+        let mut short_data = vec![0; DISCRIMINATOR_LEN];
+        let short = AccountInfo::new(
+            &keys[0],
+            false,
+            false,
+            &mut short_lamports,
+            &mut short_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            load(&[&short, second]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_consul_shard_pack() {
+        let shard = ConsulShard {
+            gravity_contract: Pubkey::new_unique(),
+            index: 0,
+            shard_count: 1,
+            consuls: sorted_consuls(ConsulShard::MAX_CONSULS),
+        };
+        let mut packed = vec![0xff; ConsulShard::LEN];
+        shard.try_pack_into_slice(&mut packed).unwrap();
+        assert_eq!(ConsulShard::unpack(&packed), Ok(shard.clone()));
+
+        // neither a shard over the cap nor a short account is written to
+        let oversized = ConsulShard {
+            consuls: sorted_consuls(ConsulShard::MAX_CONSULS + 1),
+            ..shard.clone()
+        };
+        let mut dst = vec![0; ConsulShard::LEN * 2];
+        assert_eq!(
+            oversized.try_pack_into_slice(&mut dst),
+            Err(GravityError::TooManyConsuls.into())
+        );
+        assert!(is_contract_empty(&dst));
+        let pack_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            oversized.pack_into_slice(&mut dst.clone())
+        }));
+        assert!(pack_result.is_err());
+
+        let mut short = vec![0; ConsulShard::LEN - 1];
+        assert_eq!(
+            shard.try_pack_into_slice(&mut short),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+        assert!(is_contract_empty(&short));
+
+        // nor does an oversized shard decode
+        let mut data = ConsulShard::DISCRIMINATOR.to_vec();
+        oversized.serialize(&mut data).unwrap();
+        assert_eq!(
+            ConsulShard::unpack_from_slice(&data),
+            Err(GravityError::TooManyConsuls.into())
+        );
+    }
+
+    #[test]
+    fn test_decode_account() {
+        let gravity_contract_info = build_contract(sorted_consuls(3));
+        let mut packed = vec![0; GravityContract::LEN];
+        gravity_contract_info.pack_into_slice(&mut packed);
+        assert_eq!(
+            decode_account(&packed),
+            Ok(DecodedAccount::Gravity(gravity_contract_info))
+        );
+
+        let pending_update = PendingUpdate {
+            gravity_contract: Pubkey::new_unique(),
+            started_at: 5,
+            ..PendingUpdate::default()
+        };
+        let mut packed = vec![0; PendingUpdate::LEN];
+        pending_update.pack_into_slice(&mut packed);
+        assert_eq!(
+            decode_account(&packed),
+            Ok(DecodedAccount::PendingUpdate(pending_update))
+        );
+
+        let nebula_registry = NebulaRegistry {
+            gravity_contract: Pubkey::new_unique(),
+            nebulas: vec![Pubkey::new_unique()],
+        };
+        let mut packed = vec![0; NebulaRegistry::LEN];
+        nebula_registry.pack_into_slice(&mut packed);
+        assert_eq!(
+            decode_account(&packed),
+            Ok(DecodedAccount::NebulaRegistry(nebula_registry))
+        );
+
+        // the Nebula program decodes its own accounts
+        packed[..DISCRIMINATOR_LEN].copy_from_slice(&NEBULA_CONTRACT);
+        assert_eq!(
+            decode_account(&packed),
+            Err(GravityError::WrongAccountType.into())
+        );
+        assert_eq!(
+            decode_account(&[0; 64]),
+            Err(GravityError::WrongAccountType.into())
+        );
+        assert_eq!(
+            decode_account(&GravityContract::DISCRIMINATOR),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_verify_scan_result() {
+        let program_id = Pubkey::new_unique();
+        let impostor_program = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; GravityContract::LEN];
+        build_contract(sorted_consuls(3)).pack_into_slice(&mut data);
+
+        for (owner, expected) in [
+            (&program_id, Ok(())),
+            (&impostor_program, Err(GravityError::SpoofedAccount.into())),
+        ] {
+            let account = AccountInfo::new(
+                &key,
+                false,
+                false,
+                &mut lamports,
+                &mut data,
+                owner,
+                false,
+                0,
+            );
+            assert_eq!(verify_scan_result(&account, &program_id), expected);
+        }
+
+        data[..DISCRIMINATOR_LEN].copy_from_slice(&NEBULA_CONTRACT);
+        let account = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            verify_scan_result(&account, &program_id),
+            Err(GravityError::WrongAccountType.into())
+        );
     }
 }
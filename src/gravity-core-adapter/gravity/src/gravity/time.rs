@@ -0,0 +1,49 @@
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Slot},
+    program_error::ProgramError,
+    sysvar::Sysvar,
+};
+
+/// Where time-dependent checks read the current slot from.
+pub trait TimeSource {
+    fn current_slot(&self) -> Slot;
+}
+
+/// The `Clock` sysvar, from the account handed to an instruction or straight from the runtime.
+pub struct SysvarClock(pub Clock);
+
+impl SysvarClock {
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<Self, ProgramError> {
+        Ok(SysvarClock(Clock::from_account_info(account_info)?))
+    }
+
+    /// Reads the clock without the sysvar account, for instructions whose accounts don't include it.
+    pub fn get() -> Result<Self, ProgramError> {
+        Ok(SysvarClock(Clock::get()?))
+    }
+}
+
+impl TimeSource for SysvarClock {
+    fn current_slot(&self) -> Slot {
+        self.0.slot
+    }
+}
+
+/// Stands still at `slot` until moved, for driving expiry in tests.
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub struct MockClock {
+    pub slot: Slot,
+}
+
+impl MockClock {
+    pub fn advance(&mut self, slots: Slot) {
+        self.slot += slots;
+    }
+}
+
+impl TimeSource for MockClock {
+    fn current_slot(&self) -> Slot {
+        self.slot
+    }
+}
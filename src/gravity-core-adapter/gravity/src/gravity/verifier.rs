@@ -0,0 +1,378 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    secp256k1_program,
+    sysvar::{self, instructions::load_instruction_at},
+};
+
+use crate::gravity::{
+    signature::{EthAddress, RecoverableSignature},
+    state::ConsulSignature,
+};
+
+/// The ed25519 signature verification program. It is a native program of the 1.8 and later
+/// releases, active on clusters that enabled their `ed25519_program_enabled` feature, and
+/// missing from the 1.6 runtime the crate builds against. Updates signed by ed25519 consuls
+/// can only be submitted to such clusters; on a 1.6 cluster only secp256k1 consuls, checked
+/// by the secp256k1 program it does have, can reach a quorum.
+pub mod ed25519_program {
+    solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+}
+
+/// Length of one entry of the offsets table of an ed25519 program instruction.
+pub const ED25519_OFFSETS_LEN: usize = 14;
+
+/// Length of one entry of the offsets table of a secp256k1 program instruction.
+pub const SECP256K1_OFFSETS_LEN: usize = 11;
+
+/// Instruction index the ed25519 program reads as the instruction carrying the offsets itself.
+pub const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Where signature checks get their answer from.
+pub trait SignatureVerifier {
+    fn verify_ed25519(&self, signer: &Pubkey, message: &[u8], signature: &ConsulSignature) -> bool;
+
+    /// The address of whoever signed the keccak256 of `message`, `None` for a signature that
+    /// doesn't recover.
+    fn recover_secp256k1(
+        &self,
+        message: &[u8],
+        signature: &RecoverableSignature,
+    ) -> Option<EthAddress>;
+}
+
+/// Checks signatures in software, for clients holding no transaction to take the native
+/// programs' results from. On-chain the same checks would cost more compute than a
+/// transaction has.
+#[cfg(any(test, feature = "client"))]
+pub struct SoftwareVerifier;
+
+#[cfg(any(test, feature = "client"))]
+impl SignatureVerifier for SoftwareVerifier {
+    fn verify_ed25519(&self, signer: &Pubkey, message: &[u8], signature: &ConsulSignature) -> bool {
+        crate::gravity::signature::verify_signature(signer, message, signature)
+    }
+
+    fn recover_secp256k1(
+        &self,
+        message: &[u8],
+        signature: &RecoverableSignature,
+    ) -> Option<EthAddress> {
+        crate::gravity::signature::recover_consul(message, signature)
+            .map(|consul| crate::gravity::signature::consul_eth_address(&consul))
+    }
+}
+
+/// The signatures the ed25519 and secp256k1 programs checked in the current transaction.
+/// A transaction carrying an invalid one fails as a whole, so any signature listed is a
+/// valid one.
+#[derive(PartialEq, Default, Debug, Clone)]
+pub struct NativeVerifier {
+    ed25519: Vec<(Pubkey, Vec<u8>, ConsulSignature)>,
+    secp256k1: Vec<(EthAddress, Vec<u8>, RecoverableSignature)>,
+}
+
+impl NativeVerifier {
+    pub fn from_account_info(instructions: &AccountInfo) -> Result<Self, ProgramError> {
+        if !sysvar::instructions::check_id(instructions.key) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Self::from_instructions_data(&instructions.try_borrow_data()?)
+    }
+
+    /// Reads the checked signatures out of the instructions sysvar data.
+    pub fn from_instructions_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let count = data
+            .get(..2)
+            .map(|count| u16::from_le_bytes([count[0], count[1]]))
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let instructions = (0..count as usize)
+            .map(|index| load_instruction_at(index, data))
+            .collect::<Result<Vec<Instruction>, _>>()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        let mut verifier = NativeVerifier::default();
+        for (index, instruction) in instructions.iter().enumerate() {
+            if instruction.program_id == ed25519_program::id() {
+                verifier.read_ed25519(&instructions, index)?;
+            } else if instruction.program_id == secp256k1_program::id() {
+                verifier.read_secp256k1(&instructions, index)?;
+            }
+        }
+
+        Ok(verifier)
+    }
+
+    fn read_ed25519(
+        &mut self,
+        instructions: &[Instruction],
+        index: usize,
+    ) -> Result<(), ProgramError> {
+        let data = &instructions[index].data;
+        let count = *data.first().ok_or(ProgramError::InvalidInstructionData)? as usize;
+
+        for i in 0..count {
+            // the signature count is followed by a padding byte
+            let start = 2 + i * ED25519_OFFSETS_LEN;
+            let offsets = data
+                .get(start..start + ED25519_OFFSETS_LEN)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            let field = |n: usize| u16::from_le_bytes([offsets[2 * n], offsets[2 * n + 1]]);
+
+            let signature = instruction_slice(instructions, index, field(1), field(0), 64)?;
+            let signer = instruction_slice(instructions, index, field(3), field(2), 32)?;
+            let message =
+                instruction_slice(instructions, index, field(6), field(4), field(5) as usize)?;
+
+            let mut consul_signature = [0; 64];
+            consul_signature.copy_from_slice(signature);
+            self.ed25519
+                .push((Pubkey::new(signer), message.to_vec(), consul_signature));
+        }
+
+        Ok(())
+    }
+
+    fn read_secp256k1(
+        &mut self,
+        instructions: &[Instruction],
+        index: usize,
+    ) -> Result<(), ProgramError> {
+        let data = &instructions[index].data;
+        let count = *data.first().ok_or(ProgramError::InvalidInstructionData)? as usize;
+
+        for i in 0..count {
+            let start = 1 + i * SECP256K1_OFFSETS_LEN;
+            let offsets = data
+                .get(start..start + SECP256K1_OFFSETS_LEN)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            let offset = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]);
+
+            // the instruction indices are single bytes, the offsets and size two
+            let signature =
+                instruction_slice(instructions, index, offsets[2] as u16, offset(0), 65)?;
+            let address = instruction_slice(instructions, index, offsets[5] as u16, offset(3), 20)?;
+            let message = instruction_slice(
+                instructions,
+                index,
+                offsets[10] as u16,
+                offset(6),
+                offset(8) as usize,
+            )?;
+
+            let mut recoverable = [0; 65];
+            recoverable.copy_from_slice(signature);
+            let mut eth_address = [0; 20];
+            eth_address.copy_from_slice(address);
+            self.secp256k1
+                .push((eth_address, message.to_vec(), recoverable));
+        }
+
+        Ok(())
+    }
+}
+
+impl SignatureVerifier for NativeVerifier {
+    fn verify_ed25519(&self, signer: &Pubkey, message: &[u8], signature: &ConsulSignature) -> bool {
+        self.ed25519
+            .iter()
+            .any(|(checked_signer, checked_message, checked_signature)| {
+                checked_signer == signer
+                    && checked_message.as_slice() == message
+                    && checked_signature[..] == signature[..]
+            })
+    }
+
+    fn recover_secp256k1(
+        &self,
+        message: &[u8],
+        signature: &RecoverableSignature,
+    ) -> Option<EthAddress> {
+        self.secp256k1
+            .iter()
+            .find(|(_, checked_message, checked_signature)| {
+                checked_message.as_slice() == message && checked_signature[..] == signature[..]
+            })
+            .map(|(address, _, _)| *address)
+    }
+}
+
+/// `len` bytes at `offset` into the data of instruction `instruction_index`, the instruction
+/// at `current` standing in for `CURRENT_INSTRUCTION`, which only the ed25519 program uses.
+fn instruction_slice(
+    instructions: &[Instruction],
+    current: usize,
+    instruction_index: u16,
+    offset: u16,
+    len: usize,
+) -> Result<&[u8], ProgramError> {
+    let instruction_index = if instruction_index == CURRENT_INSTRUCTION {
+        current
+    } else {
+        instruction_index as usize
+    };
+    let offset = offset as usize;
+
+    instructions
+        .get(instruction_index)
+        .and_then(|instruction| instruction.data.get(offset..offset + len))
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use solana_program::message::Message;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::gravity::{
+        instruction::{verify_ed25519_signatures, verify_secp256k1_signatures},
+        signature::{consul_eth_address, secp256k1_consul, signed_message},
+        state::GravityContract,
+    };
+
+    fn sign(consul: &Keypair, message: &[u8]) -> ConsulSignature {
+        let mut signature = [0; 64];
+        signature.copy_from_slice(consul.sign_message(message).as_ref());
+        signature
+    }
+
+    fn instructions_data(instructions: &[Instruction]) -> Vec<u8> {
+        Message::new(instructions, None).serialize_instructions(true)
+    }
+
+    #[test]
+    fn test_native_verifier() {
+        let consuls: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let message = b"round 8";
+        let signatures: Vec<(Pubkey, ConsulSignature)> = consuls
+            .iter()
+            .map(|consul| (consul.pubkey(), sign(consul, message)))
+            .collect();
+
+        let other = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], vec![]);
+        let data = instructions_data(&[
+            other.clone(),
+            verify_ed25519_signatures(message, &signatures),
+            other,
+        ]);
+        let verifier = NativeVerifier::from_instructions_data(&data).unwrap();
+
+        for (signer, signature) in signatures.iter() {
+            assert!(verifier.verify_ed25519(signer, message, signature));
+            assert!(!verifier.verify_ed25519(signer, b"round 9", signature));
+        }
+        assert!(!verifier.verify_ed25519(&signatures[1].0, message, &signatures[0].1));
+
+        // without the ed25519 program instruction nothing counts as checked
+        let verifier = NativeVerifier::from_instructions_data(&instructions_data(&[])).unwrap();
+        assert!(!verifier.verify_ed25519(&signatures[0].0, message, &signatures[0].1));
+    }
+
+    #[test]
+    fn test_native_verifier_offsets() {
+        let consul = Keypair::new();
+        let message = b"round 8";
+        let signature = sign(&consul, message);
+
+        // the message lives in another instruction of the transaction
+        let carrier = Instruction::new_with_bytes(Pubkey::new_unique(), message, vec![]);
+        let mut data = vec![1, 0];
+        for field in [14 + 2, u16::MAX, 2 + 14 + 64, u16::MAX, 0, 7, 0].iter() {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&signature);
+        data.extend_from_slice(consul.pubkey().as_ref());
+        let ed25519 = Instruction::new_with_bytes(ed25519_program::id(), &data, vec![]);
+
+        let verifier =
+            NativeVerifier::from_instructions_data(&instructions_data(&[carrier, ed25519.clone()]))
+                .unwrap();
+        assert!(verifier.verify_ed25519(&consul.pubkey(), message, &signature));
+
+        // offsets pointing past the data are refused rather than read
+        let mut truncated = ed25519;
+        truncated.data.truncate(2 + 14 + 64);
+        assert_eq!(
+            NativeVerifier::from_instructions_data(&instructions_data(&[truncated])),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            NativeVerifier::from_instructions_data(&[1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    fn secp256k1_sign(secret_key: &secp256k1::SecretKey, message: &[u8]) -> RecoverableSignature {
+        let message = secp256k1::Message::parse(&solana_program::keccak::hash(message).to_bytes());
+        let (signature, recovery_id) = secp256k1::sign(&message, secret_key);
+
+        let mut recoverable = [0; 65];
+        recoverable[..64].copy_from_slice(&signature.serialize());
+        recoverable[64] = recovery_id.serialize();
+        recoverable
+    }
+
+    #[test]
+    fn test_native_verifier_secp256k1() {
+        let secret_keys: Vec<secp256k1::SecretKey> = (1..4)
+            .map(|seed| secp256k1::SecretKey::parse(&[seed; 32]).unwrap())
+            .collect();
+        let consuls: Vec<Pubkey> = secret_keys
+            .iter()
+            .map(|secret_key| secp256k1_consul(&secp256k1::PublicKey::from_secret_key(secret_key)))
+            .collect();
+        let gravity_contract_info = GravityContract {
+            bft: 2,
+            consuls: consuls.clone(),
+            ..GravityContract::default()
+        };
+        let message = &signed_message(0, b"round 8");
+
+        let signatures: Vec<(EthAddress, RecoverableSignature)> = (0..2)
+            .map(|i| {
+                (
+                    consul_eth_address(&consuls[i]),
+                    secp256k1_sign(&secret_keys[i], message),
+                )
+            })
+            .collect();
+        let recoverable: Vec<RecoverableSignature> =
+            signatures.iter().map(|(_, signature)| *signature).collect();
+
+        let other = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], vec![]);
+        let data =
+            instructions_data(&[other, verify_secp256k1_signatures(1, message, &signatures)]);
+        let verifier = NativeVerifier::from_instructions_data(&data).unwrap();
+
+        assert_eq!(
+            verifier.recover_secp256k1(message, &recoverable[0]),
+            SoftwareVerifier.recover_secp256k1(message, &recoverable[0])
+        );
+        assert_eq!(
+            gravity_contract_info.verify_multisig_recoverable(&verifier, message, &recoverable),
+            Ok(())
+        );
+        // signatures the program wasn't handed don't recover at all
+        let unchecked = secp256k1_sign(&secret_keys[2], message);
+        assert_eq!(verifier.recover_secp256k1(message, &unchecked), None);
+        assert_eq!(
+            verifier.recover_secp256k1(b"round 9", &recoverable[0]),
+            None
+        );
+
+        // the SDK's own single-signature instruction reads the same, placed first
+        let sdk_instruction =
+            solana_sdk::secp256k1_instruction::new_secp256k1_instruction(&secret_keys[2], message);
+        let verifier =
+            NativeVerifier::from_instructions_data(&instructions_data(&[sdk_instruction])).unwrap();
+        assert_eq!(
+            verifier.recover_secp256k1(message, &unchecked),
+            Some(consul_eth_address(&consuls[2]))
+        );
+    }
+}
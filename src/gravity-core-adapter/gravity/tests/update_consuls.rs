@@ -0,0 +1,800 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    keccak,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::Multisig;
+
+use solana_gravity_contract::gravity::{
+    error::GravityError,
+    instruction::{
+        batch_update_rounds, emergency_replace, update_consuls, update_round_mixed,
+        verify_ed25519_signatures, verify_secp256k1_signatures,
+    },
+    processor::GravityProcessor,
+    signature::{consul_eth_address, round_update_message, MixedSignature, SignatureBundle},
+    state::{GravityContract, RotationLog},
+    verifier::ed25519_program,
+};
+
+fn program_test_with_contract(
+    program_id: Pubkey,
+    gravity_contract_key: Pubkey,
+    multisig_key: Pubkey,
+    consuls: &[Keypair],
+) -> ProgramTest {
+    let mut program_test = ProgramTest::new(
+        "solana_gravity_contract",
+        program_id,
+        processor!(GravityProcessor::process),
+    );
+
+    let gravity_contract_info = GravityContract {
+        initializer_pubkey: Pubkey::new_unique(),
+        bft: 2,
+        consuls: consuls.iter().map(|consul| consul.pubkey()).collect(),
+        last_round: 7,
+        multisig_account: multisig_key,
+        consul_epoch: 0,
+        chain_id: 0,
+        last_update_slot: 0,
+        secp256k1_consuls: 0,
+        consul_shards: 0,
+    };
+    let mut data = vec![0; GravityContract::LEN];
+    gravity_contract_info.pack_into_slice(&mut data);
+    program_test.add_account(
+        gravity_contract_key,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+
+    let mut multisig = Multisig {
+        m: 2,
+        n: consuls.len() as u8,
+        is_initialized: true,
+        ..Multisig::default()
+    };
+    for (i, consul) in consuls.iter().enumerate() {
+        multisig.signers[i] = consul.pubkey();
+    }
+    let mut data = vec![0; Multisig::LEN];
+    multisig.pack_into_slice(&mut data);
+    program_test.add_account(
+        multisig_key,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+
+    // the native processor can't grow accounts through the system program, so the rotation
+    // log the first rotation would create is allocated upfront
+    program_test.add_account(
+        RotationLog::address(&gravity_contract_key, &program_id).0,
+        Account {
+            lamports: 1_000_000_000,
+            data: vec![0; RotationLog::LEN],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+
+    program_test
+}
+
+#[tokio::test]
+async fn test_duplicate_round_update_in_one_transaction() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let multisig_key = Pubkey::new_unique();
+    let consuls = [Keypair::new(), Keypair::new()];
+
+    let (mut banks_client, payer, recent_blockhash) =
+        program_test_with_contract(program_id, gravity_contract_key, multisig_key, &consuls)
+            .start()
+            .await;
+
+    let signer_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+    let instruction = update_consuls(
+        &program_id,
+        &payer.pubkey(),
+        &gravity_contract_key,
+        &multisig_key,
+        &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+        &signer_pubkeys,
+        8,
+    )
+    .unwrap();
+
+    // the second instruction sees the round advanced by the first one
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction.clone(), instruction.clone()],
+        Some(&payer.pubkey()),
+        &[&payer, &consuls[0], &consuls[1]],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GravityError::InputRoundMismatch as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &consuls[0], &consuls[1]],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN])
+            .unwrap()
+            .last_round,
+        8
+    );
+}
+
+#[tokio::test]
+async fn test_round_update_records_slot() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let multisig_key = Pubkey::new_unique();
+    let consuls = [Keypair::new(), Keypair::new()];
+
+    let mut context =
+        program_test_with_contract(program_id, gravity_contract_key, multisig_key, &consuls)
+            .start_with_context()
+            .await;
+    context.warp_to_slot(50).unwrap();
+
+    let signer_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+    let instruction = update_consuls(
+        &program_id,
+        &context.payer.pubkey(),
+        &gravity_contract_key,
+        &multisig_key,
+        &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+        &signer_pubkeys,
+        8,
+    )
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &consuls[0], &consuls[1]],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let gravity_contract_account = context
+        .banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let gravity_contract_info =
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN]).unwrap();
+    assert_eq!(gravity_contract_info.last_update_slot, 50);
+    assert!(!gravity_contract_info.is_stale(60, 10));
+    assert!(gravity_contract_info.is_stale(61, 10));
+}
+
+#[tokio::test]
+async fn test_rotation_log_chain() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let multisig_key = Pubkey::new_unique();
+    let (rotation_log_key, _) = RotationLog::address(&gravity_contract_key, &program_id);
+    let consuls = [Keypair::new(), Keypair::new()];
+
+    let (mut banks_client, payer, recent_blockhash) =
+        program_test_with_contract(program_id, gravity_contract_key, multisig_key, &consuls)
+            .start()
+            .await;
+
+    let signer_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+    let mut rotated_consuls = signer_pubkeys.clone();
+    for round in 8..10 {
+        rotated_consuls.push(Pubkey::new_unique());
+        let instruction = update_consuls(
+            &program_id,
+            &payer.pubkey(),
+            &gravity_contract_key,
+            &multisig_key,
+            &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+            &rotated_consuls,
+            round,
+        )
+        .unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, &consuls[0], &consuls[1]],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // a rotation logged anywhere else than the contract's own log is refused
+    rotated_consuls.push(Pubkey::new_unique());
+    let mut instruction = update_consuls(
+        &program_id,
+        &payer.pubkey(),
+        &gravity_contract_key,
+        &multisig_key,
+        &signer_pubkeys.iter().collect::<Vec<&Pubkey>>(),
+        &rotated_consuls,
+        10,
+    )
+    .unwrap();
+    instruction.accounts[5].pubkey = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &consuls[0], &consuls[1]],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GravityError::RotationLogMismatch as u32)
+        )
+    );
+
+    let rotation_log_account = banks_client
+        .get_account(rotation_log_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let rotation_log = RotationLog::unpack(&rotation_log_account.data).unwrap();
+    assert_eq!(rotation_log.gravity_contract, gravity_contract_key);
+    assert_eq!(rotation_log.entries.len(), 2);
+    assert!(rotation_log.verify_chain());
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let gravity_contract_info =
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN]).unwrap();
+    let last_entry = rotation_log.entries.last().unwrap();
+    assert_eq!(last_entry.epoch, gravity_contract_info.consul_epoch);
+    assert_eq!(
+        last_entry.new_commitment,
+        gravity_contract_info.consul_commitment()
+    );
+    assert_eq!(last_entry.initiator, payer.pubkey());
+    assert_eq!(rotation_log_account.owner, program_id);
+}
+
+#[tokio::test]
+async fn test_emergency_replace_threshold() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let multisig_key = Pubkey::new_unique();
+    let consuls = [Keypair::new(), Keypair::new(), Keypair::new()];
+
+    let (mut banks_client, payer, recent_blockhash) =
+        program_test_with_contract(program_id, gravity_contract_key, multisig_key, &consuls)
+            .start()
+            .await;
+
+    let consul_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+    let replacement = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+    let instruction = |signers: &[Pubkey]| {
+        emergency_replace(
+            &program_id,
+            &payer.pubkey(),
+            &gravity_contract_key,
+            &multisig_key,
+            &signers.iter().collect::<Vec<&Pubkey>>(),
+            &replacement,
+            8,
+        )
+        .unwrap()
+    };
+
+    // two signers make the regular quorum but not the emergency one
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction(&consul_pubkeys[..2])],
+        Some(&payer.pubkey()),
+        &[&payer, &consuls[0], &consuls[1]],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GravityError::BelowEmergencyThreshold as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction(&consul_pubkeys)],
+        Some(&payer.pubkey()),
+        &[&payer, &consuls[0], &consuls[1], &consuls[2]],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let gravity_contract_info =
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN]).unwrap();
+    assert_eq!(gravity_contract_info.consuls, replacement);
+    assert_eq!(gravity_contract_info.last_round, 8);
+
+    // the old consuls lose the multisig along with the contract
+    let multisig_account = banks_client
+        .get_account(multisig_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let multisig = Multisig::unpack(&multisig_account.data).unwrap();
+    assert_eq!((multisig.m, multisig.n), (2, 2));
+    assert_eq!(multisig.signers[..2], replacement[..]);
+    assert!(multisig.signers[2..]
+        .iter()
+        .all(|signer| *signer == Pubkey::default()));
+}
+
+/// Stands in for the ed25519 program, which the 1.6 runtime doesn't have. The signatures it
+/// is handed here are the consuls' own, so it would pass them all.
+fn ed25519_program_stand_in(_: &Pubkey, _: &[AccountInfo], _: &[u8]) -> ProgramResult {
+    Ok(())
+}
+
+/// A step of a batch together with the ed25519 program instruction checking its signatures.
+fn signed_round(consuls: &[Keypair], round: u64) -> ((u64, SignatureBundle), Instruction) {
+    let consul_pubkeys: Vec<Pubkey> = consuls.iter().map(|consul| consul.pubkey()).collect();
+    let message = round_update_message(0, round, 0, &consul_pubkeys);
+
+    let signatures: Vec<(Pubkey, [u8; 64])> = consuls
+        .iter()
+        .map(|consul| {
+            let mut signature = [0; 64];
+            signature.copy_from_slice(consul.sign_message(&message).as_ref());
+            (consul.pubkey(), signature)
+        })
+        .collect();
+    let bundle = SignatureBundle {
+        signatures: signatures
+            .iter()
+            .enumerate()
+            .map(|(i, (_, signature))| (i as u8, *signature))
+            .collect(),
+    };
+
+    (
+        (round, bundle),
+        verify_ed25519_signatures(&message, &signatures),
+    )
+}
+
+/// The instructions of a transaction applying `rounds` in one batch.
+fn batch_transaction_instructions(
+    program_id: &Pubkey,
+    gravity_contract_key: &Pubkey,
+    consuls: &[Keypair],
+    rounds: &[u64],
+) -> Vec<Instruction> {
+    let (updates, mut instructions): (Vec<_>, Vec<_>) = rounds
+        .iter()
+        .map(|round| signed_round(consuls, *round))
+        .unzip();
+    instructions.push(batch_update_rounds(program_id, gravity_contract_key, updates).unwrap());
+
+    instructions
+}
+
+#[tokio::test]
+async fn test_batch_update_rounds() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let multisig_key = Pubkey::new_unique();
+    let consuls = [Keypair::new(), Keypair::new()];
+
+    let mut program_test =
+        program_test_with_contract(program_id, gravity_contract_key, multisig_key, &consuls);
+    program_test.add_program(
+        "ed25519_program",
+        ed25519_program::id(),
+        processor!(ed25519_program_stand_in),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // a step going backwards rejects the batch as a whole
+    let transaction = Transaction::new_signed_with_payer(
+        &batch_transaction_instructions(&program_id, &gravity_contract_key, &consuls, &[8, 7]),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(GravityError::InputRoundMismatch as u32)
+        )
+    );
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN])
+            .unwrap()
+            .last_round,
+        7
+    );
+
+    // the relayer holds no authority, a quorum of keys outside the consul set doesn't count
+    let outsiders = [Keypair::new(), Keypair::new()];
+    let (update, verify_instruction) = signed_round(&outsiders, 8);
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            verify_instruction,
+            batch_update_rounds(&program_id, &gravity_contract_key, vec![update]).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GravityError::InvalidBFTCount as u32)
+        )
+    );
+
+    // signatures the ed25519 program wasn't asked to check don't count
+    let mut instructions =
+        batch_transaction_instructions(&program_id, &gravity_contract_key, &consuls, &[8, 9]);
+    instructions.remove(1);
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GravityError::InvalidBFTCount as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &batch_transaction_instructions(&program_id, &gravity_contract_key, &consuls, &[8, 9]),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN])
+            .unwrap()
+            .last_round,
+        9
+    );
+}
+
+#[tokio::test]
+async fn test_update_round_mixed() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let ed25519_consul = Keypair::new();
+    let secp256k1_key = secp256k1::SecretKey::parse(&[1; 32]).unwrap();
+    let secp256k1_public_key = secp256k1::PublicKey::from_secret_key(&secp256k1_key);
+    let secp256k1_consul =
+        Pubkey::new(&keccak::hash(&secp256k1_public_key.serialize()[1..]).to_bytes());
+
+    let mut program_test = ProgramTest::new(
+        "solana_gravity_contract",
+        program_id,
+        processor!(GravityProcessor::process),
+    );
+    program_test.add_program(
+        "ed25519_program",
+        ed25519_program::id(),
+        processor!(ed25519_program_stand_in),
+    );
+
+    let gravity_contract_info = GravityContract {
+        initializer_pubkey: Pubkey::new_unique(),
+        bft: 2,
+        consuls: vec![ed25519_consul.pubkey(), secp256k1_consul],
+        last_round: 7,
+        multisig_account: Pubkey::new_unique(),
+        secp256k1_consuls: 0b10,
+        ..GravityContract::default()
+    };
+    let mut data = vec![0; GravityContract::LEN];
+    gravity_contract_info.pack_into_slice(&mut data);
+    program_test.add_account(
+        gravity_contract_key,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let message = round_update_message(0, 8, 0, &gravity_contract_info.consuls);
+    let mut ed25519_signature = [0; 64];
+    ed25519_signature.copy_from_slice(ed25519_consul.sign_message(&message).as_ref());
+    let (signature, recovery_id) = secp256k1::sign(
+        &secp256k1::Message::parse(&keccak::hash(&message).to_bytes()),
+        &secp256k1_key,
+    );
+    let mut secp256k1_signature = [0; 65];
+    secp256k1_signature[..64].copy_from_slice(&signature.serialize());
+    secp256k1_signature[64] = recovery_id.serialize();
+
+    let transaction_instructions = |signatures| {
+        vec![
+            verify_ed25519_signatures(&message, &[(ed25519_consul.pubkey(), ed25519_signature)]),
+            verify_secp256k1_signatures(
+                1,
+                &message,
+                &[(consul_eth_address(&secp256k1_consul), secp256k1_signature)],
+            ),
+            update_round_mixed(&program_id, &gravity_contract_key, 8, signatures).unwrap(),
+        ]
+    };
+
+    // the contract records its second consul as secp256k1, so an ed25519 signature doesn't count
+    let mut misrouted = [0; 64];
+    misrouted.copy_from_slice(&secp256k1_signature[..64]);
+    let transaction = Transaction::new_signed_with_payer(
+        &transaction_instructions(vec![
+            (0, MixedSignature::Ed25519(ed25519_signature)),
+            (1, MixedSignature::Ed25519(misrouted)),
+        ]),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(GravityError::InvalidBFTCount as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &transaction_instructions(vec![
+            (0, MixedSignature::Ed25519(ed25519_signature)),
+            (1, MixedSignature::Secp256k1(secp256k1_signature)),
+        ]),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN])
+            .unwrap()
+            .last_round,
+        8
+    );
+}
+
+/// A consul signing with `secret`, as the contract and the secp256k1 program know it.
+fn secp256k1_consul(secret: u8) -> (secp256k1::SecretKey, Pubkey) {
+    let key = secp256k1::SecretKey::parse(&[secret; 32]).unwrap();
+    let public_key = secp256k1::PublicKey::from_secret_key(&key);
+
+    (
+        key,
+        Pubkey::new(&keccak::hash(&public_key.serialize()[1..]).to_bytes()),
+    )
+}
+
+fn secp256k1_sign(message: &[u8], key: &secp256k1::SecretKey) -> [u8; 65] {
+    let (signature, recovery_id) = secp256k1::sign(
+        &secp256k1::Message::parse(&keccak::hash(message).to_bytes()),
+        key,
+    );
+    let mut recoverable = [0; 65];
+    recoverable[..64].copy_from_slice(&signature.serialize());
+    recoverable[64] = recovery_id.serialize();
+
+    recoverable
+}
+
+/// The bank of the 1.6 runtime, with none of the programs standing in for native ones: its
+/// secp256k1 program checks the signatures `NativeVerifier` reads back, and there is no
+/// ed25519 program to check the others.
+#[tokio::test]
+async fn test_update_round_on_native_programs() {
+    let program_id = Pubkey::new_unique();
+    let gravity_contract_key = Pubkey::new_unique();
+    let (first_key, first_consul) = secp256k1_consul(1);
+    let (second_key, second_consul) = secp256k1_consul(2);
+
+    let mut program_test = ProgramTest::new(
+        "solana_gravity_contract",
+        program_id,
+        processor!(GravityProcessor::process),
+    );
+    let gravity_contract_info = GravityContract {
+        initializer_pubkey: Pubkey::new_unique(),
+        bft: 2,
+        consuls: vec![first_consul, second_consul],
+        last_round: 7,
+        multisig_account: Pubkey::new_unique(),
+        secp256k1_consuls: 0b11,
+        ..GravityContract::default()
+    };
+    let mut data = vec![0; GravityContract::LEN];
+    gravity_contract_info.pack_into_slice(&mut data);
+    program_test.add_account(
+        gravity_contract_key,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let message = round_update_message(0, 8, 0, &gravity_contract_info.consuls);
+    let signatures = [
+        secp256k1_sign(&message, &first_key),
+        secp256k1_sign(&message, &second_key),
+    ];
+    let transaction_instructions = |checked_message: &[u8]| {
+        vec![
+            verify_secp256k1_signatures(
+                0,
+                checked_message,
+                &[
+                    (consul_eth_address(&first_consul), signatures[0]),
+                    (consul_eth_address(&second_consul), signatures[1]),
+                ],
+            ),
+            update_round_mixed(
+                &program_id,
+                &gravity_contract_key,
+                8,
+                vec![
+                    (0, MixedSignature::Secp256k1(signatures[0])),
+                    (1, MixedSignature::Secp256k1(signatures[1])),
+                ],
+            )
+            .unwrap(),
+        ]
+    };
+
+    // from a message other than the one signed the native program recovers other addresses,
+    // and the runtime rejects the transaction before any of it runs
+    let transaction = Transaction::new_signed_with_payer(
+        &transaction_instructions(&round_update_message(
+            0,
+            9,
+            0,
+            &gravity_contract_info.consuls,
+        )),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InvalidAccountIndex
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &transaction_instructions(&message),
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let gravity_contract_account = banks_client
+        .get_account(gravity_contract_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        GravityContract::unpack(&gravity_contract_account.data[..GravityContract::LEN])
+            .unwrap()
+            .last_round,
+        8
+    );
+
+    // nor is there an ed25519 program for the consuls of the other scheme
+    assert!(banks_client
+        .get_account(ed25519_program::id())
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,106 @@
+use solana_program::hash::hash;
+
+pub type Discriminator = [u8; 8];
+
+pub const DISCRIMINATOR_LEN: usize = 8;
+
+pub const GRAVITY_CONTRACT: Discriminator = [0x2d, 0xc4, 0xb9, 0xd6, 0x16, 0x7a, 0x8f, 0x31];
+pub const NEBULA_CONTRACT: Discriminator = [0x56, 0xd2, 0x04, 0x17, 0x40, 0x71, 0x66, 0xc7];
+pub const IBPORT_CONTRACT: Discriminator = [0x18, 0x06, 0x93, 0x6c, 0x24, 0x84, 0xe2, 0x95];
+pub const LUPORT_CONTRACT: Discriminator = [0x3d, 0x4b, 0xb1, 0xd5, 0x7e, 0x98, 0x1b, 0xa8];
+pub const PENDING_UPDATE: Discriminator = [0x18, 0xd4, 0x3d, 0x49, 0x82, 0x6f, 0x0f, 0x70];
+pub const NEBULA_REGISTRY: Discriminator = [0x08, 0xb1, 0xcd, 0x3a, 0x32, 0x3b, 0xac, 0x8a];
+pub const ROTATION_LOG: Discriminator = [0x8b, 0x4b, 0x57, 0xad, 0xf7, 0x11, 0x92, 0xdb];
+pub const CONSUL_SHARD: Discriminator = [0xb8, 0xc0, 0xef, 0x15, 0x87, 0x2a, 0x90, 0xe1];
+
+/// Every state type the programs keep in accounts, keyed by its discriminator.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AccountKind {
+    Gravity,
+    Nebula,
+    IBPort,
+    LUPort,
+    PendingUpdate,
+    NebulaRegistry,
+    RotationLog,
+    ConsulShard,
+}
+
+impl AccountKind {
+    pub const ALL: [AccountKind; 8] = [
+        AccountKind::Gravity,
+        AccountKind::Nebula,
+        AccountKind::IBPort,
+        AccountKind::LUPort,
+        AccountKind::PendingUpdate,
+        AccountKind::NebulaRegistry,
+        AccountKind::RotationLog,
+        AccountKind::ConsulShard,
+    ];
+
+    pub fn discriminator(&self) -> Discriminator {
+        match self {
+            AccountKind::Gravity => GRAVITY_CONTRACT,
+            AccountKind::Nebula => NEBULA_CONTRACT,
+            AccountKind::IBPort => IBPORT_CONTRACT,
+            AccountKind::LUPort => LUPORT_CONTRACT,
+            AccountKind::PendingUpdate => PENDING_UPDATE,
+            AccountKind::NebulaRegistry => NEBULA_REGISTRY,
+            AccountKind::RotationLog => ROTATION_LOG,
+            AccountKind::ConsulShard => CONSUL_SHARD,
+        }
+    }
+
+    /// Looks at the leading bytes of `data`, which may be a whole account.
+    pub fn from_discriminator(data: &[u8]) -> Option<AccountKind> {
+        let discriminator = data.get(..DISCRIMINATOR_LEN)?;
+
+        Self::ALL
+            .iter()
+            .find(|kind| kind.discriminator() == discriminator)
+            .copied()
+    }
+}
+
+/// Leading bytes of `sha256("account:<name>")`, the same derivation Anchor uses.
+pub fn account_discriminator(name: &str) -> Discriminator {
+    let mut discriminator = [0; DISCRIMINATOR_LEN];
+    discriminator.copy_from_slice(
+        &hash(format!("account:{}", name).as_bytes()).to_bytes()[..DISCRIMINATOR_LEN],
+    );
+    discriminator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_discriminator() {
+        assert_eq!(account_discriminator("GravityContract"), GRAVITY_CONTRACT);
+        assert_eq!(account_discriminator("NebulaContract"), NEBULA_CONTRACT);
+        assert_eq!(account_discriminator("IBPortContract"), IBPORT_CONTRACT);
+        assert_eq!(account_discriminator("LUPortContract"), LUPORT_CONTRACT);
+        assert_eq!(account_discriminator("PendingUpdate"), PENDING_UPDATE);
+        assert_eq!(account_discriminator("NebulaRegistry"), NEBULA_REGISTRY);
+        assert_eq!(account_discriminator("RotationLog"), ROTATION_LOG);
+        assert_eq!(account_discriminator("ConsulShard"), CONSUL_SHARD);
+    }
+
+    #[test]
+    fn test_account_kind_from_discriminator() {
+        for kind in AccountKind::ALL.iter() {
+            let mut data = kind.discriminator().to_vec();
+            assert_eq!(AccountKind::from_discriminator(&data), Some(*kind));
+
+            data.extend_from_slice(&[1, 2, 3]);
+            assert_eq!(AccountKind::from_discriminator(&data), Some(*kind));
+        }
+
+        assert_eq!(AccountKind::from_discriminator(&[0; 8]), None);
+        assert_eq!(
+            AccountKind::from_discriminator(&GRAVITY_CONTRACT[..7]),
+            None
+        );
+    }
+}
@@ -1,7 +1,10 @@
 // // mod gravity;
 // // #[cfg(not(feature = "no-entrypoint"))]
 // pub mod entrypoint;
+pub mod discriminator;
+pub mod merkle;
 pub mod misc;
 pub mod model;
+pub mod snapshot;
 pub mod validation;
 pub mod ports;
\ No newline at end of file
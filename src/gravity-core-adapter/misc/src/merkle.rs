@@ -0,0 +1,117 @@
+use solana_program::hash::hashv;
+
+pub type MerkleHash = [u8; 32];
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+pub fn hash_leaf(leaf: &[u8]) -> MerkleHash {
+    hashv(&[LEAF_PREFIX, leaf]).to_bytes()
+}
+
+/// Siblings are hashed in sorted order, so proofs don't have to carry the side of each node.
+pub fn hash_node(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let (first, second) = if left <= right {
+        (left, right)
+    } else {
+        (right, left)
+    };
+
+    hashv(&[NODE_PREFIX, first, second]).to_bytes()
+}
+
+pub fn verify_merkle_inclusion(root: MerkleHash, leaf: &[u8], proof: &[MerkleHash]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(hash_leaf(leaf), |node, sibling| hash_node(&node, sibling));
+
+    computed == root
+}
+
+fn next_level(level: &[MerkleHash]) -> Vec<MerkleHash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            _ => pair[0],
+        })
+        .collect()
+}
+
+/// Root over hashed `leaves`, whose unpaired last node on a level is carried up as is.
+pub fn merkle_root(leaves: &[MerkleHash]) -> MerkleHash {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level.first().copied().unwrap_or_default()
+}
+
+/// Siblings on the way from the leaf at `index` up to `merkle_root`, for `verify_merkle_inclusion`.
+pub fn merkle_proof(leaves: &[MerkleHash], mut index: usize) -> Vec<MerkleHash> {
+    let mut level = leaves.to_vec();
+    let mut proof = vec![];
+
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    proof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_merkle_inclusion() {
+        let leaves: Vec<&[u8]> = vec![b"btc", b"eth", b"sol", b"waves"];
+        let hashed: Vec<MerkleHash> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+
+        let left = hash_node(&hashed[0], &hashed[1]);
+        let right = hash_node(&hashed[2], &hashed[3]);
+        let root = hash_node(&left, &right);
+
+        assert!(verify_merkle_inclusion(root, b"btc", &[hashed[1], right]));
+        assert!(verify_merkle_inclusion(root, b"sol", &[hashed[3], left]));
+
+        assert!(!verify_merkle_inclusion(root, b"doge", &[hashed[1], right]));
+        assert!(!verify_merkle_inclusion(root, b"btc", &[hashed[2], right]));
+        assert!(!verify_merkle_inclusion(root, b"btc", &[hashed[1]]));
+        // an inner node can't pass for a leaf
+        assert!(!verify_merkle_inclusion(root, &left, &[right]));
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let leaves: Vec<&[u8]> = vec![b"btc", b"eth", b"sol", b"waves", b"doge"];
+        let hashed: Vec<MerkleHash> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+
+        let root = merkle_root(&hashed);
+        assert_eq!(
+            merkle_root(&hashed[..4]),
+            hash_node(
+                &hash_node(&hashed[0], &hashed[1]),
+                &hash_node(&hashed[2], &hashed[3]),
+            )
+        );
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert!(verify_merkle_inclusion(
+                root,
+                leaf,
+                &merkle_proof(&hashed, index)
+            ));
+        }
+        assert!(!verify_merkle_inclusion(
+            root,
+            b"doge",
+            &merkle_proof(&hashed, 0)
+        ));
+    }
+}
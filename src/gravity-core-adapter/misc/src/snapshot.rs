@@ -0,0 +1,116 @@
+use std::convert::TryInto;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::discriminator::AccountKind;
+
+/// Leads every snapshot file.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"GSNP";
+
+/// Layout version written by `export`, bumped whenever the entry layout changes.
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// A program account as held in a snapshot, its data exactly as stored on chain.
+pub type SnapshotAccount = (Pubkey, AccountKind, Vec<u8>);
+
+/// Writes the magic, the version and the account count, then one entry per account:
+/// its key, the position of its kind in `AccountKind::ALL` and its length-prefixed data.
+/// Kinds are tagged explicitly since not every state type leads with its discriminator.
+pub fn export(accounts: &[SnapshotAccount]) -> Vec<u8> {
+    let mut out = SNAPSHOT_MAGIC.to_vec();
+    out.push(SNAPSHOT_VERSION);
+    out.extend_from_slice(&(accounts.len() as u32).to_le_bytes());
+
+    for (key, kind, data) in accounts.iter() {
+        let tag = AccountKind::ALL
+            .iter()
+            .position(|other| other == kind)
+            .unwrap() as u8;
+
+        out.extend_from_slice(key.as_ref());
+        out.push(tag);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    out
+}
+
+fn take<'a>(src: &mut &'a [u8], len: usize) -> Result<&'a [u8], ProgramError> {
+    if src.len() < len {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let (head, tail) = src.split_at(len);
+    *src = tail;
+    Ok(head)
+}
+
+fn take_u32(src: &mut &[u8]) -> Result<usize, ProgramError> {
+    Ok(u32::from_le_bytes(take(src, 4)?.try_into().unwrap()) as usize)
+}
+
+/// Reads back a file written by `export`, refusing unknown versions and kinds, truncated
+/// entries and trailing bytes. The data of each account is returned as is, for the unpacker
+/// of its kind.
+pub fn import(mut src: &[u8]) -> Result<Vec<SnapshotAccount>, ProgramError> {
+    if take(&mut src, SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if take(&mut src, 1)?[0] != SNAPSHOT_VERSION {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let count = take_u32(&mut src)?;
+    let mut accounts = Vec::with_capacity(count.min(src.len()));
+    for _ in 0..count {
+        let key = Pubkey::new(take(&mut src, 32)?);
+        let kind = *AccountKind::ALL
+            .get(take(&mut src, 1)?[0] as usize)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let len = take_u32(&mut src)?;
+
+        accounts.push((key, kind, take(&mut src, len)?.to_vec()));
+    }
+
+    if !src.is_empty() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_rejects_malformed() {
+        let accounts = vec![
+            (Pubkey::new_unique(), AccountKind::Gravity, vec![1, 2, 3]),
+            (Pubkey::new_unique(), AccountKind::Nebula, vec![]),
+        ];
+        let snapshot = export(&accounts);
+        assert_eq!(import(&snapshot), Ok(accounts));
+
+        let mut wrong_version = snapshot.clone();
+        wrong_version[SNAPSHOT_MAGIC.len()] = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            import(&wrong_version),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut unknown_kind = snapshot.clone();
+        unknown_kind[SNAPSHOT_MAGIC.len() + 1 + 4 + 32] = AccountKind::ALL.len() as u8;
+        assert_eq!(import(&unknown_kind), Err(ProgramError::InvalidAccountData));
+
+        assert_eq!(
+            import(&snapshot[..snapshot.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut trailing = snapshot;
+        trailing.push(0);
+        assert_eq!(import(&trailing), Err(ProgramError::InvalidAccountData));
+    }
+}
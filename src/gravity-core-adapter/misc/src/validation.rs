@@ -5,6 +5,8 @@ use std::ops::Range;
 use arrayref::{array_ref};
 
 use solana_program::{
+    account_info::AccountInfo,
+    msg,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
@@ -116,6 +118,117 @@ impl PDAResolver {
     }
 }
 
+/// What a handler takes the account at some position for. Flags set here are required,
+/// while an account may always carry more than its role asks for.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct AccountRole {
+    pub name: &'static str,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountRole {
+    pub const fn readonly(name: &'static str) -> Self {
+        AccountRole {
+            name,
+            is_signer: false,
+            is_writable: false,
+        }
+    }
+
+    pub const fn writable(name: &'static str) -> Self {
+        AccountRole {
+            name,
+            is_signer: false,
+            is_writable: true,
+        }
+    }
+
+    pub const fn signer(name: &'static str) -> Self {
+        AccountRole {
+            name,
+            is_signer: true,
+            is_writable: false,
+        }
+    }
+}
+
+/// What an account at some position lacks for its role.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AccountLayoutIssue {
+    Missing,
+    NotSigner,
+    NotWritable,
+}
+
+/// The first account `verify_account_layout` refused, by position and the role expected there.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct AccountLayoutError {
+    pub position: usize,
+    pub role: AccountRole,
+    pub issue: AccountLayoutIssue,
+}
+
+impl From<AccountLayoutError> for ProgramError {
+    fn from(e: AccountLayoutError) -> Self {
+        match e.issue {
+            AccountLayoutIssue::Missing => ProgramError::NotEnoughAccountKeys,
+            AccountLayoutIssue::NotSigner => ProgramError::MissingRequiredSignature,
+            AccountLayoutIssue::NotWritable => ProgramError::InvalidArgument,
+        }
+    }
+}
+
+/// Checks the leading `accounts` against `expected`, position by position, and logs the
+/// first account that lacks a flag its role requires, which the error also carries.
+/// Accounts past `expected` are left to the handler.
+pub fn verify_account_layout(
+    accounts: &[AccountInfo],
+    expected: &[AccountRole],
+) -> Result<(), AccountLayoutError> {
+    if accounts.len() < expected.len() {
+        msg!(
+            "Error: expected {} accounts, got {}; missing {}",
+            expected.len(),
+            accounts.len(),
+            expected[accounts.len()].name
+        );
+        return Err(AccountLayoutError {
+            position: accounts.len(),
+            role: expected[accounts.len()],
+            issue: AccountLayoutIssue::Missing,
+        });
+    }
+
+    for (position, (account, role)) in accounts.iter().zip(expected.iter()).enumerate() {
+        let issue = if role.is_signer && !account.is_signer {
+            msg!(
+                "Error: account {} ({}) must be a signer",
+                position,
+                role.name
+            );
+            AccountLayoutIssue::NotSigner
+        } else if role.is_writable && !account.is_writable {
+            msg!(
+                "Error: account {} ({}) must be writable",
+                position,
+                role.name
+            );
+            AccountLayoutIssue::NotWritable
+        } else {
+            continue;
+        };
+
+        return Err(AccountLayoutError {
+            position,
+            role: *role,
+            issue,
+        });
+    }
+
+    Ok(())
+}
+
 pub fn validate_pubkey_match<E: std::error::Error>(
     multisig_owner_keys: &Vec<Pubkey>,
     data_provider: &Pubkey,
@@ -142,4 +255,87 @@ pub trait TokenMintConstrained<E> {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_account_layout() {
+        let owner = Pubkey::new_unique();
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let (mut signer_lamports, mut writable_lamports) = (0, 0);
+        let (mut signer_data, mut writable_data) = (vec![], vec![]);
+
+        let signer = AccountInfo::new(
+            &keys[0],
+            true,
+            false,
+            &mut signer_lamports,
+            &mut signer_data,
+            &owner,
+            false,
+            0,
+        );
+        let writable = AccountInfo::new(
+            &keys[1],
+            false,
+            true,
+            &mut writable_lamports,
+            &mut writable_data,
+            &owner,
+            false,
+            0,
+        );
+        let expected = [
+            AccountRole::signer("initializer"),
+            AccountRole::writable("contract"),
+        ];
+
+        assert_eq!(
+            verify_account_layout(&[signer.clone(), writable.clone()], &expected),
+            Ok(())
+        );
+        let error =
+            verify_account_layout(&[writable.clone(), signer.clone()], &expected).unwrap_err();
+        assert_eq!(
+            error,
+            AccountLayoutError {
+                position: 0,
+                role: expected[0],
+                issue: AccountLayoutIssue::NotSigner,
+            }
+        );
+        assert_eq!(
+            ProgramError::from(error),
+            ProgramError::MissingRequiredSignature
+        );
+
+        let error =
+            verify_account_layout(&[signer.clone(), signer.clone()], &expected).unwrap_err();
+        assert_eq!((error.position, error.role.name), (1, "contract"));
+        assert_eq!(error.issue, AccountLayoutIssue::NotWritable);
+        assert_eq!(ProgramError::from(error), ProgramError::InvalidArgument);
+
+        let error = verify_account_layout(std::slice::from_ref(&signer), &expected).unwrap_err();
+        assert_eq!((error.position, error.role.name), (1, "contract"));
+        assert_eq!(error.issue, AccountLayoutIssue::Missing);
+        assert_eq!(
+            ProgramError::from(error),
+            ProgramError::NotEnoughAccountKeys
+        );
+
+        // extra privileges and trailing accounts pass
+        assert_eq!(
+            verify_account_layout(
+                &[signer.clone(), writable.clone(), signer],
+                &[
+                    AccountRole::readonly("payer"),
+                    AccountRole::readonly("contract")
+                ]
+            ),
+            Ok(())
+        );
+    }
+}
@@ -12,6 +12,7 @@ pub fn allocation_by_instruction_index(
             NebulaContractInstruction::DATA_TYPE_ALLOC_RANGE,
             NebulaContractInstruction::PUBKEY_ALLOC,
             NebulaContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
+            NebulaContractInstruction::PUBKEY_ALLOC,
         ],
         // UpdateOracles
         1 => vec![
@@ -40,6 +40,9 @@ pub enum NebulaError {
 
     #[error("Pulse validation order mismatch")]
     PulseValidationOrderMismatch,
+
+    #[error("Gravity contract is not the one the Nebula is linked to")]
+    GravityContractMismatch,
 }
 
 impl From<NebulaError> for ProgramError {
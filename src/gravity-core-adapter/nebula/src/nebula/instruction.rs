@@ -21,6 +21,8 @@ pub enum NebulaContractInstruction {
         gravity_contract_program_id: Pubkey,
         initial_oracles: Vec<Pubkey>,
         oracles_bft: u8,
+        /// The program the Gravity contract is owned by, following the oracles.
+        gravity_program_id: Pubkey,
     },
     UpdateOracles {
         new_oracles: Vec<Pubkey>,
@@ -102,12 +104,14 @@ impl NebulaContractInstruction {
                 let gravity_contract_program_id =
                     extract_from_range(rest, ranges[2].clone(), |x| Pubkey::new(x))?;
                 let initial_oracles = retrieve_oracles(rest, ranges[3].clone(), oracles_bft)?;
+                let gravity_program_id = extract_from_range(rest, ranges[4].clone(), Pubkey::new)?;
 
                 Self::InitContract {
                     nebula_data_type,
                     gravity_contract_program_id,
                     initial_oracles,
                     oracles_bft,
+                    gravity_program_id,
                 }
             }
             // UpdateOracles
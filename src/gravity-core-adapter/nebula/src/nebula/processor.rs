@@ -18,7 +18,7 @@ use solana_gravity_contract::gravity::{
 };
 
 use crate::nebula::instruction::NebulaContractInstruction;
-use crate::nebula::state::{NebulaContract};
+use crate::nebula::state::{validate_gravity_reference, NebulaContract};
 use crate::nebula::error::NebulaError;
 
 // use solana_port_contract::ibport::instruction::attach_value;
@@ -36,6 +36,7 @@ impl NebulaProcessor {
         gravity_contract_data_account: &Pubkey,
         initial_oracles: Vec<Pubkey>,
         oracles_bft: u8,
+        gravity_program_id: &Pubkey,
         _program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -59,12 +60,23 @@ impl NebulaProcessor {
 
         nebula_contract_info.oracles = initial_oracles.clone();
         nebula_contract_info.gravity_contract = *gravity_contract_data_account;
+        nebula_contract_info.gravity_program = *gravity_program_id;
 
         msg!("instantiated nebula contract");
 
         msg!("picking multisig account");
         let nebula_contract_multisig_account = next_account_info(account_info_iter)?;
 
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+        if gravity_contract_account.key != gravity_contract_data_account {
+            return Err(GravityError::InvalidGravityReference.into());
+        }
+        validate_gravity_reference(
+            nebula_contract_account.key,
+            gravity_program_id,
+            gravity_contract_account,
+        )?;
+
         msg!("initializing multisig program");
         MiscProcessor::process_init_multisig(
             &nebula_contract_multisig_account,
@@ -356,6 +368,7 @@ impl NebulaProcessor {
                 gravity_contract_program_id,
                 initial_oracles,
                 oracles_bft,
+                gravity_program_id,
             } => {
                 msg!("Instruction: Init Nebula Contract");
 
@@ -365,6 +378,7 @@ impl NebulaProcessor {
                     &gravity_contract_program_id,
                     initial_oracles,
                     oracles_bft,
+                    &gravity_program_id,
                     program_id,
                 )
             }
@@ -424,3 +438,137 @@ impl NebulaProcessor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use solana_gravity_contract::gravity::state::GravityContract;
+
+    fn init_data(
+        gravity_contract: &Pubkey,
+        oracles: &[Pubkey],
+        bft: u8,
+        gravity_program: &Pubkey,
+    ) -> Vec<u8> {
+        let mut data = vec![0, bft, 0];
+        data.extend_from_slice(gravity_contract.as_ref());
+        for oracle in oracles {
+            data.extend_from_slice(oracle.as_ref());
+        }
+        data.extend_from_slice(gravity_program.as_ref());
+        data
+    }
+
+    /// The Nebula an init naming `named_gravity` and `named_program` leaves, with the account
+    /// passed as the Gravity contract being `gravity_key` owned by `gravity_owner`.
+    fn run_init(
+        gravity_key: &Pubkey,
+        gravity_owner: &Pubkey,
+        named_gravity: &Pubkey,
+        named_program: &Pubkey,
+    ) -> Result<NebulaContract, ProgramError> {
+        let program_id = Pubkey::new_unique();
+        let initializer_key = Pubkey::new_unique();
+        let nebula_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let oracles = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let system_program = Pubkey::default();
+
+        let (mut initializer_lamports, mut initializer_data) = (0, vec![]);
+        let (mut nebula_lamports, mut nebula_data) = (0, vec![0; NebulaContract::LEN]);
+        let (mut multisig_lamports, mut multisig_data) = (0, vec![0; Multisig::LEN]);
+        let (mut gravity_lamports, mut gravity_data) = (0, vec![0; GravityContract::LEN]);
+        GravityContract::default().pack_into_slice(&mut gravity_data);
+
+        let accounts = [
+            AccountInfo::new(
+                &initializer_key,
+                true,
+                false,
+                &mut initializer_lamports,
+                &mut initializer_data,
+                &system_program,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &nebula_key,
+                false,
+                true,
+                &mut nebula_lamports,
+                &mut nebula_data,
+                &program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &multisig_key,
+                false,
+                true,
+                &mut multisig_lamports,
+                &mut multisig_data,
+                &program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                gravity_key,
+                false,
+                false,
+                &mut gravity_lamports,
+                &mut gravity_data,
+                gravity_owner,
+                false,
+                0,
+            ),
+        ];
+
+        NebulaProcessor::process(
+            &program_id,
+            &accounts,
+            &init_data(named_gravity, &oracles, 2, named_program),
+        )?;
+        drop(accounts);
+
+        NebulaContract::unpack_from_slice(&nebula_data)
+    }
+
+    #[test]
+    fn test_init_validates_gravity_account() {
+        let gravity_key = Pubkey::new_unique();
+        let gravity_program_id = Pubkey::new_unique();
+
+        let nebula = run_init(
+            &gravity_key,
+            &gravity_program_id,
+            &gravity_key,
+            &gravity_program_id,
+        )
+        .unwrap();
+        assert_eq!(nebula.gravity_contract, gravity_key);
+        assert_eq!(nebula.gravity_program, gravity_program_id);
+        // the passed account has to be the Gravity contract the instruction names
+        assert_eq!(
+            run_init(
+                &Pubkey::new_unique(),
+                &gravity_program_id,
+                &gravity_key,
+                &gravity_program_id
+            )
+            .unwrap_err(),
+            GravityError::InvalidGravityReference.into()
+        );
+        // and be owned by the Gravity program it names, not just carry its bytes
+        assert_eq!(
+            run_init(
+                &gravity_key,
+                &Pubkey::new_unique(),
+                &gravity_key,
+                &gravity_program_id
+            )
+            .unwrap_err(),
+            GravityError::SpoofedAccount.into()
+        );
+    }
+}
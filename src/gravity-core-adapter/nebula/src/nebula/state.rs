@@ -2,6 +2,7 @@
 
 
 use solana_program::{
+    account_info::AccountInfo,
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -11,9 +12,15 @@ use solana_program::{
 use gravity_misc::model::{
     AbstractRecordHandler, RecordHandler, DataType, PulseID, SubscriptionID
 };
+use gravity_misc::discriminator::AccountKind;
 use gravity_misc::validation::validate_pubkey_match;
 
-use solana_gravity_contract::gravity::state::PartialStorage;
+use solana_gravity_contract::gravity::{
+    error::GravityError,
+    signature::SignatureBundle,
+    state::{GravityContract, HashAlgo, PartialStorage},
+    verifier::SignatureVerifier,
+};
 
 use crate::nebula::error::NebulaError;
 
@@ -33,6 +40,13 @@ pub struct Pulse {
     // pub height: u64,
 }
 
+impl Pulse {
+    /// Whether `raw_data` is what the consuls committed to, hashed the way its source chain does.
+    pub fn verify_data(&self, raw_data: &[u8], algo: HashAlgo) -> bool {
+        self.data_hash == algo.digest(raw_data)
+    }
+}
+
 pub type NebulaQueue<T> = Vec<T>;
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
@@ -53,6 +67,9 @@ pub struct NebulaContract {
 
     pub is_state_initialized: bool,
     pub initializer_pubkey: Pubkey,
+
+    /// The program `gravity_contract` has to be owned by, named at init.
+    pub gravity_program: Pubkey,
 }
 
 impl PartialStorage for NebulaContract {
@@ -193,4 +210,416 @@ impl NebulaContract {
             None => return Err(NebulaError::InvalidSubscriptionID.into()),
         }
     }
+
+    /// Refuses data for a source nobody has subscribed to, before any of it is persisted.
+    pub fn assert_known_source(&self, source_id: &SubscriptionID) -> Result<(), ProgramError> {
+        if !self.subscriptions_map.contains_key(source_id) {
+            msg!(
+                "Error: no subscription registered for source {:?}",
+                source_id
+            );
+            return Err(GravityError::UnknownDataSource.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks a Nebula round update against the Gravity contract the Nebula is linked to: the
+/// account has to be that one, owned by the Gravity program the Nebula was initialized with
+/// and holding a Gravity contract, whose consuls have to reach quorum.
+pub fn verify_nebula_update(
+    verifier: &dyn SignatureVerifier,
+    nebula: &NebulaContract,
+    gravity_contract_account: &AccountInfo,
+    bundle: &SignatureBundle,
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    if nebula.gravity_contract != *gravity_contract_account.key {
+        return Err(NebulaError::GravityContractMismatch.into());
+    }
+    if *gravity_contract_account.owner != nebula.gravity_program {
+        msg!(
+            "Error: {} is owned by {}, not the Gravity program",
+            gravity_contract_account.key,
+            gravity_contract_account.owner
+        );
+        return Err(GravityError::SpoofedAccount.into());
+    }
+
+    let data = gravity_contract_account.try_borrow_data()?;
+    if AccountKind::from_discriminator(&data) != Some(AccountKind::Gravity) {
+        return Err(GravityError::InvalidGravityReference.into());
+    }
+    let gravity = GravityContract::unpack(
+        data.get(..GravityContract::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?,
+    )?;
+
+    gravity.verify_multisig(verifier, message, bundle)
+}
+
+/// The Gravity contract a Nebula takes its trust from has to be one: an account owned by
+/// `gravity_program`, leading with the Gravity discriminator, and not the Nebula itself.
+pub fn validate_gravity_reference(
+    nebula: &Pubkey,
+    gravity_program: &Pubkey,
+    gravity_contract_account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if gravity_contract_account.owner != gravity_program {
+        msg!(
+            "Error: {} is owned by {}, not the Gravity program",
+            gravity_contract_account.key,
+            gravity_contract_account.owner
+        );
+        return Err(GravityError::SpoofedAccount.into());
+    }
+
+    let data = gravity_contract_account.try_borrow_data()?;
+    if gravity_contract_account.key == nebula
+        || AccountKind::from_discriminator(&data) != Some(AccountKind::Gravity)
+    {
+        msg!(
+            "Error: {} is not a Gravity contract",
+            gravity_contract_account.key
+        );
+        return Err(GravityError::InvalidGravityReference.into());
+    }
+
+    Ok(())
+}
+
+/// Whether the Gravity and Nebula rounds are at most `tolerance` apart, either way round.
+pub fn rounds_in_sync(gravity: &GravityContract, nebula: &NebulaContract, tolerance: u64) -> bool {
+    let (a, b) = (gravity.last_round, nebula.last_round);
+
+    a.max(b) - a.min(b) <= tolerance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gravity_misc::snapshot;
+    use solana_gravity_contract::gravity::{
+        signature::signed_message, state::ConsulSignature, verifier::SoftwareVerifier,
+    };
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn sign(consul: &Keypair, message: &[u8]) -> ConsulSignature {
+        let mut signature = [0; 64];
+        signature.copy_from_slice(consul.sign_message(message).as_ref());
+        signature
+    }
+
+    #[test]
+    fn test_verify_nebula_update() {
+        let consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let gravity_program = Pubkey::new_unique();
+        let gravity_key = Pubkey::new_unique();
+        let gravity = GravityContract {
+            bft: 2,
+            consuls: consuls.iter().map(|consul| consul.pubkey()).collect(),
+            ..GravityContract::default()
+        };
+        let nebula = NebulaContract {
+            gravity_contract: gravity_key,
+            gravity_program,
+            ..NebulaContract::default()
+        };
+
+        let message = &signed_message(gravity.chain_id, b"pulse 8");
+        let bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&consuls[0], message)),
+                (2, sign(&consuls[2], message)),
+            ],
+        };
+
+        let spoofed_consuls: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let spoofed = GravityContract {
+            consuls: spoofed_consuls
+                .iter()
+                .map(|consul| consul.pubkey())
+                .collect(),
+            ..gravity.clone()
+        };
+        let spoofed_bundle = SignatureBundle {
+            signatures: vec![
+                (0, sign(&spoofed_consuls[0], message)),
+                (1, sign(&spoofed_consuls[1], message)),
+            ],
+        };
+
+        let packed = |contract: &GravityContract| {
+            let mut data = vec![0; GravityContract::LEN];
+            contract.pack_into_slice(&mut data);
+            data
+        };
+        let impostor_program = Pubkey::new_unique();
+        let other_key = Pubkey::new_unique();
+        let mut not_gravity = packed(&gravity);
+        not_gravity[..8].copy_from_slice(&AccountKind::Nebula.discriminator());
+
+        // (account key, owner, data, bundle, outcome)
+        let cases = [
+            (
+                &gravity_key,
+                &gravity_program,
+                packed(&gravity),
+                &bundle,
+                Ok(()),
+            ),
+            // a Gravity contract the Nebula isn't linked to, even with quorum among its own consuls
+            (
+                &other_key,
+                &gravity_program,
+                packed(&spoofed),
+                &spoofed_bundle,
+                Err(NebulaError::GravityContractMismatch.into()),
+            ),
+            // the linked key, but written by another program
+            (
+                &gravity_key,
+                &impostor_program,
+                packed(&spoofed),
+                &spoofed_bundle,
+                Err(GravityError::SpoofedAccount.into()),
+            ),
+            (
+                &gravity_key,
+                &gravity_program,
+                not_gravity,
+                &bundle,
+                Err(GravityError::InvalidGravityReference.into()),
+            ),
+            (
+                &gravity_key,
+                &gravity_program,
+                vec![],
+                &bundle,
+                Err(GravityError::InvalidGravityReference.into()),
+            ),
+            // the linked contract doesn't take another set's signatures
+            (
+                &gravity_key,
+                &gravity_program,
+                packed(&gravity),
+                &spoofed_bundle,
+                Err(GravityError::InvalidBFTCount.into()),
+            ),
+        ];
+
+        for (key, owner, mut data, bundle, expected) in cases {
+            let mut lamports = 0;
+            let gravity_contract_account =
+                AccountInfo::new(key, false, false, &mut lamports, &mut data, owner, false, 0);
+
+            assert_eq!(
+                verify_nebula_update(
+                    &SoftwareVerifier,
+                    &nebula,
+                    &gravity_contract_account,
+                    bundle,
+                    message
+                ),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_pulse_verify_data() {
+        let raw_data = b"price 1.0042".to_vec();
+
+        for (algo, digest) in [
+            (
+                HashAlgo::Sha256,
+                solana_program::hash::hash(&raw_data).to_bytes(),
+            ),
+            (
+                HashAlgo::Keccak256,
+                solana_program::keccak::hash(&raw_data).to_bytes(),
+            ),
+        ] {
+            let pulse = Pulse {
+                data_hash: digest.to_vec(),
+            };
+            assert!(pulse.verify_data(&raw_data, algo));
+            assert!(!pulse.verify_data(b"price 1.0043", algo));
+        }
+
+        let pulse = Pulse {
+            data_hash: solana_program::hash::hash(&raw_data).to_bytes().to_vec(),
+        };
+        assert!(!pulse.verify_data(&raw_data, HashAlgo::Keccak256));
+    }
+
+    #[test]
+    fn test_validate_gravity_reference() {
+        let program_id = Pubkey::new_unique();
+        let gravity_program_id = Pubkey::new_unique();
+        let nebula_key = Pubkey::new_unique();
+        let gravity_key = Pubkey::new_unique();
+        let mut lamports = 0;
+
+        // the owner alone isn't enough: the data has to be a Gravity contract too
+        let mut nebula_data = vec![0; NebulaContract::LEN];
+        NebulaContract {
+            gravity_contract: nebula_key,
+            ..NebulaContract::default()
+        }
+        .pack_into_slice(&mut nebula_data);
+        let nebula_account = AccountInfo::new(
+            &nebula_key,
+            false,
+            true,
+            &mut lamports,
+            &mut nebula_data,
+            &gravity_program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            validate_gravity_reference(&nebula_key, &gravity_program_id, &nebula_account),
+            Err(GravityError::InvalidGravityReference.into())
+        );
+
+        let mut lamports = 0;
+        let mut gravity_data = vec![0; GravityContract::LEN];
+        GravityContract::default().pack_into_slice(&mut gravity_data);
+        let gravity_account = AccountInfo::new(
+            &gravity_key,
+            false,
+            false,
+            &mut lamports,
+            &mut gravity_data,
+            &gravity_program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            validate_gravity_reference(&nebula_key, &gravity_program_id, &gravity_account),
+            Ok(())
+        );
+        // the same bytes under any other owner are an impostor
+        let mut impostor_lamports = 0;
+        let mut impostor_data = gravity_account.data.borrow().to_vec();
+        let impostor_account = AccountInfo::new(
+            &gravity_key,
+            false,
+            false,
+            &mut impostor_lamports,
+            &mut impostor_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            validate_gravity_reference(&nebula_key, &gravity_program_id, &impostor_account),
+            Err(GravityError::SpoofedAccount.into())
+        );
+        // a Gravity contract still can't stand in for the Nebula referencing it
+        assert_eq!(
+            validate_gravity_reference(&gravity_key, &gravity_program_id, &gravity_account),
+            Err(GravityError::InvalidGravityReference.into())
+        );
+    }
+
+    #[test]
+    fn test_assert_known_source() {
+        let mut nebula = NebulaContract::default();
+        let source_id: SubscriptionID = [7; 16];
+        assert_eq!(
+            nebula.assert_known_source(&source_id),
+            Err(GravityError::UnknownDataSource.into())
+        );
+
+        nebula
+            .subscribe(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                1,
+                10,
+                &source_id,
+            )
+            .unwrap();
+        assert_eq!(nebula.assert_known_source(&source_id), Ok(()));
+        assert_eq!(
+            nebula.assert_known_source(&[8; 16]),
+            Err(GravityError::UnknownDataSource.into())
+        );
+    }
+
+    #[test]
+    fn test_rounds_in_sync() {
+        let gravity = GravityContract {
+            last_round: 100,
+            ..GravityContract::default()
+        };
+        let nebula_at = |last_round| NebulaContract {
+            last_round,
+            ..NebulaContract::default()
+        };
+
+        assert!(rounds_in_sync(&gravity, &nebula_at(100), 0));
+        assert!(rounds_in_sync(&gravity, &nebula_at(97), 3));
+        assert!(rounds_in_sync(&gravity, &nebula_at(103), 3));
+
+        assert!(!rounds_in_sync(&gravity, &nebula_at(96), 3));
+        assert!(!rounds_in_sync(&gravity, &nebula_at(104), 3));
+        assert!(!rounds_in_sync(
+            &gravity,
+            &nebula_at(u64::MAX),
+            u64::MAX - 101
+        ));
+        assert!(rounds_in_sync(
+            &gravity,
+            &nebula_at(u64::MAX),
+            u64::MAX - 100
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let gravity = GravityContract {
+            bft: 2,
+            consuls: vec![
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            ],
+            last_round: 7,
+            ..GravityContract::default()
+        };
+        let nebula = NebulaContract {
+            oracles: vec![Pubkey::new_unique()],
+            bft: 1,
+            gravity_contract: Pubkey::new_unique(),
+            last_round: 5,
+            is_state_initialized: true,
+            ..NebulaContract::default()
+        };
+
+        let mut gravity_data = vec![0; GravityContract::LEN];
+        gravity.pack_into_slice(&mut gravity_data);
+        let mut nebula_data = vec![0; NebulaContract::LEN];
+        nebula.pack_into_slice(&mut nebula_data);
+
+        let accounts = vec![
+            (nebula.gravity_contract, AccountKind::Gravity, gravity_data),
+            (Pubkey::new_unique(), AccountKind::Nebula, nebula_data),
+        ];
+        let imported = snapshot::import(&snapshot::export(&accounts)).unwrap();
+        assert_eq!(imported, accounts);
+
+        assert_eq!(
+            GravityContract::unpack_from_slice(&imported[0].2).unwrap(),
+            gravity
+        );
+        assert_eq!(
+            NebulaContract::unpack_from_slice(&imported[1].2).unwrap(),
+            nebula
+        );
+    }
 }